@@ -1,27 +1,17 @@
 // Server configuration
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
-use std::net::{SocketAddr, UdpSocket};
+use std::net::{IpAddr, Ipv4Addr};
+use std::net::UdpSocket;
 use std::str::FromStr;
 
-use rolldhcp::dhcp::DhcpLease;
-use rolldhcp::dhcp::DhcpServer;
-
-// Add hashmap
-use std::collections::HashMap;
-// use rolldhcp::dhcp::{options, packet, server};
+use rolldhcp::dhcp::{DhcpOption, DhcpServer, FileLeaseStore, LeaseStore, Server};
+use rolldhcp::ipnet::Ipv4Net;
 
 // All use ipv4 at present
 const IPV4_ONLY: bool = true;
 const SERVER_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
 const SUBNET_MASK: IpAddr = IpAddr::V4(Ipv4Addr::new(255, 255, 255, 0));
 
-const SUNET_IP_RANGE: [IpAddr; 2] = [
-    IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2)),
-    IpAddr::V4(Ipv4Addr::new(192, 168, 1, 254)),
-];
+const SUNET_IP_RANGE: [Ipv4Addr; 2] = [Ipv4Addr::new(192, 168, 1, 2), Ipv4Addr::new(192, 168, 1, 254)];
 
 const DNS_IPS: [IpAddr; 2] = [
     // Google DNS servers
@@ -36,7 +26,7 @@ const BROADCAST_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 255));
 // Lease time and number
 const LEASE_FILE_PATH: &str = "rolldhcp.leases";
 const LEASE_DURATION_SECS: u32 = 86400;
-const LEASE_NUM: u32 = 252;
+const LEASE_NUM: u32 = 253;
 
 // TODO: Derived constants
 // const IP_START_NUM: u32 = u32::from_be_bytes(IP_START);
@@ -54,10 +44,6 @@ fn main() {
     .expect("Could not bind to address");
     socket.set_broadcast(true).expect("Could not set broadcast");
 
-    // Set lease storage
-    let mut leases_vec: Vec<DhcpLease> = vec![];
-    // let mut leases_lut: HashMap<IpAddr, (MacAddress, Option<u64>)>;
-
     // Learning
     // Dismasq leases
 
@@ -68,42 +54,28 @@ fn main() {
     // 4.	Hostname: The name of the device, if known.
     // 5.	Client ID: Optionally used to identify the client separately from its hardware address. if known. The client-ID is used as the computer's unique-ID in preference to the MAC address, if it's available. Some DHCP clients provide it, and some don't. The ones that do normally derive it from the MAC address unless explicity configured, but it could be something like a serial number, which would protect a computer from losing its identify if **the network interface were replaced**.
 
-    // Get the leases from persistent storage
-    let lease_file = File::open(LEASE_FILE_PATH).expect("Could not open lease file");
-    // Read the lease_file according to the format mentioned above
-    let reader = BufReader::new(lease_file);
-    for line in reader.lines() {
-        if let Err(e) = line {
-            eprintln!("Error reading lease file: {}", e);
-            return;
-        }
-        let line = line.unwrap();
-        let l = DhcpLease::from_str(&line).expect("Failed to parse lease");
-        leases_vec.push(l);
-    }
+    // Get the leases from persistent storage, written back on every change
+    let store = FileLeaseStore::open(LEASE_FILE_PATH).expect("Could not open lease file");
 
     // The protocol defines
     // that the key will be (IP-subnet-number, hardware-address) unless the
     // client explicitly supplies an identifier using the 'client
     // identifier' option.
-
-    let leases_lut: HashMap<IpAddr, DhcpLease> = leases_vec
-        .into_iter()
-        .map(|lease| (lease.get_ip().clone(), lease))
-        .collect::<HashMap<IpAddr, DhcpLease>>();
-    // move ownership to leases_lut
     // compile only in debug mode
-    #[cfg(debug_print)]
+    #[cfg(debug_assertions)]
     {
-        println!("[DEBUG PRINT] Leases: {:?}", leases_lut);
+        println!("[DEBUG PRINT] Leases: {:?}", store.iter().collect::<Vec<_>>());
     }
-    let dhcp_lease_server = DhcpServer {
-        leases: leases_lut,
-        last_leases: 0,
-        server: Server
-    };
-    // start udp server
-    // dhcp_lease_server.start(socket);
+    let subnet = Ipv4Net::from_str("192.168.1.0/24").expect("Invalid subnet");
+    let server = Server::new(socket, SERVER_IP, BROADCAST_IP);
+    let mut dhcp_lease_server = DhcpServer::with_store(server, subnet, SUNET_IP_RANGE[0], LEASE_NUM, store);
+    dhcp_lease_server.set_default_lease_duration(LEASE_DURATION_SECS);
+    dhcp_lease_server.set_options(vec![
+        DhcpOption::SubnetMask(SUBNET_MASK),
+        DhcpOption::Routers(vec![ROUTER_IP]),
+        DhcpOption::DomainNameServers(DNS_IPS.to_vec()),
+    ]);
     // loop serve
-    server::Server::serve(socket, SERVER_IP, BROADCAST_IP, dhcp_lease_server);
+    let err = dhcp_lease_server.serve();
+    eprintln!("Server stopped: {}", err);
 }
@@ -0,0 +1,164 @@
+use crate::dhcp::packet::{ConvertResult, Packet};
+use crate::dhcp::{is_ipxe_client, DhcpOption, VendorOptionSpace, VendorValue};
+use std::net::Ipv4Addr;
+
+// Client System Architecture types (RFC 4578 section 2.1, IANA registry)
+pub const ARCH_X86_BIOS: u16 = 0;
+pub const ARCH_X86_UEFI: u16 = 6;
+pub const ARCH_X64_UEFI: u16 = 7;
+// Sent by x64 UEFI firmware in place of 7
+pub const ARCH_X64_UEFI_ALT: u16 = 9;
+pub const ARCH_ARM32_UEFI: u16 = 10;
+pub const ARCH_ARM64_UEFI: u16 = 11;
+pub const ARCH_X86_UEFI_HTTP: u16 = 15;
+pub const ARCH_X64_UEFI_HTTP: u16 = 16;
+pub const ARCH_ARM32_UEFI_HTTP: u16 = 18;
+pub const ARCH_ARM64_UEFI_HTTP: u16 = 19;
+
+// Vendor classes (option 60) of network boot firmware, e.g.
+// "PXEClient:Arch:00007:UNDI:003016" or "HTTPClient:Arch:00016:UNDI:003001"
+pub const PXE_CLIENT: &str = "PXEClient";
+pub const HTTP_CLIENT: &str = "HTTPClient";
+
+/// Architecture of a network boot client, from option 93 or else from the
+/// `Arch` field of its vendor class. A PXE client without either is a BIOS.
+pub fn client_architecture(in_packet: &Packet) -> Option<u16> {
+    if let Some(types) = in_packet.get_client_architecture() {
+        return types.first().copied();
+    }
+    let class = std::str::from_utf8(in_packet.get_class_identifier()?).ok()?;
+    let rest = class
+        .strip_prefix(PXE_CLIENT)
+        .or_else(|| class.strip_prefix(HTTP_CLIENT))?;
+    match rest.strip_prefix(":Arch:") {
+        Some(arch) => arch.get(..5)?.parse().ok(),
+        None if class.starts_with(PXE_CLIENT) => Some(ARCH_X86_BIOS),
+        None => None,
+    }
+}
+
+/// What a network boot client of some architectures is told to load.
+///
+/// The boot file goes in the `file` header field and option 67, the next
+/// server in `siaddr` and the TFTP server name in option 66. For HTTP boot
+/// the boot file is the URL of the image.
+///
+/// To chainload iPXE, the boot file is the iPXE binary and the iPXE script is
+/// the URL given to iPXE once it runs. Giving iPXE its own binary again would
+/// make it load itself forever.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BootRule {
+    architectures: Vec<u16>,
+    boot_file: String,
+    next_server: Option<Ipv4Addr>,
+    tftp_server_name: Option<String>,
+    ipxe_script: Option<String>,
+    pxe: VendorOptionSpace,
+}
+
+impl BootRule {
+    pub fn new(architectures: &[u16], boot_file: &str) -> BootRule {
+        BootRule {
+            architectures: architectures.to_vec(),
+            boot_file: boot_file.to_string(),
+            next_server: None,
+            tftp_server_name: None,
+            ipxe_script: None,
+            pxe: VendorOptionSpace::pxe(),
+        }
+    }
+    pub fn set_next_server(&mut self, next_server: Ipv4Addr) {
+        self.next_server = Some(next_server);
+    }
+    pub fn set_tftp_server_name(&mut self, name: &str) {
+        self.tftp_server_name = Some(name.to_string());
+    }
+    pub fn set_ipxe_script(&mut self, url: &str) {
+        self.ipxe_script = Some(url.to_string());
+    }
+    /// Add a PXE sub-option sent in option 43, e.g. `PXE_DISCOVERY_CONTROL`.
+    pub fn add_pxe_sub_option(&mut self, code: u8, value: VendorValue) -> ConvertResult<()> {
+        self.pxe.add_sub_option(code, value)
+    }
+    pub fn get_architectures(&self) -> &[u16] {
+        &self.architectures
+    }
+    pub fn get_boot_file(&self) -> &str {
+        &self.boot_file
+    }
+    pub fn get_next_server(&self) -> Option<Ipv4Addr> {
+        self.next_server
+    }
+    pub fn get_tftp_server_name(&self) -> &Option<String> {
+        &self.tftp_server_name
+    }
+    pub fn get_ipxe_script(&self) -> &Option<String> {
+        &self.ipxe_script
+    }
+    /// The iPXE script for clients running iPXE, the boot file otherwise.
+    pub fn boot_file_for(&self, in_packet: &Packet) -> &str {
+        match &self.ipxe_script {
+            Some(script) if is_ipxe_client(in_packet) => script,
+            _ => &self.boot_file,
+        }
+    }
+
+    /// Set next-server and the boot file in the header of the `reply` to
+    /// `in_packet`. A boot file too long for the `file` field is only sent in
+    /// option 67.
+    pub fn apply(&self, in_packet: &Packet, reply: &mut Packet) {
+        if let Some(next_server) = self.next_server {
+            reply.set_siaddr(u32::from(next_server));
+        }
+        // On error set_file leaves the field empty
+        let _ = reply.set_file(self.boot_file_for(in_packet));
+    }
+
+    /// Options of the reply to the boot client `in_packet`.
+    pub fn options(&self, in_packet: &Packet) -> ConvertResult<Vec<DhcpOption>> {
+        let mut options = Vec::new();
+        // Boot firmware ignores offers without its vendor class (PXE
+        // specification 2.1, UEFI specification 24.7)
+        let class = in_packet.get_class_identifier().map(|c| c.as_slice());
+        for vendor in [PXE_CLIENT, HTTP_CLIENT] {
+            if class.is_some_and(|c| c.starts_with(vendor.as_bytes())) {
+                options.push(DhcpOption::ClassIdentifier(vendor.as_bytes().to_vec()));
+            }
+        }
+        if let Some(name) = &self.tftp_server_name {
+            options.push(DhcpOption::TftpServerName(name.clone()));
+        }
+        options.push(DhcpOption::BootfileName(
+            self.boot_file_for(in_packet).to_string(),
+        ));
+        if !self.pxe.get_sub_options().is_empty() {
+            options.push(DhcpOption::VendorSpecificInformation(self.pxe.encode()?));
+        }
+        Ok(options)
+    }
+}
+
+/// Boot rules by client architecture, the first matching rule applies.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct BootPolicy {
+    rules: Vec<BootRule>,
+}
+
+impl BootPolicy {
+    pub fn new() -> BootPolicy {
+        BootPolicy { rules: vec![] }
+    }
+    pub fn add_rule(&mut self, rule: BootRule) {
+        self.rules.push(rule);
+    }
+    pub fn get_rules(&self) -> &[BootRule] {
+        &self.rules
+    }
+    /// The rule for `in_packet`, `None` for clients that are not booting.
+    pub fn select(&self, in_packet: &Packet) -> Option<&BootRule> {
+        let arch = client_architecture(in_packet)?;
+        self.rules
+            .iter()
+            .find(|rule| rule.architectures.contains(&arch))
+    }
+}
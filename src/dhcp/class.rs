@@ -0,0 +1,83 @@
+use crate::dhcp::packet::Packet;
+use crate::dhcp::{client_architecture, DhcpOption};
+
+/// A test on the fields and options of a client message.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ClassMatch {
+    VendorClass(Vec<u8>), // option 60 starts with the bytes, e.g. "MSFT" or "PXEClient"
+    UserClass(Vec<u8>),   // option 77 holds the class
+    MacPrefix(Vec<u8>),   // hardware address starts with the bytes, e.g. an OUI
+    CircuitId(Vec<u8>),   // option 82 Circuit-ID equals the bytes
+    Architecture(u16),    // client system architecture, see boot.rs
+    All(Vec<ClassMatch>),
+    Any(Vec<ClassMatch>),
+    Not(Box<ClassMatch>),
+}
+
+impl ClassMatch {
+    pub fn matches(&self, in_packet: &Packet) -> bool {
+        match self {
+            ClassMatch::VendorClass(prefix) => in_packet
+                .get_class_identifier()
+                .is_some_and(|class| class.starts_with(prefix)),
+            ClassMatch::UserClass(class) => in_packet
+                .get_user_classes()
+                .is_some_and(|classes| classes.contains(&class.as_slice())),
+            ClassMatch::MacPrefix(prefix) => in_packet
+                .get_client_mac()
+                .is_some_and(|mac| mac.get_octets().starts_with(prefix)),
+            ClassMatch::CircuitId(id) => in_packet.get_circuit_id() == Some(id.as_slice()),
+            ClassMatch::Architecture(arch) => client_architecture(in_packet) == Some(*arch),
+            ClassMatch::All(matches) => matches.iter().all(|m| m.matches(in_packet)),
+            ClassMatch::Any(matches) => matches.iter().any(|m| m.matches(in_packet)),
+            ClassMatch::Not(m) => !m.matches(in_packet),
+        }
+    }
+}
+
+/// A named group of clients, e.g. phones or printers, with the settings they
+/// get.
+///
+/// The lease time and options of a class replace those of the subnet and the
+/// server. Pools name the classes they allow or deny, see
+/// [`AddressPool::allow_class`](crate::dhcp::AddressPool::allow_class).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ClientClass {
+    name: String,
+    rule: ClassMatch,
+    lease_duration: Option<u32>,
+    options: Vec<DhcpOption>,
+}
+
+impl ClientClass {
+    pub fn new(name: &str, rule: ClassMatch) -> ClientClass {
+        ClientClass {
+            name: name.to_string(),
+            rule,
+            lease_duration: None,
+            options: vec![],
+        }
+    }
+    /// Set the lease time in seconds.
+    pub fn set_lease_duration(&mut self, secs: u32) {
+        self.lease_duration = Some(secs);
+    }
+    pub fn set_options(&mut self, options: Vec<DhcpOption>) {
+        self.options = options;
+    }
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    pub fn get_rule(&self) -> &ClassMatch {
+        &self.rule
+    }
+    pub fn get_lease_duration(&self) -> Option<u32> {
+        self.lease_duration
+    }
+    pub fn get_options(&self) -> &[DhcpOption] {
+        &self.options
+    }
+    pub fn matches(&self, in_packet: &Packet) -> bool {
+        self.rule.matches(in_packet)
+    }
+}
@@ -0,0 +1,252 @@
+use crate::dhcp::packet::{ConvertPacketError, ConvertResult};
+use crate::dhcp::CLIENT_FQDN;
+
+// DNS names in options use the wire format of RFC 1035 section 3.1: a
+// sequence of length-prefixed labels of at most 63 octets. A name ending in
+// '.' is fully qualified and encoded with the terminating zero-length label,
+// a name without it is partial and encoded without (RFC 4702 section 2.3.1).
+pub const MAX_LABEL_LEN: usize = 63;
+
+/// Encodes `name` in DNS wire format, see above for partial names.
+pub fn encode_dns_name(code: u8, name: &str) -> ConvertResult<Vec<u8>> {
+    let (labels, fully_qualified) = match name.strip_suffix('.') {
+        Some(labels) => (labels, true),
+        None => (name, false),
+    };
+    let mut data = Vec::with_capacity(name.len() + 2);
+    if !labels.is_empty() {
+        for label in labels.split('.') {
+            if label.is_empty() || label.len() > MAX_LABEL_LEN {
+                return Err(ConvertPacketError::InvalidOptionValue(code));
+            }
+            data.push(label.len() as u8);
+            data.extend_from_slice(label.as_bytes());
+        }
+    }
+    if fully_qualified {
+        data.push(0);
+    }
+    Ok(data)
+}
+
+/// Decodes a single uncompressed name in DNS wire format that fills `data`.
+pub fn decode_dns_name(code: u8, data: &[u8]) -> ConvertResult<String> {
+    let mut name = String::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        let len = data[i] as usize;
+        if len == 0 {
+            // The root label ends the name and must be the last octet
+            if i + 1 != data.len() {
+                return Err(ConvertPacketError::InvalidOptionLength(code));
+            }
+            name.push('.');
+            return Ok(name);
+        }
+        if len > MAX_LABEL_LEN {
+            return Err(ConvertPacketError::InvalidOptionValue(code));
+        }
+        let label = data
+            .get(i + 1..i + 1 + len)
+            .ok_or(ConvertPacketError::InvalidOptionLength(code))?;
+        let label = std::str::from_utf8(label).map_err(|_| ConvertPacketError::NonUtf8String)?;
+        if !name.is_empty() {
+            name.push('.');
+        }
+        name.push_str(label);
+        i += 1 + len;
+    }
+    Ok(name)
+}
+
+// Compression pointers (RFC 1035 section 4.1.4) in the Domain Search option
+// count from the start of the (concatenated) option payload (RFC 3397)
+const POINTER_MASK: u8 = 0xc0;
+const MAX_POINTER: usize = 0x3fff;
+const MAX_NAME_LEN: usize = 255;
+
+/// Encodes a list of domain names as in the Domain Search option: each name
+/// fully qualified, with suffixes already written replaced by a pointer.
+pub fn encode_domain_search(code: u8, names: &[String]) -> ConvertResult<Vec<u8>> {
+    if names.is_empty() {
+        return Err(ConvertPacketError::InvalidOptionLength(code));
+    }
+    let mut data = Vec::new();
+    // Offsets of the suffixes written so far, compared case-insensitively
+    let mut suffixes: Vec<(String, usize)> = Vec::new();
+    for name in names {
+        let name = name.strip_suffix('.').unwrap_or(name);
+        if name.is_empty() || name.len() + 2 > MAX_NAME_LEN {
+            return Err(ConvertPacketError::InvalidOptionValue(code));
+        }
+        let labels: Vec<&str> = name.split('.').collect();
+        let mut pointer = None;
+        for i in 0..labels.len() {
+            let label = labels[i];
+            if label.is_empty() || label.len() > MAX_LABEL_LEN {
+                return Err(ConvertPacketError::InvalidOptionValue(code));
+            }
+            let suffix = labels[i..].join(".").to_ascii_lowercase();
+            if let Some((_, offset)) = suffixes.iter().find(|(s, _)| *s == suffix) {
+                pointer = Some(*offset);
+                break;
+            }
+            if data.len() <= MAX_POINTER {
+                suffixes.push((suffix, data.len()));
+            }
+            data.push(label.len() as u8);
+            data.extend_from_slice(label.as_bytes());
+        }
+        match pointer {
+            Some(offset) => data.extend_from_slice(&(offset as u16 | 0xc000).to_be_bytes()),
+            None => data.push(0),
+        }
+    }
+    Ok(data)
+}
+
+/// Decodes the Domain Search option, following compression pointers.
+///
+/// Every pointer followed while reading one name must point before the
+/// previous one, so a pointer loop is reported instead of followed forever.
+pub fn decode_domain_search(code: u8, data: &[u8]) -> ConvertResult<Vec<String>> {
+    if data.is_empty() {
+        return Err(ConvertPacketError::InvalidOptionLength(code));
+    }
+    let mut names = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let (name, next) = read_compressed_name(code, data, i)?;
+        names.push(name);
+        i = next;
+    }
+    Ok(names)
+}
+
+// Read the name starting at `start`, returns it (without the trailing '.')
+// and the offset following it
+fn read_compressed_name(code: u8, data: &[u8], start: usize) -> ConvertResult<(String, usize)> {
+    let mut name = String::new();
+    let mut pos = start;
+    let mut next = None;
+    let mut limit = start;
+    loop {
+        let len = *data
+            .get(pos)
+            .ok_or(ConvertPacketError::InvalidOptionLength(code))?;
+        match len & POINTER_MASK {
+            0 if len == 0 => {
+                pos += 1;
+                break;
+            }
+            0 => {
+                let label = data
+                    .get(pos + 1..pos + 1 + len as usize)
+                    .ok_or(ConvertPacketError::InvalidOptionLength(code))?;
+                let label =
+                    std::str::from_utf8(label).map_err(|_| ConvertPacketError::NonUtf8String)?;
+                if !name.is_empty() {
+                    name.push('.');
+                }
+                name.push_str(label);
+                if name.len() + 2 > MAX_NAME_LEN {
+                    return Err(ConvertPacketError::InvalidOptionValue(code));
+                }
+                pos += 1 + len as usize;
+            }
+            POINTER_MASK => {
+                let low = *data
+                    .get(pos + 1)
+                    .ok_or(ConvertPacketError::InvalidOptionLength(code))?;
+                let target = u16::from_be_bytes([len & !POINTER_MASK, low]) as usize;
+                if target >= limit {
+                    return Err(ConvertPacketError::InvalidOptionValue(code));
+                }
+                next.get_or_insert(pos + 2);
+                limit = target;
+                pos = target;
+            }
+            // 0x40 and 0x80 label types are reserved
+            _ => return Err(ConvertPacketError::InvalidOptionValue(code)),
+        }
+    }
+    Ok((name, next.unwrap_or(pos)))
+}
+
+// Client FQDN flags (RFC 4702 section 2.1)
+pub const FQDN_FLAG_S: u8 = 0x01; // the server should perform the A RR update
+pub const FQDN_FLAG_O: u8 = 0x02; // the server has overridden the client's S flag
+pub const FQDN_FLAG_E: u8 = 0x04; // the name is in DNS wire format
+pub const FQDN_FLAG_N: u8 = 0x08; // the server should not perform any DNS update
+
+/// The Client FQDN option (81) described in [RFC 4702](https://datatracker.ietf.org/doc/html/rfc4702).
+///
+/// A client uses it to tell the server its name and who should update DNS,
+/// the server answers with the name it will use and its decision. The name
+/// follows the trailing '.' convention of [`encode_dns_name`].
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ClientFqdn {
+    flags: u8,
+    rcode1: u8,
+    rcode2: u8,
+    name: String,
+}
+
+impl ClientFqdn {
+    pub fn new(flags: u8, rcode1: u8, rcode2: u8, name: String) -> ClientFqdn {
+        ClientFqdn {
+            flags,
+            rcode1,
+            rcode2,
+            name,
+        }
+    }
+    pub fn get_flags(&self) -> u8 {
+        self.flags
+    }
+    pub fn get_rcode1(&self) -> u8 {
+        self.rcode1
+    }
+    pub fn get_rcode2(&self) -> u8 {
+        self.rcode2
+    }
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    pub fn server_update(&self) -> bool {
+        self.flags & FQDN_FLAG_S != 0
+    }
+    pub fn server_override(&self) -> bool {
+        self.flags & FQDN_FLAG_O != 0
+    }
+    pub fn encoded(&self) -> bool {
+        self.flags & FQDN_FLAG_E != 0
+    }
+    pub fn no_update(&self) -> bool {
+        self.flags & FQDN_FLAG_N != 0
+    }
+
+    pub fn decode(data: &[u8]) -> ConvertResult<ClientFqdn> {
+        let (flags, rcode1, rcode2, name) = match data {
+            [flags, rcode1, rcode2, name @ ..] => (*flags, *rcode1, *rcode2, name),
+            _ => return Err(ConvertPacketError::InvalidOptionLength(CLIENT_FQDN)),
+        };
+        let name = match flags & FQDN_FLAG_E != 0 {
+            true => decode_dns_name(CLIENT_FQDN, name)?,
+            // Deprecated ASCII encoding, still sent by some old clients
+            false => {
+                String::from_utf8(name.to_vec()).map_err(|_| ConvertPacketError::NonUtf8String)?
+            }
+        };
+        Ok(ClientFqdn::new(flags, rcode1, rcode2, name))
+    }
+
+    pub fn encode(&self) -> ConvertResult<Vec<u8>> {
+        let mut data = vec![self.flags, self.rcode1, self.rcode2];
+        match self.encoded() {
+            true => data.extend_from_slice(&encode_dns_name(CLIENT_FQDN, &self.name)?),
+            false => data.extend_from_slice(self.name.as_bytes()),
+        }
+        Ok(data)
+    }
+}
@@ -0,0 +1,169 @@
+use crate::dhcp::lease::{
+    format_expiry, format_placeholder, parse_expiry, parse_hex_id, parse_placeholder,
+};
+use crate::dhcp::{DhcpLease, LeaseError, ParseDhcpLeaseError, StorageError, StorageResult};
+use std::fmt;
+use std::net::{IpAddr, Ipv6Addr};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A DHCPv6 lease line of a dnsmasq.leases file: expiry time, IAID (with a
+/// `T` prefix for a temporary address), IPv6 address, hostname and client
+/// DUID.
+///
+/// The server hands out no IPv6 addresses, these leases are kept so that a
+/// file taken over from dnsmasq is written back whole.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DnsmasqLease6 {
+    expiry: Duration,
+    iaid: u32,
+    temporary: bool,
+    ip: Ipv6Addr,
+    hostname: Option<String>,
+    duid: Option<String>,
+}
+
+impl DnsmasqLease6 {
+    pub fn get_expiry(&self) -> &Duration {
+        &self.expiry
+    }
+    pub fn get_iaid(&self) -> u32 {
+        self.iaid
+    }
+    pub fn is_temporary(&self) -> bool {
+        self.temporary
+    }
+    pub fn get_ip(&self) -> &Ipv6Addr {
+        &self.ip
+    }
+    pub fn get_hostname(&self) -> &Option<String> {
+        &self.hostname
+    }
+    pub fn get_duid(&self) -> &Option<String> {
+        &self.duid
+    }
+}
+
+impl FromStr for DnsmasqLease6 {
+    type Err = LeaseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() != 5 {
+            return Err(LeaseError::from(ParseDhcpLeaseError::InvalidFieldsLength));
+        }
+        let expiry = parse_expiry(fields[0])?;
+        let (temporary, iaid) = match fields[1].strip_prefix('T') {
+            Some(iaid) => (true, iaid),
+            None => (false, fields[1]),
+        };
+        let iaid = iaid.parse().map_err(|_| ParseDhcpLeaseError::InvalidIaid)?;
+        let ip = fields[2]
+            .parse()
+            .map_err(|_| ParseDhcpLeaseError::InvalidIpAddr)?;
+        let duid = match parse_placeholder(fields[4]) {
+            Some(duid) => Some(parse_hex_id(&duid).ok_or(ParseDhcpLeaseError::InvalidClientId)?),
+            None => None,
+        };
+        Ok(DnsmasqLease6 {
+            expiry,
+            iaid,
+            temporary,
+            ip,
+            hostname: parse_placeholder(fields[3]),
+            duid,
+        })
+    }
+}
+
+impl fmt::Display for DnsmasqLease6 {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "{} {}{} {} {} {}",
+            format_expiry(&self.expiry),
+            if self.temporary { "T" } else { "" },
+            self.iaid,
+            self.ip,
+            format_placeholder(&self.hostname),
+            format_placeholder(&self.duid),
+        )
+    }
+}
+
+/// The content of a dnsmasq.leases file: DHCPv4 leases, see
+/// [`DhcpLease::from_str`], then the server DUID line `duid <DUID>` and the
+/// DHCPv6 leases, see [`DnsmasqLease6`].
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct DnsmasqLeases {
+    leases: Vec<DhcpLease>,
+    duid: Option<String>,
+    leases6: Vec<DnsmasqLease6>,
+}
+
+impl DnsmasqLeases {
+    pub fn new(leases: Vec<DhcpLease>) -> DnsmasqLeases {
+        DnsmasqLeases {
+            leases,
+            ..Default::default()
+        }
+    }
+    /// Read a lease file. Every line must be a lease or the DUID line, the
+    /// first line that is not is reported with its number.
+    pub fn parse(content: &str) -> StorageResult<DnsmasqLeases> {
+        let mut file = DnsmasqLeases::default();
+        for (i, line) in content.lines().enumerate() {
+            let invalid = |e: LeaseError| StorageError::InvalidLease(i + 1, e);
+            let fields = line.split_whitespace().collect::<Vec<&str>>();
+            match fields.as_slice() {
+                [] => {}
+                ["duid", duid] => {
+                    if file.duid.is_some() {
+                        return Err(invalid(ParseDhcpLeaseError::DuplicateDuid.into()));
+                    }
+                    let duid = parse_hex_id(duid)
+                        .ok_or_else(|| invalid(ParseDhcpLeaseError::InvalidClientId.into()))?;
+                    file.duid = Some(duid);
+                }
+                // The address tells a DHCPv4 lease from a DHCPv6 one, like dnsmasq does
+                [_, _, ip, ..] => match ip.parse::<IpAddr>() {
+                    Ok(IpAddr::V6(_)) => file.leases6.push(line.parse().map_err(invalid)?),
+                    _ => file.leases.push(line.parse().map_err(invalid)?),
+                },
+                _ => return Err(invalid(ParseDhcpLeaseError::InvalidFieldsLength.into())),
+            }
+        }
+        Ok(file)
+    }
+    pub fn set_leases(&mut self, leases: Vec<DhcpLease>) {
+        self.leases = leases;
+    }
+    pub fn get_leases(&self) -> &[DhcpLease] {
+        &self.leases
+    }
+    pub fn into_leases(self) -> Vec<DhcpLease> {
+        self.leases
+    }
+    /// The DUID of the DHCPv6 server that wrote the file.
+    pub fn get_duid(&self) -> &Option<String> {
+        &self.duid
+    }
+    pub fn get_leases6(&self) -> &[DnsmasqLease6] {
+        &self.leases6
+    }
+}
+
+impl fmt::Display for DnsmasqLeases {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for lease in &self.leases {
+            writeln!(fmt, "{}", lease)?;
+        }
+        if let Some(duid) = &self.duid {
+            writeln!(fmt, "duid {}", duid)?;
+        }
+        for lease in &self.leases6 {
+            writeln!(fmt, "{}", lease)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+use crate::dhcp::dns::*;
+use crate::dhcp::packet::Packet;
+
+/// Who performs DNS updates for a client's name (RFC 4702 section 4).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum DnsUpdateMode {
+    /// Follow the client: the server updates the A record only when the client
+    /// sets the S flag, and nothing when it sets the N flag.
+    Client,
+    /// The server updates the A and PTR records, overriding the client.
+    Server,
+    /// Nobody updates DNS on behalf of DHCP.
+    NoUpdate,
+}
+
+/// How the server turns the name a client sends (Host Name option 12 or Client
+/// FQDN option 81) into the hostname stored in its lease.
+///
+/// Names are reduced to their first label, renamed by the rewrite rules and,
+/// unless disabled, sanitised to a valid RFC 1123 host label. The configured
+/// domain makes up the fully qualified name returned in option 81.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct HostnamePolicy {
+    dns_update: DnsUpdateMode,
+    domain: Option<String>,
+    sanitize: bool,
+    rewrites: Vec<(String, String)>,
+}
+
+/// Result of [`HostnamePolicy::decide`] for one client message.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct HostnameDecision {
+    hostname: Option<String>,
+    fqdn: Option<String>,
+    server_updates: bool,
+    reply: Option<ClientFqdn>,
+}
+
+impl HostnameDecision {
+    /// Hostname to store in the lease.
+    pub fn get_hostname(&self) -> &Option<String> {
+        &self.hostname
+    }
+    /// Fully qualified name, when a domain is configured.
+    pub fn get_fqdn(&self) -> &Option<String> {
+        &self.fqdn
+    }
+    /// Whether the server is responsible for the A record update.
+    pub fn server_updates(&self) -> bool {
+        self.server_updates
+    }
+    /// Client FQDN option for the reply, only when the client sent one.
+    pub fn get_reply(&self) -> &Option<ClientFqdn> {
+        &self.reply
+    }
+}
+
+impl Default for HostnamePolicy {
+    fn default() -> Self {
+        HostnamePolicy::new(DnsUpdateMode::Client, None)
+    }
+}
+
+impl HostnamePolicy {
+    pub fn new(dns_update: DnsUpdateMode, domain: Option<String>) -> HostnamePolicy {
+        HostnamePolicy {
+            dns_update,
+            domain: domain.map(|d| d.trim_matches('.').to_ascii_lowercase()),
+            sanitize: true,
+            rewrites: vec![],
+        }
+    }
+    pub fn set_sanitize(&mut self, sanitize: bool) {
+        self.sanitize = sanitize;
+    }
+    /// Rename a client that calls itself `from` (case-insensitive) to `to`.
+    pub fn add_rewrite(&mut self, from: &str, to: &str) {
+        self.rewrites
+            .push((from.to_ascii_lowercase(), to.to_string()));
+    }
+    pub fn get_dns_update(&self) -> DnsUpdateMode {
+        self.dns_update
+    }
+    pub fn get_domain(&self) -> &Option<String> {
+        &self.domain
+    }
+
+    /// Decide the hostname and the DNS update responsibility for `in_packet`.
+    pub fn decide(&self, in_packet: &Packet) -> HostnameDecision {
+        let client_fqdn = in_packet.get_client_fqdn();
+        // Option 81 takes precedence over option 12 (RFC 4702 section 3)
+        let requested = match client_fqdn {
+            Some(fqdn) if !fqdn.get_name().trim_matches('.').is_empty() => Some(fqdn.get_name()),
+            _ => in_packet.get_host_name().map(|name| name.as_str()),
+        };
+        let hostname = requested.and_then(|name| self.rewrite(name));
+        let fqdn = match (&hostname, &self.domain) {
+            (Some(host), Some(domain)) => Some(format!("{}.{}.", host, domain)),
+            _ => None,
+        };
+
+        let (server_updates, reply) = match client_fqdn {
+            Some(client) => {
+                let (s, o, n) = match self.dns_update {
+                    DnsUpdateMode::Server => (true, !client.server_update(), false),
+                    DnsUpdateMode::Client => (
+                        client.server_update() && !client.no_update(),
+                        false,
+                        client.no_update(),
+                    ),
+                    DnsUpdateMode::NoUpdate => (false, client.server_update(), true),
+                };
+                let mut flags = client.get_flags() & FQDN_FLAG_E;
+                if s {
+                    flags |= FQDN_FLAG_S;
+                }
+                if o {
+                    flags |= FQDN_FLAG_O;
+                }
+                if n {
+                    flags |= FQDN_FLAG_N;
+                }
+                let name = fqdn
+                    .clone()
+                    .or_else(|| hostname.clone())
+                    .unwrap_or_default();
+                // Servers set both RCODE fields to 255 (RFC 4702 section 2.2)
+                (s, Some(ClientFqdn::new(flags, 255, 255, name)))
+            }
+            None => (self.dns_update == DnsUpdateMode::Server, None),
+        };
+        HostnameDecision {
+            // Without a name there is nothing to register
+            server_updates: server_updates && hostname.is_some(),
+            hostname,
+            fqdn,
+            reply,
+        }
+    }
+
+    // First label of the requested name, renamed and sanitised
+    fn rewrite(&self, name: &str) -> Option<String> {
+        let host = name.split('.').next()?;
+        let host = match self
+            .rewrites
+            .iter()
+            .find(|(from, _)| from.eq_ignore_ascii_case(host))
+        {
+            Some((_, to)) => to.as_str(),
+            None => host,
+        };
+        match self.sanitize {
+            true => sanitize_hostname(host),
+            false if host.is_empty() => None,
+            false => Some(host.to_string()),
+        }
+    }
+}
+
+/// Turn `name` into a valid host label (RFC 1123 section 2.1): lowercase
+/// letters, digits and hyphens, not starting or ending with a hyphen and at
+/// most 63 octets. Other characters become hyphens, `None` if nothing is left.
+pub fn sanitize_hostname(name: &str) -> Option<String> {
+    let label: String = name
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_lowercase(),
+            false => '-',
+        })
+        .take(MAX_LABEL_LEN)
+        .collect();
+    let label = label.trim_matches('-');
+    match label.is_empty() {
+        true => None,
+        false => Some(label.to_string()),
+    }
+}
@@ -0,0 +1,74 @@
+use crate::dhcp::packet::{ConvertResult, Packet};
+use crate::dhcp::{
+    encode_sub_options, VendorOptionSpace, VendorOptionType, VendorSubOption, IPXE_ENCAPSULATED,
+};
+
+// iPXE sub-options of option 175 (include/ipxe/dhcp.h in the iPXE sources)
+pub const IPXE_PRIORITY: u8 = 1;
+pub const IPXE_KEEP_SAN: u8 = 8;
+pub const IPXE_SKIP_SAN_BOOT: u8 = 9;
+pub const IPXE_NO_PXEDHCP: u8 = 176;
+pub const IPXE_BUS_ID: u8 = 177;
+pub const IPXE_USERNAME: u8 = 190;
+pub const IPXE_PASSWORD: u8 = 191;
+pub const IPXE_VERSION: u8 = 235;
+// Feature indicators sent by the client, each set to 1 when the feature is
+// built in
+pub const IPXE_FEATURE_PXEEXT: u8 = 16;
+pub const IPXE_FEATURE_ISCSI: u8 = 17;
+pub const IPXE_FEATURE_AOE: u8 = 18;
+pub const IPXE_FEATURE_HTTP: u8 = 19;
+pub const IPXE_FEATURE_HTTPS: u8 = 20;
+pub const IPXE_FEATURE_TFTP: u8 = 21;
+pub const IPXE_FEATURE_DNS: u8 = 23;
+pub const IPXE_FEATURE_BZIMAGE: u8 = 24;
+pub const IPXE_FEATURE_PXE: u8 = 33;
+pub const IPXE_FEATURE_EFI: u8 = 36;
+pub const IPXE_FEATURE_MENU: u8 = 39;
+
+// User class sent by iPXE in option 77
+pub const IPXE_USER_CLASS: &[u8] = b"iPXE";
+
+fn ipxe_option_space() -> VendorOptionSpace {
+    let mut space = VendorOptionSpace::new("ipxe", b"");
+    space.define(IPXE_PRIORITY, VendorOptionType::U8);
+    space.define(IPXE_KEEP_SAN, VendorOptionType::U8);
+    space.define(IPXE_SKIP_SAN_BOOT, VendorOptionType::U8);
+    space.define(IPXE_NO_PXEDHCP, VendorOptionType::U8);
+    space.define(IPXE_USERNAME, VendorOptionType::String);
+    space.define(IPXE_PASSWORD, VendorOptionType::String);
+    for feature in [
+        IPXE_FEATURE_PXEEXT,
+        IPXE_FEATURE_ISCSI,
+        IPXE_FEATURE_AOE,
+        IPXE_FEATURE_HTTP,
+        IPXE_FEATURE_HTTPS,
+        IPXE_FEATURE_TFTP,
+        IPXE_FEATURE_DNS,
+        IPXE_FEATURE_BZIMAGE,
+        IPXE_FEATURE_PXE,
+        IPXE_FEATURE_EFI,
+        IPXE_FEATURE_MENU,
+    ] {
+        space.define(feature, VendorOptionType::U8);
+    }
+    space
+}
+
+/// Parses the payload of option 175, sub-options of unknown type stay bytes.
+pub fn decode_ipxe_options(data: &[u8]) -> ConvertResult<Vec<VendorSubOption>> {
+    ipxe_option_space().decode_option(IPXE_ENCAPSULATED, data)
+}
+
+pub fn encode_ipxe_options(sub_options: &[VendorSubOption]) -> ConvertResult<Vec<u8>> {
+    encode_sub_options(IPXE_ENCAPSULATED, sub_options, false)
+}
+
+/// Whether `in_packet` comes from iPXE rather than from the PXE firmware of
+/// the network card: iPXE sends the user class `iPXE` and option 175.
+pub fn is_ipxe_client(in_packet: &Packet) -> bool {
+    let user_class = in_packet.get_user_classes().is_some_and(|classes| {
+        classes.contains(&IPXE_USER_CLASS)
+    });
+    user_class || in_packet.get_ipxe_options().is_some()
+}
@@ -0,0 +1,386 @@
+use crate::dhcp::lease::parse_hex_id;
+use crate::dhcp::{
+    format_client_id, BindingState, DhcpLease, LeaseError, ParseDhcpLeaseError, StorageError,
+    StorageResult, INFINITE_EXPIRY,
+};
+use crate::macaddress::MacAddress;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Read the leases of an ISC dhcpd.leases file, `lease <address> { ... }`
+/// blocks with their start and end times, binding state, hardware address,
+/// uid and client-hostname.
+///
+/// dhcpd appends a block on every change, the last block of an address is
+/// its lease. Other declarations, e.g. `host` or `failover peer`, and
+/// statements of a lease not carried by [`DhcpLease`] are skipped. A lease
+/// without hardware address, e.g. a free one, has the nil MAC address, a
+/// lease of another hardware type than Ethernet is skipped with a warning.
+/// Carry over the bindings with [`DhcpLease::is_active`].
+pub fn parse_isc_leases(content: &str) -> StorageResult<Vec<DhcpLease>> {
+    let mut tokens = Tokens::new(content)?;
+    // The last lease of each address, in the order the addresses first appear
+    let mut leases: Vec<Option<DhcpLease>> = vec![];
+    let mut index: HashMap<IpAddr, usize> = HashMap::new();
+    while let Some(token) = tokens.next() {
+        let line = token.line;
+        let invalid =
+            |e: ParseDhcpLeaseError| StorageError::InvalidLease(line, LeaseError::from(e));
+        if token.value != Value::Word("lease".to_string()) {
+            tokens.skip_statement(token).map_err(invalid)?;
+            continue;
+        }
+        let (ip, lease) = parse_lease(&mut tokens).map_err(invalid)?;
+        if lease.is_none() {
+            eprintln!(
+                "[WARN] Skipping the lease of {} on line {}: not an Ethernet hardware address",
+                ip, line
+            );
+        }
+        match index.get(&ip) {
+            Some(&i) => leases[i] = lease,
+            None => {
+                index.insert(ip, leases.len());
+                leases.push(lease);
+            }
+        }
+    }
+    Ok(leases.into_iter().flatten().collect())
+}
+
+/// Write `leases` as dhcpd.leases blocks, see [`parse_isc_leases`].
+pub fn format_isc_leases(leases: &[DhcpLease]) -> String {
+    let mut out = String::new();
+    for lease in leases {
+        // Writing to a String never fails
+        let _ = write_lease(&mut out, lease);
+    }
+    out
+}
+
+fn write_lease(out: &mut String, lease: &DhcpLease) -> std::fmt::Result {
+    writeln!(out, "lease {} {{", lease.get_ip())?;
+    if let Some(start) = lease.get_start() {
+        writeln!(out, "  starts {};", format_time(start))?;
+    }
+    writeln!(out, "  ends {};", format_time(lease.get_expiry()))?;
+    writeln!(out, "  binding state {};", lease.get_state())?;
+    if *lease.get_mac() != MacAddress::nil() {
+        writeln!(
+            out,
+            "  hardware ethernet {};",
+            format_client_id(lease.get_mac().get_octets())
+        )?;
+    }
+    if let Some(chi) = lease.get_chi() {
+        writeln!(out, "  uid {};", chi)?;
+    }
+    if let Some(hostname) = lease.get_hostname() {
+        writeln!(out, "  client-hostname {};", quote(hostname.as_bytes()))?;
+    }
+    writeln!(out, "}}")
+}
+
+// The address of a lease block and its lease, None for a hardware type
+// DhcpLease cannot hold
+fn parse_lease(tokens: &mut Tokens) -> Result<(IpAddr, Option<DhcpLease>), ParseDhcpLeaseError> {
+    let ip = match tokens.next().map(|t| t.value) {
+        Some(Value::Word(ip)) => match ip.parse() {
+            Ok(ip @ IpAddr::V4(_)) => ip,
+            _ => return Err(ParseDhcpLeaseError::InvalidIpAddr),
+        },
+        _ => return Err(ParseDhcpLeaseError::UnexpectedToken),
+    };
+    tokens.expect(Value::Open)?;
+    let mut start = None;
+    let mut expiry = Duration::ZERO;
+    let mut state = BindingState::Free;
+    let mut mac = MacAddress::nil();
+    let mut chi = None;
+    let mut hostname = None;
+    let mut ethernet = true;
+    while let Some(statement) = tokens.statement()? {
+        match statement.as_slice() {
+            [Value::Word(w), time @ ..] if w == "starts" => start = parse_time(time)?,
+            [Value::Word(w), time @ ..] if w == "ends" => {
+                expiry = parse_time(time)?.unwrap_or(INFINITE_EXPIRY)
+            }
+            [Value::Word(b), Value::Word(s), Value::Word(value)]
+                if b == "binding" && s == "state" =>
+            {
+                state = value.parse()?;
+            }
+            [Value::Word(h), Value::Word(kind), Value::Word(value)] if h == "hardware" => {
+                // e.g. token-ring, the rest of the block is still read
+                ethernet = kind == "ethernet";
+                if ethernet {
+                    mac = MacAddress::from_str(value)
+                        .map_err(|_| ParseDhcpLeaseError::InvalidMacAddress)?;
+                }
+            }
+            [Value::Word(u), Value::Str(id)] if u == "uid" => chi = Some(format_client_id(id)),
+            [Value::Word(u), Value::Word(id)] if u == "uid" => {
+                chi = Some(parse_hex_id(id).ok_or(ParseDhcpLeaseError::InvalidClientId)?);
+            }
+            [Value::Word(c), Value::Str(name)] if c == "client-hostname" => {
+                hostname = Some(String::from_utf8_lossy(name).into_owned());
+            }
+            // e.g. cltt, next binding state, set or on statements
+            _ => {}
+        }
+    }
+    let mut lease = DhcpLease::new(expiry, mac, ip, hostname, chi);
+    if let Some(start) = start {
+        lease.set_start(start);
+    }
+    lease.set_state(state);
+    Ok((ip, ethernet.then_some(lease)))
+}
+
+// `<weekday> <yyyy/mm/dd> <hh:mm:ss>` in UTC, `epoch <seconds>` or `never`
+fn parse_time(time: &[Value]) -> Result<Option<Duration>, ParseDhcpLeaseError> {
+    let invalid = ParseDhcpLeaseError::ParseExpiredTimeError;
+    let words: Vec<&str> = time
+        .iter()
+        .map(|value| match value {
+            Value::Word(word) => Ok(word.as_str()),
+            _ => Err(invalid.clone()),
+        })
+        .collect::<Result<_, _>>()?;
+    match words.as_slice() {
+        ["never"] => Ok(None),
+        ["epoch", secs] => Ok(Some(Duration::from_secs(
+            secs.parse().map_err(|_| invalid)?,
+        ))),
+        [_, date, clock] => {
+            let number = |s: &str| s.parse::<i64>().map_err(|_| invalid.clone());
+            let date: Vec<i64> = date.split('/').map(number).collect::<Result<_, _>>()?;
+            let clock: Vec<i64> = clock.split(':').map(number).collect::<Result<_, _>>()?;
+            match (date.as_slice(), clock.as_slice()) {
+                ([y, m, d], [h, min, s])
+                    if (1..=12).contains(m)
+                        && (1..=31).contains(d)
+                        && (0..24).contains(h)
+                        && (0..60).contains(min)
+                        && (0..61).contains(s) =>
+                {
+                    let secs = days_from_civil(*y, *m, *d) * 86400 + h * 3600 + min * 60 + s;
+                    u64::try_from(secs)
+                        .map(|secs| Some(Duration::from_secs(secs)))
+                        .map_err(|_| invalid)
+                }
+                _ => Err(invalid),
+            }
+        }
+        _ => Err(invalid),
+    }
+}
+
+fn format_time(time: &Duration) -> String {
+    if *time == INFINITE_EXPIRY {
+        return "never".to_string();
+    }
+    let secs = time.as_secs() as i64;
+    let days = secs.div_euclid(86400);
+    let rest = secs.rem_euclid(86400);
+    let (y, m, d) = civil_from_days(days);
+    // 1970-01-01 was a Thursday, dhcpd counts from Sunday as 0
+    let weekday = (days + 4).rem_euclid(7);
+    format!(
+        "{} {:04}/{:02}/{:02} {:02}:{:02}:{:02}",
+        weekday,
+        y,
+        m,
+        d,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+// Days since 1970-01-01 of a date of the proleptic Gregorian calendar
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+// A quoted string, bytes outside printable ASCII as octal escapes like dhcpd
+fn quote(bytes: &[u8]) -> String {
+    let mut quoted = String::from("\"");
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => {
+                quoted.push('\\');
+                quoted.push(b as char);
+            }
+            0x20..=0x7e => quoted.push(b as char),
+            _ => quoted.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Value {
+    Word(String),
+    Str(Vec<u8>),
+    Open,
+    Close,
+    Semicolon,
+}
+
+struct Token {
+    value: Value,
+    line: usize,
+}
+
+struct Tokens {
+    tokens: std::vec::IntoIter<Token>,
+}
+
+impl Tokens {
+    // Split the file into words, quoted strings and punctuation, without comments
+    fn new(content: &str) -> StorageResult<Tokens> {
+        let mut tokens = vec![];
+        let mut chars = content.chars().peekable();
+        let mut line = 1;
+        while let Some(c) = chars.next() {
+            let value = match c {
+                '\n' => {
+                    line += 1;
+                    continue;
+                }
+                c if c.is_whitespace() => continue,
+                '#' => {
+                    while chars.next_if(|c| *c != '\n').is_some() {}
+                    continue;
+                }
+                '{' => Value::Open,
+                '}' => Value::Close,
+                ';' => Value::Semicolon,
+                '"' => {
+                    let start = line;
+                    let mut bytes = vec![];
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => match chars.next() {
+                                Some(d @ '0'..='7') => {
+                                    let mut octal = d.to_digit(8).unwrap_or(0);
+                                    for _ in 0..2 {
+                                        if let Some(d) = chars.next_if(|c| ('0'..='7').contains(c))
+                                        {
+                                            octal = octal * 8 + d.to_digit(8).unwrap_or(0);
+                                        }
+                                    }
+                                    bytes.push(octal as u8);
+                                }
+                                Some('n') => bytes.push(b'\n'),
+                                Some('t') => bytes.push(b'\t'),
+                                Some(c) => bytes.extend(c.to_string().as_bytes()),
+                                None => break,
+                            },
+                            Some(c) => {
+                                if c == '\n' {
+                                    line += 1;
+                                }
+                                bytes.extend(c.to_string().as_bytes());
+                            }
+                            None => {
+                                let invalid =
+                                    LeaseError::from(ParseDhcpLeaseError::UnexpectedToken);
+                                return Err(StorageError::InvalidLease(start, invalid));
+                            }
+                        }
+                    }
+                    Value::Str(bytes)
+                }
+                c => {
+                    let mut word = c.to_string();
+                    while let Some(c) =
+                        chars.next_if(|c| !c.is_whitespace() && !"{};\"#".contains(*c))
+                    {
+                        word.push(c);
+                    }
+                    Value::Word(word)
+                }
+            };
+            tokens.push(Token { value, line });
+        }
+        Ok(Tokens {
+            tokens: tokens.into_iter(),
+        })
+    }
+    fn next(&mut self) -> Option<Token> {
+        self.tokens.next()
+    }
+    fn expect(&mut self, value: Value) -> Result<(), ParseDhcpLeaseError> {
+        match self.next() {
+            Some(token) if token.value == value => Ok(()),
+            _ => Err(ParseDhcpLeaseError::UnexpectedToken),
+        }
+    }
+    // The values of the next statement of a block, None at its closing brace.
+    // Nested blocks, e.g. of `on expiry`, end the statement and are skipped.
+    fn statement(&mut self) -> Result<Option<Vec<Value>>, ParseDhcpLeaseError> {
+        let mut values = vec![];
+        loop {
+            match self.next().map(|t| t.value) {
+                Some(Value::Semicolon) => return Ok(Some(values)),
+                Some(Value::Close) if values.is_empty() => return Ok(None),
+                Some(Value::Open) => {
+                    self.skip_block()?;
+                    return Ok(Some(vec![]));
+                }
+                Some(Value::Close) | None => return Err(ParseDhcpLeaseError::UnexpectedToken),
+                Some(value) => values.push(value),
+            }
+        }
+    }
+    // Skip a top-level statement or declaration starting with `first`
+    fn skip_statement(&mut self, first: Token) -> Result<(), ParseDhcpLeaseError> {
+        let mut token = Some(first);
+        while let Some(t) = token {
+            match t.value {
+                Value::Semicolon => return Ok(()),
+                Value::Open => return self.skip_block(),
+                Value::Close => return Err(ParseDhcpLeaseError::UnexpectedToken),
+                _ => token = self.next(),
+            }
+        }
+        Err(ParseDhcpLeaseError::UnexpectedToken)
+    }
+    fn skip_block(&mut self) -> Result<(), ParseDhcpLeaseError> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next().map(|t| t.value) {
+                Some(Value::Open) => depth += 1,
+                Some(Value::Close) => depth -= 1,
+                Some(_) => {}
+                None => return Err(ParseDhcpLeaseError::UnexpectedToken),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,389 @@
+use crate::dhcp::storage::sync_dir_of;
+use crate::dhcp::{
+    write_atomically, BindingState, DhcpLease, LeaseError, LeaseStore, MemoryLeaseStore,
+    ParseDhcpLeaseError, StorageError, StorageResult,
+};
+use crate::ipnet::Ipv4Net;
+use crate::macaddress::MacAddress;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Journal records after which the journal is compacted into the snapshot.
+pub const DEFAULT_COMPACT_AFTER: usize = 10000;
+
+/// Leases kept in memory, with a snapshot file and a write-ahead journal
+/// next to it.
+///
+/// A commit appends the changes since the last commit to the journal, one
+/// record per lease transition and a commit record, and syncs it. Each record
+/// carries a CRC-32 checksum. Opening the store reads the snapshot and
+/// replays the journal: a torn final record, e.g. of a crash during a commit,
+/// drops that commit.
+///
+/// Once the journal holds [`DEFAULT_COMPACT_AFTER`] records, see
+/// [`JournalLeaseStore::set_compact_after`], it is compacted: a new journal
+/// is started and a thread writes the leases to the snapshot.
+#[derive(Debug)]
+pub struct JournalLeaseStore {
+    snapshot_path: PathBuf,
+    journal_path: PathBuf,
+    journal: File,
+    leases: MemoryLeaseStore,
+    // Records of the changes not yet committed
+    pending: Vec<String>,
+    // Records in the journal
+    records: usize,
+    // Length of the journal at the last commit
+    committed_len: u64,
+    compact_after: usize,
+    compaction: Option<JoinHandle<StorageResult<()>>>,
+}
+
+impl JournalLeaseStore {
+    /// Open the store with its snapshot at `path` and its journal at
+    /// `path.journal`, missing files are an empty store.
+    pub fn open<P: AsRef<Path>>(path: P) -> StorageResult<JournalLeaseStore> {
+        let snapshot_path = path.as_ref().to_path_buf();
+        let journal_path = with_extension(&snapshot_path, "journal");
+        let old_path = old_journal_path(&journal_path);
+        let mut leases = MemoryLeaseStore::new();
+        replay(&snapshot_path, &mut leases)?;
+        // The journal of a compaction cut short by a crash
+        let interrupted = replay(&old_path, &mut leases)?.is_some();
+        let (valid_len, records) = replay(&journal_path, &mut leases)?.unwrap_or((0, 0));
+        leases.commit()?;
+        let journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&journal_path)?;
+        // Drop a torn record, new records follow the last commit
+        journal.set_len(valid_len)?;
+        let mut store = JournalLeaseStore {
+            snapshot_path,
+            journal_path,
+            journal,
+            leases,
+            pending: vec![],
+            records,
+            committed_len: valid_len,
+            compact_after: DEFAULT_COMPACT_AFTER,
+            compaction: None,
+        };
+        if interrupted {
+            store.compact_now()?;
+        }
+        Ok(store)
+    }
+    /// Compact the journal once it holds `records` records.
+    pub fn set_compact_after(&mut self, records: usize) {
+        self.compact_after = records;
+    }
+    pub fn get_snapshot_path(&self) -> &Path {
+        &self.snapshot_path
+    }
+    pub fn get_journal_path(&self) -> &Path {
+        &self.journal_path
+    }
+    /// Commit, then compact the journal into the snapshot in the background,
+    /// unless a compaction is running already.
+    pub fn compact(&mut self) -> StorageResult<()> {
+        self.commit()?;
+        self.start_compaction()
+    }
+    /// Wait for a running compaction to end.
+    pub fn wait_for_compaction(&mut self) -> StorageResult<()> {
+        match self.compaction.take() {
+            Some(compaction) => compaction.join().unwrap_or_else(|_| {
+                Err(StorageError::Io("compaction thread panicked".to_string()))
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn start_compaction(&mut self) -> StorageResult<()> {
+        if self.compaction.as_ref().is_some_and(|c| !c.is_finished()) {
+            return Ok(());
+        }
+        let old_path = old_journal_path(&self.journal_path);
+        if let Err(e) = self.wait_for_compaction() {
+            eprintln!(
+                "[WARN] Lease journal compaction failed: {}, compacting again",
+                e
+            );
+        }
+        // A failed compaction left its journal behind
+        if old_path.exists() {
+            return self.compact_now();
+        }
+        if self.records == 0 {
+            return Ok(());
+        }
+        // The journal so far is replayed after the snapshot until the
+        // compaction ends, the new journal takes the next commits
+        fs::rename(&self.journal_path, &old_path)?;
+        self.journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.journal_path)?;
+        self.records = 0;
+        self.committed_len = 0;
+        sync_dir_of(&self.journal_path)?;
+        let snapshot = self.snapshot();
+        let snapshot_path = self.snapshot_path.clone();
+        self.compaction = Some(thread::spawn(move || {
+            write_atomically(&snapshot_path, snapshot.as_bytes())?;
+            fs::remove_file(&old_path)?;
+            Ok(())
+        }));
+        Ok(())
+    }
+
+    // Write the snapshot and empty the journals, in this thread
+    fn compact_now(&mut self) -> StorageResult<()> {
+        write_atomically(&self.snapshot_path, self.snapshot().as_bytes())?;
+        let old_path = old_journal_path(&self.journal_path);
+        if old_path.exists() {
+            fs::remove_file(&old_path)?;
+        }
+        self.journal.set_len(0)?;
+        self.records = 0;
+        self.committed_len = 0;
+        Ok(())
+    }
+
+    // Cut the journal back to the last commit, dropping the records of a
+    // failed one. The journal is opened to append, the next records follow.
+    fn truncate_to_commit(&mut self) -> StorageResult<()> {
+        if self.journal.metadata()?.len() > self.committed_len {
+            self.journal.set_len(self.committed_len)?;
+            self.journal.sync_data()?;
+        }
+        Ok(())
+    }
+
+    // The committed leases as records of one commit
+    fn snapshot(&self) -> String {
+        self.leases
+            .iter_by_expiry()
+            .map(|lease| encode_record(&put_record(lease)))
+            .chain([encode_record(COMMIT)])
+            .collect()
+    }
+}
+
+impl LeaseStore for JournalLeaseStore {
+    fn insert(&mut self, lease: DhcpLease) -> StorageResult<()> {
+        self.pending.push(put_record(&lease));
+        self.leases.insert(lease)
+    }
+    fn update(&mut self, lease: DhcpLease) -> StorageResult<()> {
+        let record = put_record(&lease);
+        self.leases.update(lease)?;
+        self.pending.push(record);
+        Ok(())
+    }
+    fn remove(&mut self, ip: &IpAddr) -> StorageResult<Option<DhcpLease>> {
+        let lease = self.leases.remove(ip)?;
+        if lease.is_some() {
+            self.pending.push(format!("{} {}", DEL, ip));
+        }
+        Ok(lease)
+    }
+    fn get_by_ip(&self, ip: &IpAddr) -> Option<&DhcpLease> {
+        self.leases.get_by_ip(ip)
+    }
+    fn get_by_mac(&self, mac: &MacAddress) -> Vec<&DhcpLease> {
+        self.leases.get_by_mac(mac)
+    }
+    fn get_by_client_id(&self, client_id: &str) -> Vec<&DhcpLease> {
+        self.leases.get_by_client_id(client_id)
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = &DhcpLease> + '_> {
+        self.leases.iter()
+    }
+    fn iter_by_expiry(&self) -> Box<dyn Iterator<Item = &DhcpLease> + '_> {
+        self.leases.iter_by_expiry()
+    }
+    fn commit(&mut self) -> StorageResult<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let records: String = self
+            .pending
+            .iter()
+            .map(|record| encode_record(record))
+            .chain([encode_record(COMMIT)])
+            .collect();
+        // Records of a failed write or sync must not be replayed
+        let written = self
+            .journal
+            .write_all(records.as_bytes())
+            .and_then(|_| self.journal.sync_data());
+        if let Err(e) = written {
+            if let Err(e) = self.truncate_to_commit() {
+                eprintln!("[WARN] Failed to cut the lease journal back to its last commit: {}", e);
+            }
+            return Err(e.into());
+        }
+        self.committed_len += records.len() as u64;
+        self.records += self.pending.len() + 1;
+        self.pending.clear();
+        self.leases.commit()?;
+        // The leases are committed, a failed compaction is retried later
+        if self.records >= self.compact_after {
+            if let Err(e) = self.start_compaction() {
+                eprintln!("[WARN] Lease journal compaction failed: {}", e);
+            }
+        }
+        Ok(())
+    }
+    fn rollback(&mut self) {
+        self.pending.clear();
+        self.leases.rollback();
+        if let Err(e) = self.truncate_to_commit() {
+            eprintln!("[WARN] Failed to cut the lease journal back to its last commit: {}", e);
+        }
+    }
+    fn len(&self) -> usize {
+        self.leases.len()
+    }
+}
+
+const PUT: &str = "put";
+const DEL: &str = "del";
+const COMMIT: &str = "commit";
+
+fn with_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+fn old_journal_path(journal_path: &Path) -> PathBuf {
+    with_extension(journal_path, "old")
+}
+
+// `put <start|*> <binding state> <subnet|*> <lease>`, the lease as a
+// dnsmasq.leases line
+fn put_record(lease: &DhcpLease) -> String {
+    let start = match lease.get_start() {
+        Some(start) => start.as_secs().to_string(),
+        None => "*".to_string(),
+    };
+    let subnet = match lease.get_subnet() {
+        Some(subnet) => subnet.to_string(),
+        None => "*".to_string(),
+    };
+    format!("{} {} {} {} {}", PUT, start, lease.get_state(), subnet, lease)
+}
+
+// A record line: the CRC-32 of the record in hex, a space and the record
+fn encode_record(record: &str) -> String {
+    format!("{:08x} {}\n", crc32(record.as_bytes()), record)
+}
+
+// The record of a line, None when the checksum does not match
+fn decode_record(line: &str) -> Option<&str> {
+    let (checksum, record) = line.split_once(' ')?;
+    let checksum = u32::from_str_radix(checksum, 16).ok()?;
+    (checksum == crc32(record.as_bytes())).then_some(record)
+}
+
+enum Change {
+    Put(DhcpLease),
+    Del(IpAddr),
+}
+
+fn parse_record(record: &str) -> Result<Option<Change>, LeaseError> {
+    let invalid = || LeaseError::from(ParseDhcpLeaseError::UnexpectedToken);
+    let (kind, rest) = record.split_once(' ').unwrap_or((record, ""));
+    match kind {
+        PUT => {
+            let fields: Vec<&str> = rest.splitn(4, ' ').collect();
+            let (start, state, subnet, lease) = match fields.as_slice() {
+                [start, state, subnet, lease] => (*start, *state, *subnet, *lease),
+                _ => return Err(invalid()),
+            };
+            let mut lease = DhcpLease::from_str(lease)?;
+            if start != "*" {
+                let secs = start.parse().map_err(|_| invalid())?;
+                lease.set_start(Duration::from_secs(secs));
+            }
+            lease.set_state(BindingState::from_str(state)?);
+            if subnet != "*" {
+                lease.set_subnet(Ipv4Net::from_str(subnet).map_err(|_| invalid())?);
+            }
+            Ok(Some(Change::Put(lease)))
+        }
+        DEL => Ok(Some(Change::Del(rest.parse().map_err(|_| invalid())?))),
+        COMMIT => Ok(None),
+        _ => Err(invalid()),
+    }
+}
+
+// Apply the committed records of the file at `path` to `leases`. Returns the
+// length of the file up to the last commit and the number of records, None
+// for a missing file.
+fn replay(path: &Path, leases: &mut MemoryLeaseStore) -> StorageResult<Option<(u64, usize)>> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut changes = vec![];
+    let mut valid_len = 0;
+    let mut records = 0;
+    let mut offset = 0;
+    let lines: Vec<&[u8]> = content.split_inclusive(|b| *b == b'\n').collect();
+    for (i, line) in lines.iter().enumerate() {
+        offset += line.len();
+        // A torn record may end inside a UTF-8 sequence
+        let record = line
+            .strip_suffix(b"\n")
+            .and_then(|line| std::str::from_utf8(line).ok())
+            .and_then(decode_record);
+        let record = match record {
+            Some(record) => record,
+            // Only the last record can be torn, by a crash while appending
+            None if i + 1 == lines.len() => break,
+            None => return Err(StorageError::ChecksumMismatch(i + 1)),
+        };
+        match parse_record(record).map_err(|e| StorageError::InvalidLease(i + 1, e))? {
+            Some(change) => changes.push(change),
+            None => {
+                records += changes.len() + 1;
+                for change in changes.drain(..) {
+                    match change {
+                        Change::Put(lease) => leases.insert(lease)?,
+                        Change::Del(ip) => {
+                            leases.remove(&ip)?;
+                        }
+                    }
+                }
+                valid_len = offset as u64;
+            }
+        }
+    }
+    Ok(Some((valid_len, records)))
+}
+
+// CRC-32 (IEEE 802.3) of `data`
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xedb8_8320,
+                _ => crc >> 1,
+            };
+        }
+    }
+    !crc
+}
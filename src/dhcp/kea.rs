@@ -0,0 +1,195 @@
+use crate::dhcp::lease::parse_hex_id;
+use crate::dhcp::{
+    format_client_id, BindingState, DhcpLease, LeaseError, ParseDhcpLeaseError, StorageError,
+    StorageResult, INFINITE_EXPIRY,
+};
+use crate::macaddress::MacAddress;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+// Columns of the kea-leases4.csv files written by Kea 2.x
+const KEA_LEASES4_HEADER: &str =
+    "address,hwaddr,client_id,valid_lifetime,expire,subnet_id,fqdn_fwd,fqdn_rev,hostname,state,user_context,pool_id";
+// A valid lifetime of all ones is an infinite lease
+const KEA_INFINITE_LIFETIME: u64 = u32::MAX as u64;
+// Kea escapes commas inside a column
+const KEA_ESCAPED_COMMA: &str = "&#x2c";
+
+/// Read the leases of a Kea memfile kea-leases4.csv file. The columns are
+/// found by the header line, so files of older Kea versions are read too.
+///
+/// The start time is the expire time less the valid lifetime. The state
+/// column maps 0 (default) to active, 1 (declined) to abandoned, 2
+/// (expired-reclaimed) to expired and 3 to released. Kea appends a line on
+/// every change: the last line of an address is its lease, a line with a
+/// valid lifetime of 0 deletes it. A lease of another hardware type than
+/// Ethernet is skipped with a warning. Carry over the bindings with
+/// [`DhcpLease::is_active`].
+pub fn parse_kea_leases4(content: &str) -> StorageResult<Vec<DhcpLease>> {
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let header: Vec<&str> = match lines.next() {
+        Some((_, header)) => header.split(',').map(str::trim).collect(),
+        None => return Ok(vec![]),
+    };
+    let column = |name: &str| header.iter().position(|c| *c == name);
+    let missing =
+        || StorageError::InvalidLease(1, LeaseError::from(ParseDhcpLeaseError::MissingField));
+    let columns = Columns {
+        address: column("address").ok_or_else(missing)?,
+        hwaddr: column("hwaddr").ok_or_else(missing)?,
+        client_id: column("client_id").ok_or_else(missing)?,
+        valid_lifetime: column("valid_lifetime").ok_or_else(missing)?,
+        expire: column("expire").ok_or_else(missing)?,
+        hostname: column("hostname"),
+        state: column("state"),
+    };
+    // The last lease of each address, in the order the addresses first appear
+    let mut leases: Vec<Option<DhcpLease>> = vec![];
+    let mut index: HashMap<IpAddr, usize> = HashMap::new();
+    for (i, line) in lines {
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() != header.len() {
+            let invalid = LeaseError::from(ParseDhcpLeaseError::InvalidFieldsLength);
+            return Err(StorageError::InvalidLease(i + 1, invalid));
+        }
+        let (ip, lease) = parse_row(&fields, &columns, i + 1)
+            .map_err(|e| StorageError::InvalidLease(i + 1, e.into()))?;
+        match index.get(&ip) {
+            Some(&i) => leases[i] = lease,
+            None => {
+                index.insert(ip, leases.len());
+                leases.push(lease);
+            }
+        }
+    }
+    Ok(leases.into_iter().flatten().collect())
+}
+
+/// Write `leases` as a kea-leases4.csv file with the header of Kea 2.x.
+/// `subnet_id` gives the Kea subnet ID of a lease. A lease without a start
+/// time is written with a valid lifetime of one second, Kea keeps its expire
+/// time.
+pub fn format_kea_leases4<F: Fn(&DhcpLease) -> u32>(leases: &[DhcpLease], subnet_id: F) -> String {
+    let mut out = String::from(KEA_LEASES4_HEADER);
+    out.push('\n');
+    for lease in leases {
+        let (valid_lifetime, expire) = match (lease.get_start(), *lease.get_expiry()) {
+            (start, INFINITE_EXPIRY) => {
+                let start = start.unwrap_or_default().as_secs();
+                (KEA_INFINITE_LIFETIME, start + KEA_INFINITE_LIFETIME)
+            }
+            (Some(start), expiry) => (
+                expiry.saturating_sub(*start).as_secs().max(1),
+                expiry.as_secs(),
+            ),
+            (None, expiry) => (1, expiry.as_secs()),
+        };
+        let hwaddr = match *lease.get_mac() == MacAddress::nil() {
+            true => String::new(),
+            false => format_client_id(lease.get_mac().get_octets()),
+        };
+        let state = match lease.get_state() {
+            BindingState::Abandoned => 1,
+            BindingState::Expired
+            | BindingState::Free
+            | BindingState::Reset
+            | BindingState::Backup => 2,
+            BindingState::Released => 3,
+            BindingState::Active | BindingState::Bootp => 0,
+        };
+        out.push_str(&format!(
+            "{},{},{},{},{},{},0,0,{},{},,0\n",
+            lease.get_ip(),
+            hwaddr,
+            lease.get_chi().as_deref().unwrap_or(""),
+            valid_lifetime,
+            expire,
+            subnet_id(lease),
+            lease
+                .get_hostname()
+                .as_deref()
+                .unwrap_or("")
+                .replace(',', KEA_ESCAPED_COMMA),
+            state,
+        ));
+    }
+    out
+}
+
+struct Columns {
+    address: usize,
+    hwaddr: usize,
+    client_id: usize,
+    valid_lifetime: usize,
+    expire: usize,
+    hostname: Option<usize>,
+    state: Option<usize>,
+}
+
+// The address of a row and its lease, None for a deleted lease or one of
+// a hardware address that is not Ethernet
+fn parse_row(
+    fields: &[&str],
+    columns: &Columns,
+    line: usize,
+) -> Result<(IpAddr, Option<DhcpLease>), ParseDhcpLeaseError> {
+    let ip = match fields[columns.address].parse() {
+        Ok(ip @ IpAddr::V4(_)) => ip,
+        _ => return Err(ParseDhcpLeaseError::InvalidIpAddr),
+    };
+    let number = |column: usize| {
+        fields[column]
+            .parse::<u64>()
+            .map_err(|_| ParseDhcpLeaseError::ParseExpiredTimeError)
+    };
+    let valid_lifetime = number(columns.valid_lifetime)?;
+    if valid_lifetime == 0 {
+        return Ok((ip, None));
+    }
+    let expire = number(columns.expire)?;
+    let start = Duration::from_secs(expire.saturating_sub(valid_lifetime));
+    let expiry = match valid_lifetime {
+        KEA_INFINITE_LIFETIME => INFINITE_EXPIRY,
+        _ => Duration::from_secs(expire),
+    };
+    // A declined lease has no hardware address
+    let mac = match fields[columns.hwaddr] {
+        "" => MacAddress::nil(),
+        mac => match MacAddress::from_str(mac) {
+            Ok(mac) => mac,
+            // e.g. the 20 bytes of an InfiniBand client
+            Err(_) if parse_hex_id(mac).is_some() => {
+                eprintln!(
+                    "[WARN] Skipping the lease of {} on line {}: not an Ethernet hardware address",
+                    ip, line
+                );
+                return Ok((ip, None));
+            }
+            Err(_) => return Err(ParseDhcpLeaseError::InvalidMacAddress),
+        },
+    };
+    let chi = match fields[columns.client_id] {
+        "" => None,
+        id => Some(parse_hex_id(id).ok_or(ParseDhcpLeaseError::InvalidClientId)?),
+    };
+    let hostname = columns
+        .hostname
+        .map(|column| fields[column].replace(KEA_ESCAPED_COMMA, ","))
+        .filter(|hostname| !hostname.is_empty());
+    let state = match columns.state.map(|column| fields[column]) {
+        None | Some("0") => BindingState::Active,
+        Some("1") => BindingState::Abandoned,
+        Some("2") => BindingState::Expired,
+        Some("3") => BindingState::Released,
+        Some(_) => return Err(ParseDhcpLeaseError::InvalidBindingState),
+    };
+    let mut lease = DhcpLease::new(expiry, mac, ip, hostname, chi);
+    lease.set_start(start);
+    lease.set_state(state);
+    Ok((ip, Some(lease)))
+}
@@ -1,3 +1,6 @@
+use crate::dhcp::packet::Packet;
+use crate::dhcp::sanitize_hostname;
+use crate::ipnet::Ipv4Net;
 use crate::macaddress::MacAddress;
 use std::error::Error;
 use std::fmt;
@@ -5,45 +8,248 @@ use std::net::IpAddr;
 use std::str::FromStr;
 use std::time::Duration;
 
+/// Identity of a client: its client identifier (option 61) formatted with
+/// [`format_client_id`], its hardware address, or the Remote-ID a relay agent
+/// adds for it (option 82), e.g. the modem or subscriber line.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum ClientIdentifier {
     Mac(MacAddress),
     ClientId(String),
+    RemoteId(Vec<u8>),
+}
+
+impl ClientIdentifier {
+    /// The client identifier when the client sent one, else the MAC address.
+    pub fn new(client_id: Option<&[u8]>, mac: &MacAddress) -> ClientIdentifier {
+        match client_id {
+            Some(id) => ClientIdentifier::ClientId(format_client_id(id)),
+            None => ClientIdentifier::Mac(*mac),
+        }
+    }
+    /// Whether this is the client with `client_id` and `mac`.
+    pub fn matches(&self, client_id: Option<&[u8]>, mac: &MacAddress) -> bool {
+        match self {
+            ClientIdentifier::Mac(m) => m == mac,
+            ClientIdentifier::ClientId(chi) => {
+                client_id.is_some_and(|id| *chi == format_client_id(id))
+            }
+            ClientIdentifier::RemoteId(_) => false,
+        }
+    }
+    /// Whether this is the client that sent `in_packet`.
+    pub fn matches_packet(&self, in_packet: &Packet) -> bool {
+        match self {
+            ClientIdentifier::Mac(mac) => in_packet.get_client_mac().as_ref() == Some(mac),
+            ClientIdentifier::ClientId(_) => self.matches(
+                in_packet.get_client_identifier().map(|id| id.as_slice()),
+                &MacAddress::nil(),
+            ),
+            ClientIdentifier::RemoteId(id) => in_packet.get_remote_id() == Some(id.as_slice()),
+        }
+    }
+}
+/// State of the binding of a lease, named as in ISC dhcpd.leases.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum BindingState {
+    Free,
+    Active,
+    Expired,
+    Released,
+    // Declined by the client or found in use, see DeclinedAddress
+    Abandoned,
+    Reset,
+    Backup,
+    Bootp,
+}
+
+impl BindingState {
+    /// Whether the client holds the address until the lease expires.
+    pub fn is_bound(&self) -> bool {
+        matches!(self, BindingState::Active | BindingState::Bootp)
+    }
+}
+
+impl fmt::Display for BindingState {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BindingState::Free => "free",
+            BindingState::Active => "active",
+            BindingState::Expired => "expired",
+            BindingState::Released => "released",
+            BindingState::Abandoned => "abandoned",
+            BindingState::Reset => "reset",
+            BindingState::Backup => "backup",
+            BindingState::Bootp => "bootp",
+        };
+        fmt.write_str(name)
+    }
+}
+
+impl FromStr for BindingState {
+    type Err = ParseDhcpLeaseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "free" => Ok(BindingState::Free),
+            "active" => Ok(BindingState::Active),
+            "expired" => Ok(BindingState::Expired),
+            "released" => Ok(BindingState::Released),
+            "abandoned" => Ok(BindingState::Abandoned),
+            "reset" => Ok(BindingState::Reset),
+            "backup" => Ok(BindingState::Backup),
+            "bootp" => Ok(BindingState::Bootp),
+            _ => Err(ParseDhcpLeaseError::InvalidBindingState),
+        }
+    }
 }
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct DhcpLease {
+    // Start time, not known for leases read from a dnsmasq lease file
+    start: Option<Duration>,
     expiry: Duration,
+    state: BindingState,
     mac: MacAddress,
-    ip_v: String,
-    // ipv: [char; 2],
     ip: IpAddr,
     hostname: Option<String>,
     chi: Option<String>,
+    // Subnet the address was leased on
+    subnet: Option<Ipv4Net>,
 }
 impl DhcpLease {
+    /// A lease, a hostname a lease file cannot hold as one field, e.g. with
+    /// a space, is passed through [`sanitize_hostname`].
+    pub fn new(
+        expiry: Duration,
+        mac: MacAddress,
+        ip: IpAddr,
+        hostname: Option<String>,
+        chi: Option<String>,
+    ) -> DhcpLease {
+        DhcpLease {
+            start: None,
+            expiry,
+            state: BindingState::Active,
+            mac,
+            ip,
+            hostname: lease_hostname(hostname),
+            chi,
+            subnet: None,
+        }
+    }
+    pub fn set_start(&mut self, start: Duration) {
+        self.start = Some(start);
+    }
+    pub fn set_expiry(&mut self, expiry: Duration) {
+        self.expiry = expiry;
+    }
+    pub fn set_state(&mut self, state: BindingState) {
+        self.state = state;
+    }
+    /// Set the hostname, sanitized as by [`DhcpLease::new`].
+    pub fn set_hostname(&mut self, hostname: Option<String>) {
+        self.hostname = lease_hostname(hostname);
+    }
+    pub fn set_subnet(&mut self, subnet: Ipv4Net) {
+        self.subnet = Some(subnet);
+    }
+    /// Whether the lease belongs to the client with `client_id` and `mac`. The
+    /// client identifier is the key when both sides have one (RFC 2131
+    /// section 4.2), otherwise the hardware address is. The nil address is
+    /// nobody's, e.g. that of an imported declined lease.
+    pub fn is_for_client(&self, client_id: Option<&[u8]>, mac: &MacAddress) -> bool {
+        match (&self.chi, client_id) {
+            (Some(chi), Some(id)) => *chi == format_client_id(id),
+            _ => !self.mac.is_nil() && self.mac == *mac,
+        }
+    }
+    /// Whether the lease has expired at `now` (time since the Unix epoch).
+    pub fn is_expired(&self, now: Duration) -> bool {
+        self.expiry <= now
+    }
+    /// Whether the client holds the address at `now`: the binding is active
+    /// and the lease has not expired.
+    pub fn is_active(&self, now: Duration) -> bool {
+        self.state.is_bound() && !self.is_expired(now)
+    }
     pub fn get_ip(&self) -> &IpAddr {
         &self.ip
     }
     pub fn get_mac(&self) -> &MacAddress {
         &self.mac
     }
+    pub fn get_start(&self) -> &Option<Duration> {
+        &self.start
+    }
     pub fn get_expiry(&self) -> &Duration {
         &self.expiry
     }
     pub fn get_expiry_secs(&self) -> u64 {
         self.expiry.as_secs()
     }
+    pub fn get_state(&self) -> BindingState {
+        self.state
+    }
     pub fn get_hostname(&self) -> &Option<String> {
         &self.hostname
     }
     pub fn get_chi(&self) -> &Option<String> {
         &self.chi
     }
+    pub fn get_subnet(&self) -> &Option<Ipv4Net> {
+        &self.subnet
+    }
+}
+
+/// An address a client declined (DHCPDECLINE) because another host already
+/// uses it. It is left out of the pool until the probation time ends.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DeclinedAddress {
+    ip: IpAddr,
+    reported_by: MacAddress,
+    until: Duration,
+}
+
+impl DeclinedAddress {
+    pub fn new(ip: IpAddr, reported_by: MacAddress, until: Duration) -> DeclinedAddress {
+        DeclinedAddress {
+            ip,
+            reported_by,
+            until,
+        }
+    }
+    pub fn get_ip(&self) -> &IpAddr {
+        &self.ip
+    }
+    /// Hardware address of the client that found the conflict.
+    pub fn get_reported_by(&self) -> &MacAddress {
+        &self.reported_by
+    }
+    /// End of the probation time, since the Unix epoch.
+    pub fn get_until(&self) -> &Duration {
+        &self.until
+    }
+    pub fn is_expired(&self, now: Duration) -> bool {
+        self.until <= now
+    }
+}
+
+/// Format a client identifier (option 61) the way lease files store it, as
+/// colon separated hex octets, e.g. `01:00:11:22:33:44:55`.
+pub fn format_client_id(id: &[u8]) -> String {
+    id.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<String>>()
+        .join(":")
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DistributeDhcpLeaseError {
     LeaseAlreadyExists,
     LeaseNoAvailable,
+    UnknownSubnet,
+    // A client with neither a client identifier nor a hardware address
+    UnknownClient,
 }
 impl fmt::Display for DistributeDhcpLeaseError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -51,6 +257,8 @@ impl fmt::Display for DistributeDhcpLeaseError {
         match self {
             DistributeDhcpLeaseError::LeaseAlreadyExists => write!(fmt, "Lease already exists"),
             DistributeDhcpLeaseError::LeaseNoAvailable => write!(fmt, "Lease no available"),
+            DistributeDhcpLeaseError::UnknownSubnet => write!(fmt, "No subnet for the client's link"),
+            DistributeDhcpLeaseError::UnknownClient => write!(fmt, "No client identifier or hardware address"),
         }
     }
 }
@@ -62,8 +270,17 @@ pub enum ParseDhcpLeaseError {
     InvalidFieldsLength,
     // InvalidMacAddress(ParseMacAddressError),
     InvalidMacAddress,
-    NoSpecificIpVersion,
     InvalidIpAddr,
+    InvalidClientId,
+    // IAID of a DHCPv6 lease, see DnsmasqLease6
+    InvalidIaid,
+    // A lease file with a second DUID line
+    DuplicateDuid,
+    InvalidBindingState,
+    // Lease file syntax, e.g. a lease block without its closing brace
+    UnexpectedToken,
+    // A column the lease file header lacks
+    MissingField,
 }
 impl fmt::Display for ParseDhcpLeaseError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -73,8 +290,13 @@ impl fmt::Display for ParseDhcpLeaseError {
             ParseDhcpLeaseError::InvalidFieldsLength => write!(fmt, "Invalid fields length"),
             // ParseDhcpLeaseError::InvalidMacAddress(e) => write!(fmt, "Invalid MAC address: {}", e),
             ParseDhcpLeaseError::InvalidMacAddress => write!(fmt, "Invalid MAC address"),
-            ParseDhcpLeaseError::NoSpecificIpVersion => write!(fmt, "No specific IP version"),
             ParseDhcpLeaseError::InvalidIpAddr => write!(fmt, "Invalid IP address"),
+            ParseDhcpLeaseError::InvalidClientId => write!(fmt, "Invalid client identifier"),
+            ParseDhcpLeaseError::InvalidIaid => write!(fmt, "Invalid IAID"),
+            ParseDhcpLeaseError::DuplicateDuid => write!(fmt, "Duplicate DUID line"),
+            ParseDhcpLeaseError::InvalidBindingState => write!(fmt, "Invalid binding state"),
+            ParseDhcpLeaseError::UnexpectedToken => write!(fmt, "Unexpected token"),
+            ParseDhcpLeaseError::MissingField => write!(fmt, "Missing field"),
         }
     }
 }
@@ -85,7 +307,16 @@ pub enum LeaseError {
     ParseError(ParseDhcpLeaseError),
     DistributeError(DistributeDhcpLeaseError),
 }
-type LeaseResult<T> = Result<T, LeaseError>;
+
+impl fmt::Display for LeaseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LeaseError::ParseError(e) => write!(fmt, "{}", e),
+            LeaseError::DistributeError(e) => write!(fmt, "{}", e),
+        }
+    }
+}
+impl Error for LeaseError {}
 
 impl From<ParseDhcpLeaseError> for LeaseError {
     fn from(e: ParseDhcpLeaseError) -> Self {
@@ -93,36 +324,100 @@ impl From<ParseDhcpLeaseError> for LeaseError {
     }
 }
 
+/// Expiry time of a lease that never ends, written as 0 in lease files.
+pub const INFINITE_EXPIRY: Duration = Duration::MAX;
+
+/// A DHCPv4 lease line of a dnsmasq.leases file: expiry time in seconds since
+/// the Unix epoch (0 for an infinite lease), MAC address, IPv4 address,
+/// hostname and client identifier, `*` for a missing hostname or client
+/// identifier, e.g.
+/// `1700000000 00:11:22:33:44:55 192.168.1.20 laptop 01:00:11:22:33:44:55`.
 impl FromStr for DhcpLease {
     type Err = LeaseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let fields = s.split_whitespace().collect::<Vec<&str>>();
-        // At least encluding 1. Lease Expiry Time, 2. MAC Address, 3. IP Address
-        if fields.len() < 3 {
+        if fields.len() != 5 {
             return Err(LeaseError::from(ParseDhcpLeaseError::InvalidFieldsLength));
         }
-        let expiry_secs = fields[0]
-            .parse::<u64>()
-            .map_err(|_| ParseDhcpLeaseError::ParseExpiredTimeError)?;
-        let expiry = Duration::from_secs(expiry_secs);
-        let mac: MacAddress =
-            MacAddress::from_str(fields[1]).map_err(|_| ParseDhcpLeaseError::InvalidMacAddress)?;
-        let ip_v = fields[2].to_string();
-        if ip_v != "ipv4" && ip_v != "ipv6" {
-            return Err(LeaseError::from(ParseDhcpLeaseError::NoSpecificIpVersion));
+        let expiry = parse_expiry(fields[0])?;
+        let mac = MacAddress::from_str(fields[1]).map_err(|_| ParseDhcpLeaseError::InvalidMacAddress)?;
+        let ip = match fields[2].parse() {
+            Ok(ip @ IpAddr::V4(_)) => ip,
+            _ => return Err(LeaseError::from(ParseDhcpLeaseError::InvalidIpAddr)),
+        };
+        let hostname = parse_placeholder(fields[3]);
+        let chi = match parse_placeholder(fields[4]) {
+            Some(id) => Some(parse_hex_id(&id).ok_or(ParseDhcpLeaseError::InvalidClientId)?),
+            None => None,
+        };
+        Ok(DhcpLease::new(expiry, mac, ip, hostname, chi))
+    }
+}
+
+/// Writes the lease as a dnsmasq.leases line, see [`DhcpLease::from_str`].
+impl fmt::Display for DhcpLease {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "{} {} {} {} {}",
+            format_expiry(&self.expiry),
+            // Lowercase and colon separated like dnsmasq, not the Display of MacAddress
+            format_client_id(self.mac.get_octets()),
+            self.ip,
+            format_placeholder(&self.hostname),
+            format_placeholder(&self.chi),
+        )
+    }
+}
+
+// Lease file helpers, shared with the DHCPv6 lines of dnsmasq.rs
+
+pub(crate) fn parse_expiry(field: &str) -> Result<Duration, ParseDhcpLeaseError> {
+    match field.parse::<u64>() {
+        Ok(0) => Ok(INFINITE_EXPIRY),
+        Ok(secs) => Ok(Duration::from_secs(secs)),
+        Err(_) => Err(ParseDhcpLeaseError::ParseExpiredTimeError),
+    }
+}
+
+pub(crate) fn format_expiry(expiry: &Duration) -> u64 {
+    match *expiry == INFINITE_EXPIRY {
+        true => 0,
+        false => expiry.as_secs(),
+    }
+}
+
+pub(crate) fn parse_placeholder(field: &str) -> Option<String> {
+    (field != "*").then(|| field.to_string())
+}
+
+pub(crate) fn format_placeholder(value: &Option<String>) -> &str {
+    match value {
+        Some(value) if !value.is_empty() => value,
+        _ => "*",
+    }
+}
+
+// A hostname that fits in a lease file field and is not the placeholder,
+// others are sanitized
+fn lease_hostname(hostname: Option<String>) -> Option<String> {
+    match hostname {
+        Some(name) if name.is_empty() || name == "*" || name.contains(char::is_whitespace) => {
+            sanitize_hostname(&name)
         }
-        let ip: IpAddr = fields[3]
-            .parse()
-            .map_err(|_| ParseDhcpLeaseError::InvalidIpAddr)?;
+        hostname => hostname,
+    }
+}
 
-        Ok(DhcpLease {
-            expiry,
-            mac,
-            ip_v,
-            ip,
-            hostname: None,
-            chi: None,
+// Colon separated hex octets, returned lowercase as format_client_id writes them
+pub(crate) fn parse_hex_id(field: &str) -> Option<String> {
+    let octets = field
+        .split(':')
+        .map(|octet| match octet.len() {
+            2 if octet.chars().all(|c| c.is_ascii_hexdigit()) => u8::from_str_radix(octet, 16).ok(),
+            _ => None,
         })
-    }
+        .collect::<Option<Vec<u8>>>()?;
+    Some(format_client_id(&octets))
 }
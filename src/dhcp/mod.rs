@@ -1,13 +1,46 @@
+mod boot;
+mod class;
+mod dns;
+mod dnsmasq;
+mod hostname;
+mod ipxe;
+mod isc;
+mod journal;
+mod kea;
 mod lease;
 mod options;
 mod packet;
+mod pool;
+mod relay;
+mod reservation;
 mod server;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 mod storage;
 mod stucture;
+mod subnet;
+mod vendor;
 
 
+pub use boot::*;
+pub use class::*;
+pub use dns::*;
+pub use dnsmasq::*;
+pub use hostname::*;
+pub use ipxe::*;
+pub use isc::*;
+pub use journal::*;
+pub use kea::*;
 pub use options::*;
+pub use packet::*;
+pub use pool::*;
+pub use relay::*;
+pub use reservation::*;
 pub use server::*;
+#[cfg(feature = "sqlite")]
+pub use sqlite::*;
 pub use storage::*;
+pub use subnet::*;
 pub use lease::*;
+pub use vendor::*;
 
@@ -1,4 +1,10 @@
-use std::net::IpAddr;
+use crate::dhcp::dns::{decode_domain_search, encode_domain_search, ClientFqdn};
+use crate::dhcp::ipxe::{decode_ipxe_options, encode_ipxe_options};
+use crate::dhcp::packet::{ConvertPacketError, ConvertResult};
+use crate::dhcp::relay::*;
+use crate::dhcp::vendor::VendorSubOption;
+use crate::ipnet::Ipv4Net;
+use std::net::{IpAddr, Ipv4Addr};
 // One particular option
 // the "DHCP message type" option - must be included in every DHCP
 // message.  This option defines the "type" of the DHCP message.
@@ -27,7 +33,7 @@ use std::net::IpAddr;
 
 
 
-#[derive(PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum DhcpOption{
 
   // RFC 1497 Vendor Extensions
@@ -59,19 +65,67 @@ pub enum DhcpOption{
 
   // IP Layer Parameters per Host
   // code 19 to 25
+  IpForwarding(bool), // code 19
+  NonLocalSourceRouting(bool), // code 20
+  PolicyFilter(Vec<(IpAddr, IpAddr)>), // code 21 // address/mask pairs, the length MUST be a multiple of 8
+  MaximumDatagramReassemblySize(u16), // code 22 minimum 576
+  DefaultIpTtl(u8), // code 23 1 to 255
+  PathMtuAgingTimeout(u32), // code 24
+  PathMtuPlateauTable(Vec<u16>), // code 25 // MTU sizes ordered from smallest to largest, each at least 68
+
   // IP Layer Parameters per Interface
   // code 26 to 33
+  InterfaceMtu(u16), // code 26 minimum 68
+  AllSubnetsAreLocal(bool), // code 27
+  BroadcastAddress(IpAddr), // code 28
+  PerformMaskDiscovery(bool), // code 29
+  MaskSupplier(bool), // code 30
+  PerformRouterDiscovery(bool), // code 31
+  RouterSolicitationAddress(IpAddr), // code 32
+  StaticRoute(Vec<(IpAddr, IpAddr)>), // code 33 // destination/router pairs, the default route (0.0.0.0) is not allowed
+
   // Link Layer Parameters per Interface
   // code 34 to 36
+  TrailerEncapsulation(bool), // code 34
+  ArpCacheTimeout(u32), // code 35
+  EthernetEncapsulation(bool), // code 36
+
   // TCP Parameters
   // code 37 to 39
+  TcpDefaultTtl(u8), // code 37 minimum 1
+  TcpKeepaliveInterval(u32), // code 38
+  TcpKeepaliveGarbage(bool), // code 39
+
   // Application and Service Parameters
-  // code 40 to 42
-  // Vendor Specific Information (Add in RFC 2132)
-  // code 43-49 and 64-65 68-76
-  // TODO Unrecognized options will be recognized as Unrecognized(RawDhcpOption)
-  // including some important options e.g. MTU/ARP/NTR/Static Route
-  Unrecognized,
+  // code 40 to 49 and 64 to 76
+  NetworkInformationServiceDomain(String), // code 40
+  NetworkInformationServers(Vec<IpAddr>), // code 41
+  NtpServers(Vec<IpAddr>), // code 42
+  VendorSpecificInformation(Vec<u8>), // code 43 minimum 1 octet, opaque unless the vendor is known
+  NetbiosNameServers(Vec<IpAddr>), // code 44
+  NetbiosDatagramDistributionServers(Vec<IpAddr>), // code 45
+  NetbiosNodeType(u8), // code 46 // 0x1 B-node, 0x2 P-node, 0x4 M-node, 0x8 H-node
+  NetbiosScope(String), // code 47
+  XWindowFontServers(Vec<IpAddr>), // code 48
+  XWindowDisplayManagers(Vec<IpAddr>), // code 49
+  NetworkInformationServicePlusDomain(String), // code 64
+  NetworkInformationServicePlusServers(Vec<IpAddr>), // code 65
+  TftpServerName(String), // code 66
+  BootfileName(String), // code 67
+  MobileIpHomeAgents(Vec<IpAddr>), // code 68 // minimum length 0, i.e. no home agents are available
+  SmtpServers(Vec<IpAddr>), // code 69
+  Pop3Servers(Vec<IpAddr>), // code 70
+  NntpServers(Vec<IpAddr>), // code 71
+  WwwServers(Vec<IpAddr>), // code 72
+  FingerServers(Vec<IpAddr>), // code 73
+  IrcServers(Vec<IpAddr>), // code 74
+  StreetTalkServers(Vec<IpAddr>), // code 75
+  StreetTalkDirectoryAssistanceServers(Vec<IpAddr>), // code 76
+
+  // Options without a typed variant, or with a payload the typed variant
+  // rejects, are kept as Unrecognized(RawDhcpOption) with their real code and
+  // payload, so they survive a decode/encode round trip
+  Unrecognized(RawDhcpOption),
 
   // DHCP Extensions
   // code 50 to 61
@@ -91,6 +145,32 @@ pub enum DhcpOption{
 
   ClientIdentifier(Vec<u8>), // code 61 minimum 2 octets. The client identifier is used by the client to pass its unique identifier to the server. See HostName option.
 
+  // User Class (RFC 3004), one or more opaque classes the user or site assigned
+  // iPXE sends the plain string "iPXE" instead, kept as Unrecognized
+  UserClass(Vec<Vec<u8>>), // code 77
+
+  // Client FQDN (RFC 4702), the client's name and who updates DNS for it
+  ClientFqdn(ClientFqdn), // code 81
+
+  // Client System Architecture (RFC 4578), processor and firmware of a network boot client
+  ClientArchitecture(Vec<u16>), // code 93 // one type per architecture the client supports, see boot.rs
+
+  // Relay Agent Information (RFC 3046), added by relay agents and echoed back by the server
+  RelayAgentInformation(Vec<RelayAgentSubOption>), // code 82
+
+  // Etherboot/iPXE encapsulated options, sent by iPXE and read by it in replies
+  IpxeEncapsulated(Vec<VendorSubOption>), // code 175
+
+  // Subnet Selection (RFC 3011), the subnet to allocate from when it is not the client's link
+  SubnetSelection(IpAddr), // code 118 // echoed back unchanged
+
+  // Domain Search List (RFC 3397), names compressed as in RFC 1035
+  DomainSearch(Vec<String>), // code 119
+
+  // Classless Static Routes (RFC 3442), destination network and router
+  // A client that receives this option MUST ignore the Routers option
+  ClasslessStaticRoutes(Vec<(Ipv4Net, Ipv4Addr)>), // code 121
+  MsClasslessStaticRoutes(Vec<(Ipv4Net, Ipv4Addr)>), // code 249 // same encoding, requested by Windows clients before Vista
 
 }
 
@@ -117,7 +197,57 @@ impl DhcpOption {
             DhcpOption::SwapServer(_) => SWAP_SERVER,
             DhcpOption::RootPath(_) => ROOT_PATH,
             DhcpOption::ExtensionsPath(_) => EXTENSIONS_PATH,
-            DhcpOption::Unrecognized => 254, // Magic 254 for unrecognized options
+            DhcpOption::IpForwarding(_) => IP_FORWARDING,
+            DhcpOption::NonLocalSourceRouting(_) => NON_LOCAL_SOURCE_ROUTING,
+            DhcpOption::PolicyFilter(_) => POLICY_FILTER,
+            DhcpOption::MaximumDatagramReassemblySize(_) => MAXIMUM_DATAGRAM_REASSEMBLY_SIZE,
+            DhcpOption::DefaultIpTtl(_) => DEFAULT_IP_TTL,
+            DhcpOption::PathMtuAgingTimeout(_) => PATH_MTU_AGING_TIMEOUT,
+            DhcpOption::PathMtuPlateauTable(_) => PATH_MTU_PLATEAU_TABLE,
+            DhcpOption::InterfaceMtu(_) => INTERFACE_MTU,
+            DhcpOption::AllSubnetsAreLocal(_) => ALL_SUBNETS_ARE_LOCAL,
+            DhcpOption::BroadcastAddress(_) => BROADCAST_ADDRESS,
+            DhcpOption::PerformMaskDiscovery(_) => PERFORM_MASK_DISCOVERY,
+            DhcpOption::MaskSupplier(_) => MASK_SUPPLIER,
+            DhcpOption::PerformRouterDiscovery(_) => PERFORM_ROUTER_DISCOVERY,
+            DhcpOption::RouterSolicitationAddress(_) => ROUTER_SOLICITATION_ADDRESS,
+            DhcpOption::StaticRoute(_) => STATIC_ROUTE,
+            DhcpOption::TrailerEncapsulation(_) => TRAILER_ENCAPSULATION,
+            DhcpOption::ArpCacheTimeout(_) => ARP_CACHE_TIMEOUT,
+            DhcpOption::EthernetEncapsulation(_) => ETHERNET_ENCAPSULATION,
+            DhcpOption::TcpDefaultTtl(_) => TCP_DEFAULT_TTL,
+            DhcpOption::TcpKeepaliveInterval(_) => TCP_KEEPALIVE_INTERVAL,
+            DhcpOption::TcpKeepaliveGarbage(_) => TCP_KEEPALIVE_GARBAGE,
+            DhcpOption::NetworkInformationServiceDomain(_) => NETWORK_INFORMATION_SERVICE_DOMAIN,
+            DhcpOption::NetworkInformationServers(_) => NETWORK_INFORMATION_SERVERS,
+            DhcpOption::NtpServers(_) => NETWORK_TIME_PROTOCOL_SERVERS,
+            DhcpOption::VendorSpecificInformation(_) => VENDOR_SPECIFIC_INFORMATION,
+            DhcpOption::NetbiosNameServers(_) => NETBIOS_OVER_TCPIP_NAME_SERVER,
+            DhcpOption::NetbiosDatagramDistributionServers(_) => {
+                NETBIOS_OVER_TCPIP_DATAGRAM_DISTRIBUTION_SERVER
+            }
+            DhcpOption::NetbiosNodeType(_) => NETBIOS_OVER_TCPIP_NODE_TYPE,
+            DhcpOption::NetbiosScope(_) => NETBIOS_OVER_TCPIP_SCOPE,
+            DhcpOption::XWindowFontServers(_) => XWINDOW_SYSTEM_FONT_SERVER,
+            DhcpOption::XWindowDisplayManagers(_) => XWINDOW_SYSTEM_DISPLAY_MANAGER,
+            DhcpOption::NetworkInformationServicePlusDomain(_) => {
+                NETWORK_INFORMATION_SERVICEPLUS_DOMAIN
+            }
+            DhcpOption::NetworkInformationServicePlusServers(_) => {
+                NETWORK_INFORMATION_SERVICEPLUS_SERVERS
+            }
+            DhcpOption::TftpServerName(_) => TFTP_SERVER_NAME,
+            DhcpOption::BootfileName(_) => BOOTFILE_NAME,
+            DhcpOption::MobileIpHomeAgents(_) => MOBILE_IP_HOME_AGENT,
+            DhcpOption::SmtpServers(_) => SIMPLE_MAIL_TRANSPORT_PROTOCOL,
+            DhcpOption::Pop3Servers(_) => POST_OFFICE_PROTOCOL_SERVER,
+            DhcpOption::NntpServers(_) => NETWORK_NEWS_TRANSPORT_PROTOCOL,
+            DhcpOption::WwwServers(_) => DEFAULT_WORLD_WIDE_WEB_SERVER,
+            DhcpOption::FingerServers(_) => DEFAULT_FINGER_SERVER,
+            DhcpOption::IrcServers(_) => DEFAULT_INTERNET_RELAY_CHAT_SERVER,
+            DhcpOption::StreetTalkServers(_) => STREETTALK_SERVER,
+            DhcpOption::StreetTalkDirectoryAssistanceServers(_) => STREETTALK_DIRECTORY_ASSISTANCE,
+            DhcpOption::Unrecognized(raw) => raw.code,
             DhcpOption::RequestedIpAddress(_) => REQUESTED_IP_ADDRESS,
             DhcpOption::IpAddressLeaseTime(_) => IP_ADDRESS_LEASE_TIME,
             DhcpOption::OptionOverload(_) => OPTION_OVERLOAD,
@@ -130,17 +260,638 @@ impl DhcpOption {
             DhcpOption::RebindingTimeValue(_) => REBINDING_TIME_VALUE,
             DhcpOption::ClassIdentifier(_) => CLASS_IDENTIFIER,
             DhcpOption::ClientIdentifier(_) => CLIENT_IDENTIFIER,
+            DhcpOption::ClientFqdn(_) => CLIENT_FQDN,
+            DhcpOption::RelayAgentInformation(_) => RELAY_AGENT_INFORMATION,
+            DhcpOption::UserClass(_) => USER_CLASS,
+            DhcpOption::ClientArchitecture(_) => CLIENT_ARCHITECTURE,
+            DhcpOption::IpxeEncapsulated(_) => IPXE_ENCAPSULATED,
+            DhcpOption::SubnetSelection(_) => SUBNET_SELECTION,
+            DhcpOption::DomainSearch(_) => DOMAIN_SEARCH,
+            DhcpOption::ClasslessStaticRoutes(_) => CLASSLESS_ROUTE_FORMAT,
+            DhcpOption::MsClasslessStaticRoutes(_) => MS_CLASSLESS_ROUTE_FORMAT,
+        }
+    }
+
+    /// Parses the payload of one option (without code and length octets).
+    ///
+    /// Length rules of RFC 2132 are checked here, a violation is reported as
+    /// `InvalidOptionLength(code)`.
+    pub fn decode(code: u8, data: &[u8]) -> ConvertResult<DhcpOption> {
+        Ok(match code {
+            PAD_OPTION => DhcpOption::PadOption,
+            END_OPTION => DhcpOption::EndOption,
+            SUBNET_MASK => DhcpOption::SubnetMask(decode_ip(code, data)?),
+            TIME_OFFSET => DhcpOption::TimeOffset(decode_u32(code, data)?),
+            ROUTERS => DhcpOption::Routers(decode_ips(code, data)?),
+            TIME_SERVERS => DhcpOption::TimeServers(decode_ips(code, data)?),
+            NAME_SERVERS => DhcpOption::NameServers(decode_ips(code, data)?),
+            DOMAIN_NAME_SERVERS => DhcpOption::DomainNameServers(decode_ips(code, data)?),
+            LOG_SERVERS => DhcpOption::LogServers(decode_ips(code, data)?),
+            COOKIE_SERVERS => DhcpOption::CookieServers(decode_ips(code, data)?),
+            LPR_SERVERS => DhcpOption::LprServers(decode_ips(code, data)?),
+            IMPRESS_SERVERS => DhcpOption::ImpressServers(decode_ips(code, data)?),
+            RESOURCE_LOCATION_SERVERS => {
+                DhcpOption::ResourceLocationServers(decode_ips(code, data)?)
+            }
+            HOST_NAME => DhcpOption::HostName(decode_string(code, data)?),
+            BOOT_FILE_SIZE => DhcpOption::BootFileSize(decode_u16(code, data)?),
+            MERIT_DUMP_FILE => DhcpOption::MeritDumpFile(decode_string(code, data)?),
+            DOMAIN_NAME => DhcpOption::DomainName(decode_string(code, data)?),
+            SWAP_SERVER => DhcpOption::SwapServer(decode_ip(code, data)?),
+            ROOT_PATH => DhcpOption::RootPath(decode_string(code, data)?),
+            EXTENSIONS_PATH => DhcpOption::ExtensionsPath(decode_string(code, data)?),
+            IP_FORWARDING => DhcpOption::IpForwarding(decode_bool(code, data)?),
+            NON_LOCAL_SOURCE_ROUTING => DhcpOption::NonLocalSourceRouting(decode_bool(code, data)?),
+            POLICY_FILTER => DhcpOption::PolicyFilter(decode_ip_pairs(code, data)?),
+            MAXIMUM_DATAGRAM_REASSEMBLY_SIZE => {
+                DhcpOption::MaximumDatagramReassemblySize(check_min(code, decode_u16(code, data)?, 576)?)
+            }
+            DEFAULT_IP_TTL => DhcpOption::DefaultIpTtl(check_min(code, decode_u8(code, data)?, 1)?),
+            PATH_MTU_AGING_TIMEOUT => DhcpOption::PathMtuAgingTimeout(decode_u32(code, data)?),
+            PATH_MTU_PLATEAU_TABLE => DhcpOption::PathMtuPlateauTable(decode_mtus(code, data)?),
+            INTERFACE_MTU => DhcpOption::InterfaceMtu(check_min(code, decode_u16(code, data)?, 68)?),
+            ALL_SUBNETS_ARE_LOCAL => DhcpOption::AllSubnetsAreLocal(decode_bool(code, data)?),
+            BROADCAST_ADDRESS => DhcpOption::BroadcastAddress(decode_ip(code, data)?),
+            PERFORM_MASK_DISCOVERY => DhcpOption::PerformMaskDiscovery(decode_bool(code, data)?),
+            MASK_SUPPLIER => DhcpOption::MaskSupplier(decode_bool(code, data)?),
+            PERFORM_ROUTER_DISCOVERY => DhcpOption::PerformRouterDiscovery(decode_bool(code, data)?),
+            ROUTER_SOLICITATION_ADDRESS => {
+                DhcpOption::RouterSolicitationAddress(decode_ip(code, data)?)
+            }
+            STATIC_ROUTE => DhcpOption::StaticRoute(decode_ip_pairs(code, data)?),
+            TRAILER_ENCAPSULATION => DhcpOption::TrailerEncapsulation(decode_bool(code, data)?),
+            ARP_CACHE_TIMEOUT => DhcpOption::ArpCacheTimeout(decode_u32(code, data)?),
+            ETHERNET_ENCAPSULATION => DhcpOption::EthernetEncapsulation(decode_bool(code, data)?),
+            TCP_DEFAULT_TTL => DhcpOption::TcpDefaultTtl(check_min(code, decode_u8(code, data)?, 1)?),
+            TCP_KEEPALIVE_INTERVAL => DhcpOption::TcpKeepaliveInterval(decode_u32(code, data)?),
+            TCP_KEEPALIVE_GARBAGE => DhcpOption::TcpKeepaliveGarbage(decode_bool(code, data)?),
+            NETWORK_INFORMATION_SERVICE_DOMAIN => {
+                DhcpOption::NetworkInformationServiceDomain(decode_string(code, data)?)
+            }
+            NETWORK_INFORMATION_SERVERS => {
+                DhcpOption::NetworkInformationServers(decode_ips(code, data)?)
+            }
+            NETWORK_TIME_PROTOCOL_SERVERS => DhcpOption::NtpServers(decode_ips(code, data)?),
+            VENDOR_SPECIFIC_INFORMATION => {
+                DhcpOption::VendorSpecificInformation(decode_bytes(code, data, 1)?)
+            }
+            NETBIOS_OVER_TCPIP_NAME_SERVER => DhcpOption::NetbiosNameServers(decode_ips(code, data)?),
+            NETBIOS_OVER_TCPIP_DATAGRAM_DISTRIBUTION_SERVER => {
+                DhcpOption::NetbiosDatagramDistributionServers(decode_ips(code, data)?)
+            }
+            NETBIOS_OVER_TCPIP_NODE_TYPE => {
+                DhcpOption::NetbiosNodeType(check_node_type(decode_u8(code, data)?)?)
+            }
+            NETBIOS_OVER_TCPIP_SCOPE => DhcpOption::NetbiosScope(decode_string(code, data)?),
+            XWINDOW_SYSTEM_FONT_SERVER => DhcpOption::XWindowFontServers(decode_ips(code, data)?),
+            XWINDOW_SYSTEM_DISPLAY_MANAGER => {
+                DhcpOption::XWindowDisplayManagers(decode_ips(code, data)?)
+            }
+            NETWORK_INFORMATION_SERVICEPLUS_DOMAIN => {
+                DhcpOption::NetworkInformationServicePlusDomain(decode_string(code, data)?)
+            }
+            NETWORK_INFORMATION_SERVICEPLUS_SERVERS => {
+                DhcpOption::NetworkInformationServicePlusServers(decode_ips(code, data)?)
+            }
+            TFTP_SERVER_NAME => DhcpOption::TftpServerName(decode_string(code, data)?),
+            BOOTFILE_NAME => DhcpOption::BootfileName(decode_string(code, data)?),
+            MOBILE_IP_HOME_AGENT => {
+                // The only address list allowed to be empty
+                match data.is_empty() {
+                    true => DhcpOption::MobileIpHomeAgents(vec![]),
+                    false => DhcpOption::MobileIpHomeAgents(decode_ips(code, data)?),
+                }
+            }
+            SIMPLE_MAIL_TRANSPORT_PROTOCOL => DhcpOption::SmtpServers(decode_ips(code, data)?),
+            POST_OFFICE_PROTOCOL_SERVER => DhcpOption::Pop3Servers(decode_ips(code, data)?),
+            NETWORK_NEWS_TRANSPORT_PROTOCOL => DhcpOption::NntpServers(decode_ips(code, data)?),
+            DEFAULT_WORLD_WIDE_WEB_SERVER => DhcpOption::WwwServers(decode_ips(code, data)?),
+            DEFAULT_FINGER_SERVER => DhcpOption::FingerServers(decode_ips(code, data)?),
+            DEFAULT_INTERNET_RELAY_CHAT_SERVER => DhcpOption::IrcServers(decode_ips(code, data)?),
+            STREETTALK_SERVER => DhcpOption::StreetTalkServers(decode_ips(code, data)?),
+            STREETTALK_DIRECTORY_ASSISTANCE => {
+                DhcpOption::StreetTalkDirectoryAssistanceServers(decode_ips(code, data)?)
+            }
+            REQUESTED_IP_ADDRESS => DhcpOption::RequestedIpAddress(decode_ip(code, data)?),
+            IP_ADDRESS_LEASE_TIME => DhcpOption::IpAddressLeaseTime(decode_u32(code, data)?),
+            OPTION_OVERLOAD => {
+                DhcpOption::OptionOverload(OptionOverLoadCode::try_from(decode_u8(code, data)?)?)
+            }
+            DHCP_MESSAGE_TYPE => {
+                DhcpOption::DhcpMessageType(DhcpMessageTypeCode::try_from(decode_u8(code, data)?)?)
+            }
+            SERVER_IDENTIFIER => DhcpOption::ServerIdentifier(decode_ip(code, data)?),
+            PARAMETER_REQUEST_LIST => {
+                DhcpOption::ParameterRequestList(decode_bytes(code, data, 1)?)
+            }
+            MESSAGE => DhcpOption::Message(decode_string(code, data)?),
+            MAXIMUM_DHCP_MESSAGE_SIZE => {
+                DhcpOption::MaximumDhcpMessageSize(decode_u16(code, data)?)
+            }
+            RENEWAL_TIME_VALUE => DhcpOption::RenewalTimeValue(decode_u32(code, data)?),
+            REBINDING_TIME_VALUE => DhcpOption::RebindingTimeValue(decode_u32(code, data)?),
+            CLASS_IDENTIFIER => DhcpOption::ClassIdentifier(decode_bytes(code, data, 1)?),
+            CLIENT_IDENTIFIER => DhcpOption::ClientIdentifier(decode_bytes(code, data, 2)?),
+            CLIENT_FQDN => DhcpOption::ClientFqdn(ClientFqdn::decode(data)?),
+            RELAY_AGENT_INFORMATION => {
+                DhcpOption::RelayAgentInformation(decode_relay_agent_information(data)?)
+            }
+            USER_CLASS => match decode_user_classes(data) {
+                Some(classes) => DhcpOption::UserClass(classes),
+                None => DhcpOption::Unrecognized(RawDhcpOption::new(code, data.to_vec())),
+            },
+            CLIENT_ARCHITECTURE => DhcpOption::ClientArchitecture(decode_u16s(code, data)?),
+            IPXE_ENCAPSULATED => DhcpOption::IpxeEncapsulated(decode_ipxe_options(data)?),
+            SUBNET_SELECTION => DhcpOption::SubnetSelection(decode_ip(code, data)?),
+            DOMAIN_SEARCH => DhcpOption::DomainSearch(decode_domain_search(code, data)?),
+            CLASSLESS_ROUTE_FORMAT => {
+                DhcpOption::ClasslessStaticRoutes(decode_classless_routes(code, data)?)
+            }
+            MS_CLASSLESS_ROUTE_FORMAT => {
+                DhcpOption::MsClasslessStaticRoutes(decode_classless_routes(code, data)?)
+            }
+            _ => DhcpOption::Unrecognized(RawDhcpOption::new(code, data.to_vec())),
+        })
+    }
+
+    /// Serializes the payload of the option (without code and length octets).
+    ///
+    /// The same length rules as in [`DhcpOption::decode`] apply. The payload
+    /// may be longer than 255 octets, see [`encode_options`].
+    pub fn encode(&self) -> ConvertResult<Vec<u8>> {
+        let code = self.code();
+        let data = match self {
+            DhcpOption::PadOption | DhcpOption::EndOption => {
+                return Err(ConvertPacketError::InvalidOptionValue(code))
+            }
+            DhcpOption::Unrecognized(raw) => raw.data.clone(),
+            DhcpOption::SubnetMask(ip)
+            | DhcpOption::SwapServer(ip)
+            | DhcpOption::RequestedIpAddress(ip)
+            | DhcpOption::BroadcastAddress(ip)
+            | DhcpOption::RouterSolicitationAddress(ip)
+            | DhcpOption::ServerIdentifier(ip)
+            | DhcpOption::SubnetSelection(ip) => encode_ip(code, ip)?.to_vec(),
+            DhcpOption::TimeOffset(v)
+            | DhcpOption::PathMtuAgingTimeout(v)
+            | DhcpOption::ArpCacheTimeout(v)
+            | DhcpOption::TcpKeepaliveInterval(v)
+            | DhcpOption::IpAddressLeaseTime(v)
+            | DhcpOption::RenewalTimeValue(v)
+            | DhcpOption::RebindingTimeValue(v) => v.to_be_bytes().to_vec(),
+            DhcpOption::Routers(ips)
+            | DhcpOption::TimeServers(ips)
+            | DhcpOption::NameServers(ips)
+            | DhcpOption::DomainNameServers(ips)
+            | DhcpOption::LogServers(ips)
+            | DhcpOption::CookieServers(ips)
+            | DhcpOption::LprServers(ips)
+            | DhcpOption::ImpressServers(ips)
+            | DhcpOption::ResourceLocationServers(ips)
+            | DhcpOption::NetworkInformationServers(ips)
+            | DhcpOption::NtpServers(ips)
+            | DhcpOption::NetbiosNameServers(ips)
+            | DhcpOption::NetbiosDatagramDistributionServers(ips)
+            | DhcpOption::XWindowFontServers(ips)
+            | DhcpOption::XWindowDisplayManagers(ips)
+            | DhcpOption::NetworkInformationServicePlusServers(ips)
+            | DhcpOption::SmtpServers(ips)
+            | DhcpOption::Pop3Servers(ips)
+            | DhcpOption::NntpServers(ips)
+            | DhcpOption::WwwServers(ips)
+            | DhcpOption::FingerServers(ips)
+            | DhcpOption::IrcServers(ips)
+            | DhcpOption::StreetTalkServers(ips)
+            | DhcpOption::StreetTalkDirectoryAssistanceServers(ips) => encode_ips(code, ips)?,
+            DhcpOption::MobileIpHomeAgents(ips) => match ips.is_empty() {
+                true => vec![],
+                false => encode_ips(code, ips)?,
+            },
+            DhcpOption::PolicyFilter(pairs) | DhcpOption::StaticRoute(pairs) => {
+                encode_ip_pairs(code, pairs)?
+            }
+            DhcpOption::IpForwarding(b)
+            | DhcpOption::NonLocalSourceRouting(b)
+            | DhcpOption::AllSubnetsAreLocal(b)
+            | DhcpOption::PerformMaskDiscovery(b)
+            | DhcpOption::MaskSupplier(b)
+            | DhcpOption::PerformRouterDiscovery(b)
+            | DhcpOption::TrailerEncapsulation(b)
+            | DhcpOption::EthernetEncapsulation(b)
+            | DhcpOption::TcpKeepaliveGarbage(b) => vec![*b as u8],
+            DhcpOption::DefaultIpTtl(v) | DhcpOption::TcpDefaultTtl(v) => {
+                vec![check_min(code, *v, 1)?]
+            }
+            DhcpOption::NetbiosNodeType(v) => vec![check_node_type(*v)?],
+            DhcpOption::MaximumDatagramReassemblySize(v) => {
+                check_min(code, *v, 576)?.to_be_bytes().to_vec()
+            }
+            DhcpOption::InterfaceMtu(v) => check_min(code, *v, 68)?.to_be_bytes().to_vec(),
+            DhcpOption::PathMtuPlateauTable(mtus) => {
+                if mtus.is_empty() {
+                    return Err(ConvertPacketError::InvalidOptionLength(code));
+                }
+                let mut data = Vec::with_capacity(mtus.len() * 2);
+                for mtu in mtus {
+                    data.extend_from_slice(&check_min(code, *mtu, 68)?.to_be_bytes());
+                }
+                data
+            }
+            DhcpOption::HostName(s)
+            | DhcpOption::MeritDumpFile(s)
+            | DhcpOption::DomainName(s)
+            | DhcpOption::RootPath(s)
+            | DhcpOption::ExtensionsPath(s)
+            | DhcpOption::NetworkInformationServiceDomain(s)
+            | DhcpOption::NetbiosScope(s)
+            | DhcpOption::NetworkInformationServicePlusDomain(s)
+            | DhcpOption::TftpServerName(s)
+            | DhcpOption::BootfileName(s)
+            | DhcpOption::Message(s) => encode_bytes(code, s.as_bytes(), 1)?,
+            DhcpOption::BootFileSize(v) | DhcpOption::MaximumDhcpMessageSize(v) => {
+                v.to_be_bytes().to_vec()
+            }
+            DhcpOption::OptionOverload(overload) => vec![*overload as u8],
+            DhcpOption::DhcpMessageType(msg_type) => vec![*msg_type as u8],
+            DhcpOption::ParameterRequestList(v)
+            | DhcpOption::ClassIdentifier(v)
+            | DhcpOption::VendorSpecificInformation(v) => encode_bytes(code, v, 1)?,
+            DhcpOption::ClientIdentifier(v) => encode_bytes(code, v, 2)?,
+            DhcpOption::ClientFqdn(fqdn) => fqdn.encode()?,
+            DhcpOption::RelayAgentInformation(sub_options) => {
+                encode_relay_agent_information(sub_options)?
+            }
+            DhcpOption::ClientArchitecture(types) => {
+                if types.is_empty() {
+                    return Err(ConvertPacketError::InvalidOptionLength(code));
+                }
+                types.iter().flat_map(|t| t.to_be_bytes()).collect()
+            }
+            DhcpOption::UserClass(classes) => {
+                if classes.is_empty() {
+                    return Err(ConvertPacketError::InvalidOptionLength(code));
+                }
+                let mut data = Vec::new();
+                for class in classes {
+                    if class.is_empty() || class.len() > u8::MAX as usize {
+                        return Err(ConvertPacketError::InvalidOptionValue(code));
+                    }
+                    data.push(class.len() as u8);
+                    data.extend_from_slice(class);
+                }
+                data
+            }
+            DhcpOption::IpxeEncapsulated(sub_options) => encode_ipxe_options(sub_options)?,
+            DhcpOption::DomainSearch(names) => encode_domain_search(code, names)?,
+            DhcpOption::ClasslessStaticRoutes(routes)
+            | DhcpOption::MsClasslessStaticRoutes(routes) => encode_classless_routes(code, routes)?,
+        };
+        Ok(data)
+    }
+}
+
+impl OptionOverLoadCode {
+    pub fn overloads_file(&self) -> bool {
+        matches!(self, OptionOverLoadCode::OverloadFile | OptionOverLoadCode::OverloadBoth)
+    }
+    pub fn overloads_sname(&self) -> bool {
+        matches!(self, OptionOverLoadCode::OverloadSname | OptionOverLoadCode::OverloadBoth)
+    }
+}
+
+impl TryFrom<u8> for OptionOverLoadCode {
+    type Error = ConvertPacketError;
+    fn try_from(value: u8) -> ConvertResult<Self> {
+        match value {
+            1 => Ok(OptionOverLoadCode::OverloadFile),
+            2 => Ok(OptionOverLoadCode::OverloadSname),
+            3 => Ok(OptionOverLoadCode::OverloadBoth),
+            _ => Err(ConvertPacketError::InvalidOptionValue(OPTION_OVERLOAD)),
+        }
+    }
+}
+
+impl TryFrom<u8> for DhcpMessageTypeCode {
+    type Error = ConvertPacketError;
+    fn try_from(value: u8) -> ConvertResult<Self> {
+        match value {
+            1 => Ok(DhcpMessageTypeCode::Discover),
+            2 => Ok(DhcpMessageTypeCode::Offer),
+            3 => Ok(DhcpMessageTypeCode::Request),
+            4 => Ok(DhcpMessageTypeCode::Decline),
+            5 => Ok(DhcpMessageTypeCode::Ack),
+            6 => Ok(DhcpMessageTypeCode::Nak),
+            7 => Ok(DhcpMessageTypeCode::Release),
+            8 => Ok(DhcpMessageTypeCode::Inform),
+            _ => Err(ConvertPacketError::UnrecognizedMessageType),
         }
     }
 }
-#[derive(PartialEq, Eq, Hash, Debug)]
+
+/// Reads the options of one area (the options field, or an overloaded
+/// `file`/`sname` field) into `raw` without interpreting the payloads.
+///
+/// Pad options are skipped and reading stops at the End option; an area that
+/// runs out without an End option is accepted as long as the last option is
+/// complete. Following RFC 3396, an option that appears more than once (in
+/// this area or in one read before) has its payloads concatenated in order.
+pub fn read_options(area: &[u8], raw: &mut Vec<RawDhcpOption>) -> ConvertResult<()> {
+    let mut i = 0;
+    while i < area.len() {
+        let code = area[i];
+        match code {
+            PAD_OPTION => {
+                i += 1;
+                continue;
+            }
+            END_OPTION => break,
+            _ => {}
+        }
+        let len = *area
+            .get(i + 1)
+            .ok_or(ConvertPacketError::InvalidOptionLength(code))? as usize;
+        let data = area
+            .get(i + 2..i + 2 + len)
+            .ok_or(ConvertPacketError::InvalidOptionLength(code))?;
+        match raw.iter_mut().find(|opt| opt.code == code) {
+            Some(opt) => opt.data.extend_from_slice(data),
+            None => raw.push(RawDhcpOption::new(code, data.to_vec())),
+        }
+        i += 2 + len;
+    }
+    Ok(())
+}
+
+/// Decodes a single options area, see [`read_options`].
+pub fn decode_options(area: &[u8]) -> ConvertResult<Vec<DhcpOption>> {
+    let mut raw = Vec::new();
+    read_options(area, &mut raw)?;
+    raw.iter().map(RawDhcpOption::decode_or_raw).collect()
+}
+
+/// Encodes `options` as code/length/value triples in the order they are put
+/// on the wire. The End option is not appended.
+///
+/// A payload longer than 255 octets is split into several consecutive
+/// instances of the same option (RFC 3396).
+///
+/// Pad and End entries are skipped, raw ones too, and so is Option Overload
+/// which the packet encoder adds when it needs the `file`/`sname` fields. The
+/// Subnet Mask option is moved in front of the Routers option, and the DHCP
+/// Message Type is written first (RFC 2132 section 3.3).
+pub fn encode_options(options: &[DhcpOption]) -> ConvertResult<Vec<Vec<u8>>> {
+    let mut ordered: Vec<&DhcpOption> = options
+        .iter()
+        // By code, so that unrecognized options with these codes go too
+        .filter(|opt| !matches!(opt.code(), PAD_OPTION | END_OPTION | OPTION_OVERLOAD))
+        .collect();
+    ordered.sort_by_key(|opt| match opt.code() {
+        DHCP_MESSAGE_TYPE => 0,
+        SUBNET_MASK => 1,
+        _ => 2,
+    });
+    let mut encoded = Vec::with_capacity(ordered.len());
+    for opt in ordered {
+        let data = opt.encode()?;
+        // Zero-length options (e.g. Rapid Commit) still need one instance
+        let chunks: Vec<&[u8]> = match data.is_empty() {
+            true => vec![&data[..]],
+            false => data.chunks(u8::MAX as usize).collect(),
+        };
+        for chunk in chunks {
+            let mut tlv = Vec::with_capacity(2 + chunk.len());
+            tlv.push(opt.code());
+            tlv.push(chunk.len() as u8);
+            tlv.extend_from_slice(chunk);
+            encoded.push(tlv);
+        }
+    }
+    Ok(encoded)
+}
+
+fn encode_ip(code: u8, ip: &IpAddr) -> ConvertResult<[u8; 4]> {
+    match ip {
+        IpAddr::V4(ipv4) => Ok(ipv4.octets()),
+        IpAddr::V6(_) => Err(ConvertPacketError::InvalidOptionValue(code)),
+    }
+}
+fn encode_ips(code: u8, ips: &[IpAddr]) -> ConvertResult<Vec<u8>> {
+    if ips.is_empty() {
+        return Err(ConvertPacketError::InvalidOptionLength(code));
+    }
+    let mut data = Vec::with_capacity(ips.len() * 4);
+    for ip in ips {
+        data.extend_from_slice(&encode_ip(code, ip)?);
+    }
+    Ok(data)
+}
+// Address pairs (policy filters, static routes): length a multiple of 8
+fn encode_ip_pairs(code: u8, pairs: &[(IpAddr, IpAddr)]) -> ConvertResult<Vec<u8>> {
+    if pairs.is_empty() {
+        return Err(ConvertPacketError::InvalidOptionLength(code));
+    }
+    let mut data = Vec::with_capacity(pairs.len() * 8);
+    for (first, second) in pairs {
+        data.extend_from_slice(&encode_ip(code, first)?);
+        data.extend_from_slice(&encode_ip(code, second)?);
+    }
+    Ok(data)
+}
+// RFC 3442 destination descriptors: the prefix length followed by only the
+// significant octets of the destination, e.g. 10.17.0.0/16 is 16.10.17
+fn encode_classless_routes(code: u8, routes: &[(Ipv4Net, Ipv4Addr)]) -> ConvertResult<Vec<u8>> {
+    if routes.is_empty() {
+        return Err(ConvertPacketError::InvalidOptionLength(code));
+    }
+    let mut data = Vec::new();
+    for (destination, router) in routes {
+        let significant = (destination.get_prefix_len() as usize).div_ceil(8);
+        data.push(destination.get_prefix_len());
+        data.extend_from_slice(&destination.network().octets()[..significant]);
+        data.extend_from_slice(&router.octets());
+    }
+    Ok(data)
+}
+fn encode_bytes(code: u8, data: &[u8], min_len: usize) -> ConvertResult<Vec<u8>> {
+    if data.len() < min_len {
+        return Err(ConvertPacketError::InvalidOptionLength(code));
+    }
+    Ok(data.to_vec())
+}
+
+fn decode_u8(code: u8, data: &[u8]) -> ConvertResult<u8> {
+    match data {
+        [b] => Ok(*b),
+        _ => Err(ConvertPacketError::InvalidOptionLength(code)),
+    }
+}
+fn decode_u16(code: u8, data: &[u8]) -> ConvertResult<u16> {
+    let array: [u8; 2] = data
+        .try_into()
+        .map_err(|_| ConvertPacketError::InvalidOptionLength(code))?;
+    Ok(u16::from_be_bytes(array))
+}
+fn decode_u32(code: u8, data: &[u8]) -> ConvertResult<u32> {
+    let array: [u8; 4] = data
+        .try_into()
+        .map_err(|_| ConvertPacketError::InvalidOptionLength(code))?;
+    Ok(u32::from_be_bytes(array))
+}
+fn decode_ip(code: u8, data: &[u8]) -> ConvertResult<IpAddr> {
+    Ok(IpAddr::V4(Ipv4Addr::from(decode_u32(code, data)?)))
+}
+// Address lists: at least one address, length a multiple of 4
+fn decode_ips(code: u8, data: &[u8]) -> ConvertResult<Vec<IpAddr>> {
+    if data.is_empty() || !data.len().is_multiple_of(4) {
+        return Err(ConvertPacketError::InvalidOptionLength(code));
+    }
+    data.chunks(4).map(|ip| decode_ip(code, ip)).collect()
+}
+fn decode_ip_pairs(code: u8, data: &[u8]) -> ConvertResult<Vec<(IpAddr, IpAddr)>> {
+    if data.is_empty() || !data.len().is_multiple_of(8) {
+        return Err(ConvertPacketError::InvalidOptionLength(code));
+    }
+    data.chunks(8)
+        .map(|pair| Ok((decode_ip(code, &pair[..4])?, decode_ip(code, &pair[4..])?)))
+        .collect()
+}
+fn decode_mtus(code: u8, data: &[u8]) -> ConvertResult<Vec<u16>> {
+    if data.is_empty() || !data.len().is_multiple_of(2) {
+        return Err(ConvertPacketError::InvalidOptionLength(code));
+    }
+    data.chunks(2)
+        .map(|mtu| check_min(code, decode_u16(code, mtu)?, 68))
+        .collect()
+}
+// RFC 3004 section 4: each class is a length octet and at least one octet of
+// data, `None` when `data` is not such a list
+fn decode_user_classes(data: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut classes = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let len = data[i] as usize;
+        if len == 0 {
+            return None;
+        }
+        classes.push(data.get(i + 1..i + 1 + len)?.to_vec());
+        i += 1 + len;
+    }
+    match classes.is_empty() {
+        true => None,
+        false => Some(classes),
+    }
+}
+fn decode_u16s(code: u8, data: &[u8]) -> ConvertResult<Vec<u16>> {
+    if data.is_empty() || !data.len().is_multiple_of(2) {
+        return Err(ConvertPacketError::InvalidOptionLength(code));
+    }
+    data.chunks(2).map(|v| decode_u16(code, v)).collect()
+}
+fn decode_classless_routes(code: u8, data: &[u8]) -> ConvertResult<Vec<(Ipv4Net, Ipv4Addr)>> {
+    if data.is_empty() {
+        return Err(ConvertPacketError::InvalidOptionLength(code));
+    }
+    let mut routes = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let prefix_len = data[i];
+        if prefix_len > 32 {
+            return Err(ConvertPacketError::InvalidOptionValue(code));
+        }
+        let significant = (prefix_len as usize).div_ceil(8);
+        let descriptor = data
+            .get(i + 1..i + 1 + significant + 4)
+            .ok_or(ConvertPacketError::InvalidOptionLength(code))?;
+        let mut destination = [0u8; 4];
+        destination[..significant].copy_from_slice(&descriptor[..significant]);
+        // Bits beyond the prefix length are not part of the destination
+        let destination = Ipv4Net::new(Ipv4Addr::from(destination), prefix_len)
+            .and_then(|net| Ipv4Net::new(net.network(), prefix_len))
+            .map_err(|_| ConvertPacketError::InvalidOptionValue(code))?;
+        let router = Ipv4Addr::from(decode_u32(code, &descriptor[significant..])?);
+        routes.push((destination, router));
+        i += 1 + significant + 4;
+    }
+    Ok(routes)
+}
+fn decode_bool(code: u8, data: &[u8]) -> ConvertResult<bool> {
+    match decode_u8(code, data)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(ConvertPacketError::InvalidOptionValue(code)),
+    }
+}
+fn check_min<T: PartialOrd>(code: u8, value: T, min: T) -> ConvertResult<T> {
+    match value >= min {
+        true => Ok(value),
+        false => Err(ConvertPacketError::InvalidOptionValue(code)),
+    }
+}
+// NetBIOS node types are B-node, P-node, M-node and H-node
+fn check_node_type(node_type: u8) -> ConvertResult<u8> {
+    match node_type {
+        0x1 | 0x2 | 0x4 | 0x8 => Ok(node_type),
+        _ => Err(ConvertPacketError::InvalidOptionValue(NETBIOS_OVER_TCPIP_NODE_TYPE)),
+    }
+}
+fn decode_bytes(code: u8, data: &[u8], min_len: usize) -> ConvertResult<Vec<u8>> {
+    if data.len() < min_len {
+        return Err(ConvertPacketError::InvalidOptionLength(code));
+    }
+    Ok(data.to_vec())
+}
+// NVT ASCII strings, some clients append a trailing NUL which is dropped
+fn decode_string(code: u8, data: &[u8]) -> ConvertResult<String> {
+    let data = decode_bytes(code, data, 1)?;
+    let mut s = String::from_utf8(data).map_err(|_| ConvertPacketError::NonUtf8String)?;
+    while s.ends_with('\0') {
+        s.pop();
+    }
+    Ok(s)
+}
+/// An option as it appears on the wire: its code and payload, without the
+/// length octet.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct RawDhcpOption {
+    code: u8,
+    data: Vec<u8>,
+}
+
+impl RawDhcpOption {
+    pub fn new(code: u8, data: Vec<u8>) -> RawDhcpOption {
+        RawDhcpOption { code, data }
+    }
+    pub fn get_code(&self) -> u8 {
+        self.code
+    }
+    pub fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+    /// Parses the payload into a typed option, see [`DhcpOption::decode`].
+    pub fn decode(&self) -> ConvertResult<DhcpOption> {
+        DhcpOption::decode(self.code, &self.data)
+    }
+    /// Like [`RawDhcpOption::decode`], but a payload its typed variant rejects,
+    /// e.g. an Interface MTU below 68, is kept as `Unrecognized`. Only the DHCP
+    /// Message Type and Option Overload, which tell how to read the packet,
+    /// must be valid.
+    pub fn decode_or_raw(&self) -> ConvertResult<DhcpOption> {
+        match (self.decode(), self.code) {
+            (Ok(opt), _) => Ok(opt),
+            (Err(e), DHCP_MESSAGE_TYPE | OPTION_OVERLOAD) => Err(e),
+            (Err(_), _) => Ok(DhcpOption::Unrecognized(self.clone())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum OptionOverLoadCode {
   OverloadFile = 1,
   OverloadSname = 2,
   OverloadBoth = 3,
 }
 
-#[derive(PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum DhcpMessageTypeCode {
     Discover = 1,
     Offer = 2,
@@ -149,6 +900,7 @@ pub enum DhcpMessageTypeCode {
     Ack = 5,
     Nak = 6,
     Release = 7,
+    Inform = 8,
 }
 
 // DHCP OP Field
@@ -260,14 +1012,25 @@ pub const TFTP_SERVER_NAME: u8 = 66;
 pub const BOOTFILE_NAME: u8 = 67;
 
 pub const USER_CLASS: u8 = 77;
-// No support for DHCPv4 options in [RFC 4702](https://datatracker.ietf.org/doc/html/rfc4702)
-// pub const CLIENT_FQDN: u8 = 81;
+// Add in [RFC 4702](https://datatracker.ietf.org/doc/html/rfc4702)
+pub const CLIENT_FQDN: u8 = 81;
+// Add in RFC 4578
 pub const CLIENT_ARCHITECTURE: u8 = 93;
 pub const TZ_POSIX_STRING: u8 = 100;
 pub const TZ_DATABASE_STRING: u8 = 101;
 
+// Etherboot and iPXE
+pub const IPXE_ENCAPSULATED: u8 = 175;
+
+// Add in RFC 3011
+pub const SUBNET_SELECTION: u8 = 118;
+// Add in RFC 3397
+pub const DOMAIN_SEARCH: u8 = 119;
+
 // Add in RFC 3442 (obsoletes the static route option in RFC 2132 option 33)
 pub const CLASSLESS_ROUTE_FORMAT: u8 = 121;
+// Pre-standard copy of option 121 used by Microsoft clients
+pub const MS_CLASSLESS_ROUTE_FORMAT: u8 = 249;
 
 // No support for DHCPv4 options in [RFC 3925](https://datatracker.ietf.org/doc/html/rfc3925)
 // E.g. option code 120 (SIP) 129 143 184
@@ -349,6 +1112,7 @@ pub fn title(code: u8) -> Option<&'static str> {
         STREETTALK_SERVER => "StreetTalk Server",
         STREETTALK_DIRECTORY_ASSISTANCE => "StreetTalk Directory Assistance (STDA) Server",
 
+        CLIENT_FQDN => "Client FQDN",
         RELAY_AGENT_INFORMATION => "Relay Agent Information",
 
         // DHCP EXTENSIONS
@@ -375,13 +1139,12 @@ pub fn title(code: u8) -> Option<&'static str> {
 
         TZ_POSIX_STRING => "TZ-POSIX String",
         TZ_DATABASE_STRING => "TZ-Database String",
+        IPXE_ENCAPSULATED => "Etherboot/iPXE",
+        SUBNET_SELECTION => "Subnet Selection",
+        DOMAIN_SEARCH => "Domain Search",
         CLASSLESS_ROUTE_FORMAT => "Classless Route Format",
+        MS_CLASSLESS_ROUTE_FORMAT => "Microsoft Classless Static Route",
 
         _ => return None,
     })
 }
-
-
-pub fn test_options() {
-    println!("[TEST] test_options");
-}
\ No newline at end of file
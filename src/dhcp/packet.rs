@@ -1,58 +1,92 @@
 use crate::dhcp::*;
+use crate::macaddress::MacAddress;
+use std::error::Error;
+use std::fmt;
 use std::net::{IpAddr, Ipv4Addr};
 
-#[derive(Debug)]
-pub enum GetMessageTypeError {
-    NoMessageTypeOption,
-    WrongCode,
-}
-
-#[derive(Debug)]
-pub enum ConvertPacketError<T> {
-    ParseFromBytesError(T),
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConvertPacketError {
+    InvalidLength,
     NonUtf8String,
     UnrecognizedMessageType,
     InvalidHlen,
+    InvalidMagicCookie,
+    InvalidOptionLength(u8),
+    InvalidOptionValue(u8),
+    MessageTooLarge,
+}
+
+impl fmt::Display for ConvertPacketError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertPacketError::InvalidLength => write!(fmt, "Invalid packet length"),
+            ConvertPacketError::NonUtf8String => write!(fmt, "Non UTF-8 string"),
+            ConvertPacketError::UnrecognizedMessageType => write!(fmt, "Unrecognized message type"),
+            ConvertPacketError::InvalidHlen => write!(fmt, "Invalid hardware address length"),
+            ConvertPacketError::InvalidMagicCookie => write!(fmt, "Invalid magic cookie"),
+            ConvertPacketError::InvalidOptionLength(code) => {
+                write!(fmt, "Invalid length for option {}", code)
+            }
+            ConvertPacketError::InvalidOptionValue(code) => {
+                write!(fmt, "Invalid value for option {}", code)
+            }
+            ConvertPacketError::MessageTooLarge => write!(fmt, "Message exceeds maximum size"),
+        }
+    }
 }
+impl Error for ConvertPacketError {}
 
-trait FromBytes {
+trait FromBytes: Sized {
+    const SIZE: usize;
     fn from_be_bytes(a: &[u8]) -> Self;
 }
-// impl<const N: usize> FromBytes for [u8; N] {
-//     fn from_be_bytes(a: &[u8]) -> Self {
-//         let (int_bytes, rest) = a.split_at(N);
-//         let mut me = [0u8; N];
-//         me.copy_from_slice(int_bytes);
-//         *a = rest;
-//         me
-//     }
-// }
+impl<const N: usize> FromBytes for [u8; N] {
+    const SIZE: usize = N;
+    fn from_be_bytes(a: &[u8]) -> Self {
+        let mut me = [0u8; N];
+        me.copy_from_slice(a);
+        me
+    }
+}
 impl FromBytes for u64 {
+    const SIZE: usize = 8;
     fn from_be_bytes(a: &[u8]) -> u64 {
-        let array: [u8; 8] = a.try_into().expect("slice with incorrect length");
-        u64::from_be_bytes(array)
+        u64::from_be_bytes(<[u8; 8]>::from_be_bytes(a))
     }
 }
 impl FromBytes for u32 {
+    const SIZE: usize = 4;
     fn from_be_bytes(a: &[u8]) -> u32 {
-        let array: [u8; 4] = a.try_into().expect("slice with incorrect length");
-        u32::from_be_bytes(array)
+        u32::from_be_bytes(<[u8; 4]>::from_be_bytes(a))
     }
 }
 impl FromBytes for u16 {
+    const SIZE: usize = 2;
     fn from_be_bytes(a: &[u8]) -> Self {
-        let array: [u8; 2] = a.try_into().expect("slice with incorrect length");
-        u16::from_be_bytes(array)
+        u16::from_be_bytes(<[u8; 2]>::from_be_bytes(a))
     }
 }
 impl FromBytes for u8 {
+    const SIZE: usize = 1;
     fn from_be_bytes(a: &[u8]) -> Self {
         a[0]
     }
 }
 
-type ConvertPairResult<I, O> = Result<(I, O), ConvertPacketError<I>>;
-type ConvertSingleResult<T> = Result<T, ConvertPacketError<T>>;
+pub type ConvertResult<T> = Result<T, ConvertPacketError>;
+
+// Fixed BOOTP header (op .. file) is 236 octets, followed by the magic cookie
+pub const BOOTP_HEADER_LEN: usize = 236;
+pub const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+pub const OPTIONS_OFFSET: usize = BOOTP_HEADER_LEN + MAGIC_COOKIE.len();
+pub const MAX_HLEN: u8 = 16;
+
+// A BOOTP message is never shorter than 300 octets (RFC 951), shorter replies are padded
+pub const BOOTP_MIN_LEN: usize = 300;
+// Every DHCP client accepts a 576 octet IP datagram (RFC 2131 section 2), the
+// Maximum DHCP Message Size option counts the IP and UDP headers as well
+pub const MIN_MAX_MESSAGE_SIZE: u16 = 576;
+pub const IP_UDP_HEADER_LEN: usize = 28;
 
 /// A DHCP Message structure described in [RFC 2131](https://datatracker.ietf.org/doc/html/rfc2131).
 ///
@@ -88,73 +122,74 @@ pub struct Packet {
     // broadcast: bool,
     // loopback: bool,
 }
-fn custom_take<'a>(n: usize) -> impl Fn(&'a [u8]) -> ConvertPairResult<&'a [u8], &'a [u8]> {
-    move |input: &'a [u8]| {
-        if input.len() >= n {
-            Ok((&input[n..], &input[0..n]))
-        } else {
-            Err(ConvertPacketError::InvalidHlen)
+impl Packet {
+    // Read a big-endian `T` at `offset`, the width is taken from `T` itself
+    fn decode_with_offset<T: FromBytes>(input: &[u8], offset: usize) -> ConvertResult<T> {
+        match input.get(offset..offset + T::SIZE) {
+            Some(bytes) => Ok(T::from_be_bytes(bytes)),
+            None => Err(ConvertPacketError::InvalidLength),
         }
     }
-}
-impl Packet {
-    fn decode_with_offset<T: FromBytes>(
-        input: &[u8],
-        offset: usize,
-        n: usize,
-    ) -> ConvertSingleResult<T> {
-        if input.len() < offset {
+    fn decode(input: &[u8]) -> ConvertResult<Packet> {
+        // Fixed header and magic cookie must be present before any option
+        if input.len() < OPTIONS_OFFSET {
+            return Err(ConvertPacketError::InvalidLength);
+        }
+        let op = Self::decode_with_offset::<u8>(input, 0)?;
+        let htype = Self::decode_with_offset::<u8>(input, 1)?;
+        let hlen = Self::decode_with_offset::<u8>(input, 2)?;
+        let hops = Self::decode_with_offset::<u8>(input, 3)?;
+        let xid = Self::decode_with_offset::<u32>(input, 4)?;
+        let secs = Self::decode_with_offset::<u16>(input, 8)?;
+        let flags = Self::decode_with_offset::<u16>(input, 10)?;
+        let ciaddr = Self::decode_with_offset::<u32>(input, 12)?;
+        let yiaddr = Self::decode_with_offset::<u32>(input, 16)?;
+        let siaddr = Self::decode_with_offset::<u32>(input, 20)?;
+        let giaddr = Self::decode_with_offset::<u32>(input, 24)?;
+        let chaddr = Self::decode_with_offset::<[u8; 16]>(input, 28)?;
+        let sname = Self::decode_with_offset::<[u8; 64]>(input, 44)?;
+        let file = Self::decode_with_offset::<[u8; 128]>(input, 108)?;
+        if hlen > MAX_HLEN {
             return Err(ConvertPacketError::InvalidHlen);
-        } else {
-            Ok(T::from_be_bytes(&input[offset..offset + n]))
         }
-    }
-    fn decode(input: &[u8]) -> ConvertSingleResult<Packet> {
-        // let op = decode_op_with_offset(input, 0)?;
-        // let htype = decode_htype_with_offset(input, 1)?;
-        // let hlen = decode_hlen_with_offset(input, 2)?;
-        // let hops = decode_hops_with_offset(input, 3)?;
-        // let xid = decode_xid_with_offset(input, 4)?;
-        // let secs = decode_secs_with_offset(input, 8)?;
-        // let flags = decode_flags_with_offset(input, 10)?;
-        // let ciaddr = decode_ciaddr_with_offset(input, 12)?;
-        // let yiaddr = decode_yiaddr_with_offset(input, 16)?;
-        // let siaddr = decode_siaddr_with_offset(input, 20)?;
-        // let giaddr = decode_giaddr_with_offset(input, 24)?;
 
-        // TODO
-        // decode to new slice
-        // let chaddr = decode_chaddr_with_offset(input, 28)?;
-        // let sname = decode_sname_with_offset(input, 44)?;
-        // let file = decode_file_with_offset(input, 108)?;
-
-        // decode DHCP options
-        // let options = decode_options_with_offset(input, 236)?;
-
-        // Try poly func
-        let op = Self::decode_with_offset::<u8>(input, 0, 1).unwrap();
-        let htype = Self::decode_with_offset::<u8>(input, 1, 1).unwrap();
-        let hlen = Self::decode_with_offset::<u8>(input, 2, 1).unwrap();
-        let hops = Self::decode_with_offset::<u8>(input, 3, 1).unwrap();
-        let xid = Self::decode_with_offset::<u32>(input, 4, 4).unwrap();
-        let secs = Self::decode_with_offset::<u16>(input, 8, 2).unwrap();
-        let flags = Self::decode_with_offset::<u16>(input, 10, 2).unwrap();
-        // let ciaddr = Packet::decode_with_offset::
-        // let yiaddr = Packet::decode_with_offset::
-        // let siaddr = Packet::decode_with_offset::
-        // let giaddr = Packet::decode_with_offset::
+        let cookie = Self::decode_with_offset::<[u8; 4]>(input, BOOTP_HEADER_LEN)?;
+        if cookie != MAGIC_COOKIE {
+            return Err(ConvertPacketError::InvalidMagicCookie);
+        }
 
-        let ciaddr = todo!();
-        let yiaddr = todo!();
-        let siaddr = todo!();
-        let giaddr = todo!();
-        let chaddr = todo!();
-        let sname = todo!();
-        let file = todo!();
-        let options = todo!();
+        // read DHCP options (the end tag and any trailing padding are skipped)
+        let mut raw = Vec::new();
+        read_options(&input[OPTIONS_OFFSET..], &mut raw)?;
 
-        // skil the end tag byte
-        // let end_tag = decode_end_tag_with_offset(input, 236 + options.len())?;
+        // RFC 2131 section 4.1: with Option Overload the 'file' field is
+        // interpreted next, then the 'sname' field. Overloaded fields carry no
+        // name, so they are cleared once their options are merged.
+        let mut sname = sname;
+        let mut file = file;
+        let overload = match raw.iter().find(|opt| opt.get_code() == OPTION_OVERLOAD) {
+            Some(opt) => match opt.decode()? {
+                DhcpOption::OptionOverload(overload) => Some(overload),
+                _ => None,
+            },
+            None => None,
+        };
+        if let Some(overload) = overload {
+            if overload.overloads_file() {
+                read_options(&file, &mut raw)?;
+                file = [0u8; 128];
+            }
+            if overload.overloads_sname() {
+                read_options(&sname, &mut raw)?;
+                sname = [0u8; 64];
+            }
+        }
+        // Decode once all instances of an option are concatenated (RFC 3396).
+        // An option with an invalid value is kept raw, the packet is still served
+        let options = raw
+            .iter()
+            .map(RawDhcpOption::decode_or_raw)
+            .collect::<ConvertResult<Vec<DhcpOption>>>()?;
 
         Ok(Packet {
             op,
@@ -177,12 +212,6 @@ impl Packet {
         })
     }
 
-    // fn decode_op(input: &[u8], offset: usize) -> ConvertResult<u8> {
-    //     todo!()
-    // }
-    // fn decode_flags(input: &[u8], offset: usize) -> ConvertResult<u16>> {
-    //   todo!()
-    // }
     fn option(&self, code: u8) -> Option<&DhcpOption> {
         self.options.iter().find(|&opt| opt.code() == code)
     }
@@ -191,6 +220,7 @@ impl Packet {
 }
 
 impl Packet {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         op: u8,
         htype: u8,
@@ -226,20 +256,105 @@ impl Packet {
             options,
         }
     }
-    pub fn encode(&self, buf: &mut [u8; 2048]) -> &[u8] {
-        todo!()
-    }
-    pub fn decode_from_unchecked(bytes: &[u8]) -> Result<Packet, ConvertPacketError<Packet>> {
-        // Check the length of bytes
-        // RFC 2131
-        // debugonly
-        #[cfg(debug_assertions)]
-        {
-            println!("Packet bytes.len() = {}", bytes.len());
-            assert!(bytes.len() <= 576);
-        }
-        Self::decode(bytes);
-        todo!()
+    pub fn set_siaddr(&mut self, siaddr: u32) {
+        self.siaddr = siaddr;
+    }
+    pub fn set_giaddr(&mut self, giaddr: u32) {
+        self.giaddr = giaddr;
+    }
+    /// Set the server host name, NUL terminated so at most 63 octets.
+    pub fn set_sname(&mut self, sname: &str) -> ConvertResult<()> {
+        self.sname = [0; 64];
+        copy_c_string(&mut self.sname, sname)
+    }
+    /// Set the boot file name, NUL terminated so at most 127 octets.
+    pub fn set_file(&mut self, file: &str) -> ConvertResult<()> {
+        self.file = [0; 128];
+        copy_c_string(&mut self.file, file)
+    }
+    /// Encode the packet into `buf` for a client that did not announce a
+    /// Maximum DHCP Message Size, see [`Packet::encode_with_max_size`].
+    pub fn encode<'a>(&self, buf: &'a mut [u8; 2048]) -> ConvertResult<&'a [u8]> {
+        self.encode_with_max_size(buf, MIN_MAX_MESSAGE_SIZE)
+    }
+    /// Encode the packet into `buf` and return the bytes to send.
+    ///
+    /// `max_size` is the Maximum DHCP Message Size of the receiving client
+    /// (values below 576 are raised to 576). Options that do not fit are
+    /// reported as `MessageTooLarge` instead of being dropped.
+    pub fn encode_with_max_size<'a>(
+        &self,
+        buf: &'a mut [u8; 2048],
+        max_size: u16,
+    ) -> ConvertResult<&'a [u8]> {
+        let limit = (max_size.max(MIN_MAX_MESSAGE_SIZE) as usize - IP_UDP_HEADER_LEN).min(buf.len());
+        buf.fill(0);
+        buf[0] = self.op;
+        buf[1] = self.htype;
+        buf[2] = self.hlen;
+        buf[3] = self.hops;
+        buf[4..8].copy_from_slice(&self.xid.to_be_bytes());
+        buf[8..10].copy_from_slice(&self.secs.to_be_bytes());
+        buf[10..12].copy_from_slice(&self.flags.to_be_bytes());
+        buf[12..16].copy_from_slice(&self.ciaddr.to_be_bytes());
+        buf[16..20].copy_from_slice(&self.yiaddr.to_be_bytes());
+        buf[20..24].copy_from_slice(&self.siaddr.to_be_bytes());
+        buf[24..28].copy_from_slice(&self.giaddr.to_be_bytes());
+        buf[28..44].copy_from_slice(&self.chaddr);
+        buf[44..108].copy_from_slice(&self.sname);
+        buf[108..BOOTP_HEADER_LEN].copy_from_slice(&self.file);
+        buf[BOOTP_HEADER_LEN..OPTIONS_OFFSET].copy_from_slice(&MAGIC_COOKIE);
+
+        let options = encode_options(&self.options)?;
+        let (header, rest) = buf.split_at_mut(BOOTP_HEADER_LEN);
+        let (sname, file) = header[44..].split_at_mut(64);
+        let main = &mut rest[MAGIC_COOKIE.len()..limit - BOOTP_HEADER_LEN];
+
+        let needed: usize = options.iter().map(|opt| opt.len()).sum::<usize>() + 1;
+        let main_len = if needed <= main.len() {
+            fill_option_areas(&options, &mut [&mut main[..]])?[0]
+        } else {
+            // Spill into 'file' and then 'sname' (RFC 2131 section 4.1), but
+            // only into fields that do not already carry a name
+            let use_file = self.file.iter().all(|&b| b == 0);
+            let use_sname = self.sname.iter().all(|&b| b == 0);
+            // The Option Overload option itself stays in the options field
+            let main_cap = main.len().saturating_sub(3);
+            let mut areas: Vec<&mut [u8]> = vec![&mut main[..main_cap]];
+            if use_file {
+                areas.push(file);
+            }
+            if use_sname {
+                areas.push(sname);
+            }
+            let used = fill_option_areas(&options, &mut areas)?;
+            for (area, &len) in areas.iter_mut().zip(used.iter()).skip(1) {
+                if len > 0 {
+                    area[len] = END_OPTION;
+                }
+            }
+            let file_used = use_file && used[1] > 0;
+            let sname_used = use_sname && used[used.len() - 1] > 0;
+            let overload = match (file_used, sname_used) {
+                (true, true) => OptionOverLoadCode::OverloadBoth,
+                (false, true) => OptionOverLoadCode::OverloadSname,
+                _ => OptionOverLoadCode::OverloadFile,
+            };
+            let len = used[0];
+            main[len..len + 3].copy_from_slice(&[OPTION_OVERLOAD, 1, overload as u8]);
+            len + 3
+        };
+        let end = OPTIONS_OFFSET + main_len;
+        buf[end] = END_OPTION;
+        // Remaining octets are already zero, i.e. Pad options
+        Ok(&buf[..(end + 1).max(BOOTP_MIN_LEN)])
+    }
+    /// Decode a packet received from the wire.
+    ///
+    /// Nothing about `bytes` is assumed: truncated or malformed input of any
+    /// length is reported as a [`ConvertPacketError`].
+    pub fn decode_from_unchecked(bytes: &[u8]) -> ConvertResult<Packet> {
+        Self::decode(bytes)
     }
 
     // Inside the Packet
@@ -297,11 +412,142 @@ impl Packet {
         // Check from options field in Packet
         match self.option(DHCP_MESSAGE_TYPE) {
             Some(DhcpOption::DhcpMessageType(msg_type_code)) => Some(msg_type_code),
-            // Some(other_option) => Err(GetMessageTypeError::WrongCode),
-            // _ => Err(GetMessageTypeError::NoMessageTypeOption)
             _ => None,
         }
     }
+    /// Largest message the sender of this packet accepts, from its Maximum DHCP
+    /// Message Size option.
+    pub fn get_max_message_size(&self) -> u16 {
+        match self.option(MAXIMUM_DHCP_MESSAGE_SIZE) {
+            Some(DhcpOption::MaximumDhcpMessageSize(size)) => (*size).max(MIN_MAX_MESSAGE_SIZE),
+            _ => MIN_MAX_MESSAGE_SIZE,
+        }
+    }
+    pub fn get_parameter_request_list(&self) -> Option<&Vec<u8>> {
+        match self.option(PARAMETER_REQUEST_LIST) {
+            Some(DhcpOption::ParameterRequestList(prl)) => Some(prl),
+            _ => None,
+        }
+    }
+    pub fn get_host_name(&self) -> Option<&String> {
+        match self.option(HOST_NAME) {
+            Some(DhcpOption::HostName(name)) => Some(name),
+            _ => None,
+        }
+    }
+    pub fn get_client_fqdn(&self) -> Option<&ClientFqdn> {
+        match self.option(CLIENT_FQDN) {
+            Some(DhcpOption::ClientFqdn(fqdn)) => Some(fqdn),
+            _ => None,
+        }
+    }
+    pub fn get_relay_agent_information(&self) -> Option<&Vec<RelayAgentSubOption>> {
+        match self.option(RELAY_AGENT_INFORMATION) {
+            Some(DhcpOption::RelayAgentInformation(sub_options)) => Some(sub_options),
+            _ => None,
+        }
+    }
+    // Payload of the first relay agent sub-option with `code`, for policy matching
+    fn relay_sub_option(&self, code: u8) -> Option<&RelayAgentSubOption> {
+        self.get_relay_agent_information()?
+            .iter()
+            .find(|sub_option| sub_option.code() == code)
+    }
+    pub fn get_circuit_id(&self) -> Option<&[u8]> {
+        match self.relay_sub_option(AGENT_CIRCUIT_ID) {
+            Some(RelayAgentSubOption::CircuitId(id)) => Some(id),
+            _ => None,
+        }
+    }
+    pub fn get_remote_id(&self) -> Option<&[u8]> {
+        match self.relay_sub_option(AGENT_REMOTE_ID) {
+            Some(RelayAgentSubOption::RemoteId(id)) => Some(id),
+            _ => None,
+        }
+    }
+    pub fn get_subscriber_id(&self) -> Option<&str> {
+        match self.relay_sub_option(SUBSCRIBER_ID) {
+            Some(RelayAgentSubOption::SubscriberId(id)) => Some(id),
+            _ => None,
+        }
+    }
+    pub fn get_link_selection(&self) -> Option<Ipv4Addr> {
+        match self.relay_sub_option(LINK_SELECTION) {
+            Some(RelayAgentSubOption::LinkSelection(ip)) => Some(*ip),
+            _ => None,
+        }
+    }
+    pub fn get_class_identifier(&self) -> Option<&Vec<u8>> {
+        match self.option(CLASS_IDENTIFIER) {
+            Some(DhcpOption::ClassIdentifier(class)) => Some(class),
+            _ => None,
+        }
+    }
+    /// Raw option 43 payload, see [`VendorOptionSpace::decode`].
+    pub fn get_vendor_specific_information(&self) -> Option<&Vec<u8>> {
+        match self.option(VENDOR_SPECIFIC_INFORMATION) {
+            Some(DhcpOption::VendorSpecificInformation(data)) => Some(data),
+            _ => None,
+        }
+    }
+    /// User classes of option 77, also when sent as a single class without
+    /// the RFC 3004 length octet as iPXE does.
+    pub fn get_user_classes(&self) -> Option<Vec<&[u8]>> {
+        match self.option(USER_CLASS) {
+            Some(DhcpOption::UserClass(classes)) => {
+                Some(classes.iter().map(|c| c.as_slice()).collect())
+            }
+            Some(DhcpOption::Unrecognized(raw)) => Some(vec![raw.get_data()]),
+            _ => None,
+        }
+    }
+    pub fn get_ipxe_options(&self) -> Option<&Vec<VendorSubOption>> {
+        match self.option(IPXE_ENCAPSULATED) {
+            Some(DhcpOption::IpxeEncapsulated(sub_options)) => Some(sub_options),
+            _ => None,
+        }
+    }
+    pub fn get_client_architecture(&self) -> Option<&Vec<u16>> {
+        match self.option(CLIENT_ARCHITECTURE) {
+            Some(DhcpOption::ClientArchitecture(types)) => Some(types),
+            _ => None,
+        }
+    }
+    /// The client's hardware address, for Ethernet chaddr only.
+    pub fn get_client_mac(&self) -> Option<MacAddress> {
+        match (self.htype, self.hlen) {
+            (1, 6) => Some(MacAddress::new(
+                self.chaddr[0],
+                self.chaddr[1],
+                self.chaddr[2],
+                self.chaddr[3],
+                self.chaddr[4],
+                self.chaddr[5],
+            )),
+            _ => None,
+        }
+    }
+    pub fn get_requested_ip(&self) -> Option<Ipv4Addr> {
+        match self.option(REQUESTED_IP_ADDRESS) {
+            Some(DhcpOption::RequestedIpAddress(IpAddr::V4(ip))) => Some(*ip),
+            _ => None,
+        }
+    }
+    pub fn get_subnet_selection(&self) -> Option<Ipv4Addr> {
+        match self.option(SUBNET_SELECTION) {
+            Some(DhcpOption::SubnetSelection(IpAddr::V4(ip))) => Some(*ip),
+            _ => None,
+        }
+    }
+    pub fn get_server_identifier(&self) -> Option<IpAddr> {
+        match self.option(SERVER_IDENTIFIER) {
+            Some(DhcpOption::ServerIdentifier(ip)) => Some(*ip),
+            _ => None,
+        }
+    }
+    pub fn is_broadcast(&self) -> bool {
+        self.flags & FLAG_BROADCAST != 0
+    }
     pub fn get_client_identifier(&self) -> Option<&Vec<u8>> {
         // Check from options field in Packet
         match self.option(CLIENT_IDENTIFIER) {
@@ -311,18 +557,38 @@ impl Packet {
     }
 }
 
-// Automatically transform from u8 slice
-// only used after check
-impl From<&[u8]> for Packet {
-    #[inline]
-    fn from(bytes: &[u8]) -> Packet {
-        todo!()
+// Place encoded options into `areas` in order, keeping one octet of every area
+// for the End option. Returns the number of octets used in each area.
+fn fill_option_areas(options: &[Vec<u8>], areas: &mut [&mut [u8]]) -> ConvertResult<Vec<usize>> {
+    let mut used = vec![0; areas.len()];
+    let mut current = 0;
+    for opt in options {
+        while current < areas.len() && used[current] + opt.len() + 1 > areas[current].len() {
+            current += 1;
+        }
+        if current == areas.len() {
+            return Err(ConvertPacketError::MessageTooLarge);
+        }
+        areas[current][used[current]..used[current] + opt.len()].copy_from_slice(opt);
+        used[current] += opt.len();
+    }
+    Ok(used)
+}
+
+// Copy `s` into a zeroed header field, leaving room for the terminating NUL
+fn copy_c_string(field: &mut [u8], s: &str) -> ConvertResult<()> {
+    if s.len() >= field.len() {
+        return Err(ConvertPacketError::InvalidLength);
     }
+    field[..s.len()].copy_from_slice(s.as_bytes());
+    Ok(())
 }
 
-impl From<[u8; 2048]> for Packet {
+impl TryFrom<&[u8]> for Packet {
+    type Error = ConvertPacketError;
+    /// Uses [`Packet::decode_from_unchecked`] to convert a byte slice to a `Packet`.
     #[inline]
-    fn from(mac: [u8; 2048]) -> Packet {
-        todo!()
+    fn try_from(bytes: &[u8]) -> ConvertResult<Packet> {
+        Packet::decode_from_unchecked(bytes)
     }
 }
@@ -0,0 +1,130 @@
+use std::cell::Cell;
+use std::net::Ipv4Addr;
+
+/// A range of addresses handed out to clients, minus excluded ranges.
+///
+/// Addresses are allocated round-robin from a cursor, so a freed address is
+/// not given to another client before the rest of the pool has been used.
+/// Excluded ranges are skipped as a whole, which keeps a scan of a /16 pool
+/// with large exclusions cheap.
+///
+/// A pool that allows client classes is only used by their members, one that
+/// denies classes is never used by their members.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AddressPool {
+    first: u32,
+    last: u32,
+    // Sorted and non-overlapping, inclusive ranges
+    excluded: Vec<(u32, u32)>,
+    next: Cell<u32>,
+    client_classes: Vec<String>,
+    denied_classes: Vec<String>,
+}
+
+impl AddressPool {
+    /// The addresses from `first` to `last`, both included.
+    pub fn new(first: Ipv4Addr, last: Ipv4Addr) -> AddressPool {
+        let (first, last) = (u32::from(first), u32::from(last));
+        AddressPool {
+            first: first.min(last),
+            last: first.max(last),
+            excluded: vec![],
+            next: Cell::new(first.min(last)),
+            client_classes: vec![],
+            denied_classes: vec![],
+        }
+    }
+    pub fn get_first(&self) -> Ipv4Addr {
+        Ipv4Addr::from(self.first)
+    }
+    pub fn get_last(&self) -> Ipv4Addr {
+        Ipv4Addr::from(self.last)
+    }
+    /// Never hand out the addresses from `first` to `last`, both included.
+    pub fn exclude(&mut self, first: Ipv4Addr, last: Ipv4Addr) {
+        let (first, last) = (u32::from(first), u32::from(last));
+        self.excluded.push((first.min(last), first.max(last)));
+        self.excluded.sort_unstable();
+        let mut merged: Vec<(u32, u32)> = Vec::with_capacity(self.excluded.len());
+        for (first, last) in self.excluded.drain(..) {
+            match merged.last_mut() {
+                Some(prev) if first <= prev.1.saturating_add(1) => prev.1 = prev.1.max(last),
+                _ => merged.push((first, last)),
+            }
+        }
+        self.excluded = merged;
+    }
+    /// Reserve the pool for members of the class `name` and of the other
+    /// classes allowed.
+    pub fn allow_class(&mut self, name: &str) {
+        self.client_classes.push(name.to_string());
+    }
+    /// Keep members of the class `name` out of the pool.
+    pub fn deny_class(&mut self, name: &str) {
+        self.denied_classes.push(name.to_string());
+    }
+    pub fn get_client_classes(&self) -> &[String] {
+        &self.client_classes
+    }
+    pub fn get_denied_classes(&self) -> &[String] {
+        &self.denied_classes
+    }
+    /// Whether a member of `classes` may get an address of the pool.
+    pub fn allows(&self, classes: &[String]) -> bool {
+        !self.denied_classes.iter().any(|c| classes.contains(c))
+            && (self.client_classes.is_empty()
+                || self.client_classes.iter().any(|c| classes.contains(c)))
+    }
+    /// Whether `ip` is in the range and not excluded.
+    pub fn contains(&self, ip: &Ipv4Addr) -> bool {
+        let ip = u32::from(*ip);
+        self.first <= ip && ip <= self.last && self.excluded_until(ip).is_none()
+    }
+    /// Number of addresses in the range, excluded ones included.
+    pub fn size(&self) -> u64 {
+        (self.last - self.first) as u64 + 1
+    }
+
+    /// The next address after the cursor for which `is_free` holds, `None`
+    /// when the pool has none left.
+    pub fn allocate<F: Fn(Ipv4Addr) -> bool>(&self, is_free: F) -> Option<Ipv4Addr> {
+        let mut ip = self.next.get().clamp(self.first, self.last);
+        let mut scanned = 0;
+        while scanned < self.size() {
+            if let Some(last) = self.excluded_until(ip) {
+                // Skip the rest of the excluded range, or up to the end of the pool
+                let last = last.min(self.last);
+                scanned += (last - ip) as u64 + 1;
+                ip = self.wrap(last);
+                continue;
+            }
+            if is_free(Ipv4Addr::from(ip)) {
+                self.next.set(self.wrap(ip));
+                return Some(Ipv4Addr::from(ip));
+            }
+            scanned += 1;
+            ip = self.wrap(ip);
+        }
+        None
+    }
+
+    // The address following `ip`, back to the first one after the last
+    fn wrap(&self, ip: u32) -> u32 {
+        match ip >= self.last {
+            true => self.first,
+            false => ip + 1,
+        }
+    }
+
+    // End of the excluded range containing `ip`
+    fn excluded_until(&self, ip: u32) -> Option<u32> {
+        let i = self.excluded.partition_point(|&(first, _)| first <= ip);
+        match i {
+            0 => None,
+            _ => {
+                let (_, last) = self.excluded[i - 1];
+                (ip <= last).then_some(last)
+            }
+        }
+    }
+}
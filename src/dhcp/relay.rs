@@ -0,0 +1,130 @@
+use crate::dhcp::packet::{ConvertPacketError, ConvertResult};
+use crate::dhcp::{RawDhcpOption, RELAY_AGENT_INFORMATION};
+use std::net::Ipv4Addr;
+
+// Relay Agent Information sub-options
+// RFC 3046 (Circuit-ID, Remote-ID), RFC 3527 (Link Selection),
+// RFC 3993 (Subscriber-ID), RFC 5107 (Server Identifier Override),
+// RFC 8357 (Relay Source Port)
+pub const AGENT_CIRCUIT_ID: u8 = 1;
+pub const AGENT_REMOTE_ID: u8 = 2;
+pub const LINK_SELECTION: u8 = 5;
+pub const SUBSCRIBER_ID: u8 = 6;
+pub const SERVER_IDENTIFIER_OVERRIDE: u8 = 11;
+pub const RELAY_SOURCE_PORT: u8 = 19;
+
+/// A sub-option of the Relay Agent Information option (82).
+///
+/// A sub-option that is unknown, or whose payload does not match its type, is
+/// kept as `Unrecognized` so the option can be echoed back unchanged.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum RelayAgentSubOption {
+    CircuitId(Vec<u8>), // code 1 // e.g. switch port or VLAN the request came in on
+    RemoteId(Vec<u8>),  // code 2 // e.g. MAC or modem ID of the remote end
+    LinkSelection(Ipv4Addr), // code 5 // subnet of the client, when it differs from giaddr
+    SubscriberId(String), // code 6
+    ServerIdentifierOverride(Ipv4Addr), // code 11 // address the client should use as server identifier
+    RelaySourcePort(Option<u16>), // code 19 // the relay listens on a non-67 port, downstream port if chained
+    Unrecognized(RawDhcpOption),
+}
+
+impl RelayAgentSubOption {
+    pub fn code(&self) -> u8 {
+        match self {
+            RelayAgentSubOption::CircuitId(_) => AGENT_CIRCUIT_ID,
+            RelayAgentSubOption::RemoteId(_) => AGENT_REMOTE_ID,
+            RelayAgentSubOption::LinkSelection(_) => LINK_SELECTION,
+            RelayAgentSubOption::SubscriberId(_) => SUBSCRIBER_ID,
+            RelayAgentSubOption::ServerIdentifierOverride(_) => SERVER_IDENTIFIER_OVERRIDE,
+            RelayAgentSubOption::RelaySourcePort(_) => RELAY_SOURCE_PORT,
+            RelayAgentSubOption::Unrecognized(raw) => raw.get_code(),
+        }
+    }
+
+    /// Parses one sub-option, falling back to `Unrecognized` when the payload
+    /// does not fit the sub-option type.
+    pub fn decode(code: u8, data: &[u8]) -> RelayAgentSubOption {
+        let ipv4 = |data: &[u8]| <[u8; 4]>::try_from(data).ok().map(Ipv4Addr::from);
+        let parsed = match code {
+            AGENT_CIRCUIT_ID if !data.is_empty() => {
+                Some(RelayAgentSubOption::CircuitId(data.to_vec()))
+            }
+            AGENT_REMOTE_ID if !data.is_empty() => {
+                Some(RelayAgentSubOption::RemoteId(data.to_vec()))
+            }
+            LINK_SELECTION => ipv4(data).map(RelayAgentSubOption::LinkSelection),
+            SUBSCRIBER_ID if !data.is_empty() => String::from_utf8(data.to_vec())
+                .ok()
+                .map(RelayAgentSubOption::SubscriberId),
+            SERVER_IDENTIFIER_OVERRIDE => {
+                ipv4(data).map(RelayAgentSubOption::ServerIdentifierOverride)
+            }
+            RELAY_SOURCE_PORT => match data {
+                [] => Some(RelayAgentSubOption::RelaySourcePort(None)),
+                [hi, lo] => Some(RelayAgentSubOption::RelaySourcePort(Some(
+                    u16::from_be_bytes([*hi, *lo]),
+                ))),
+                _ => None,
+            },
+            _ => None,
+        };
+        parsed.unwrap_or_else(|| {
+            RelayAgentSubOption::Unrecognized(RawDhcpOption::new(code, data.to_vec()))
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            RelayAgentSubOption::CircuitId(v) | RelayAgentSubOption::RemoteId(v) => v.clone(),
+            RelayAgentSubOption::LinkSelection(ip)
+            | RelayAgentSubOption::ServerIdentifierOverride(ip) => ip.octets().to_vec(),
+            RelayAgentSubOption::SubscriberId(s) => s.as_bytes().to_vec(),
+            RelayAgentSubOption::RelaySourcePort(port) => match port {
+                Some(port) => port.to_be_bytes().to_vec(),
+                None => vec![],
+            },
+            RelayAgentSubOption::Unrecognized(raw) => raw.get_data().to_vec(),
+        }
+    }
+}
+
+// Sub-options that do not fit are reported against option 82 itself
+fn invalid_length() -> ConvertPacketError {
+    ConvertPacketError::InvalidOptionLength(RELAY_AGENT_INFORMATION)
+}
+
+/// Parses the payload of option 82 into its sub-options, in wire order.
+pub fn decode_relay_agent_information(data: &[u8]) -> ConvertResult<Vec<RelayAgentSubOption>> {
+    if data.is_empty() {
+        return Err(invalid_length());
+    }
+    let mut sub_options = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let code = data[i];
+        let len = *data.get(i + 1).ok_or_else(invalid_length)? as usize;
+        let sub_data = data.get(i + 2..i + 2 + len).ok_or_else(invalid_length)?;
+        sub_options.push(RelayAgentSubOption::decode(code, sub_data));
+        i += 2 + len;
+    }
+    Ok(sub_options)
+}
+
+pub fn encode_relay_agent_information(
+    sub_options: &[RelayAgentSubOption],
+) -> ConvertResult<Vec<u8>> {
+    if sub_options.is_empty() {
+        return Err(invalid_length());
+    }
+    let mut data = Vec::new();
+    for sub_option in sub_options {
+        let sub_data = sub_option.encode();
+        if sub_data.len() > u8::MAX as usize {
+            return Err(invalid_length());
+        }
+        data.push(sub_option.code());
+        data.push(sub_data.len() as u8);
+        data.extend_from_slice(&sub_data);
+    }
+    Ok(data)
+}
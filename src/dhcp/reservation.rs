@@ -0,0 +1,68 @@
+use crate::dhcp::packet::Packet;
+use crate::dhcp::{ClientIdentifier, DhcpOption};
+use std::net::Ipv4Addr;
+
+/// An address pinned to one host, inside or outside the pools of its subnet.
+///
+/// The host is found by MAC address, client identifier or the Remote-ID its
+/// relay agent adds. Its hostname, options, lease time and boot file replace
+/// those configured for the subnet and the server.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Reservation {
+    client: ClientIdentifier,
+    ip: Ipv4Addr,
+    hostname: Option<String>,
+    options: Vec<DhcpOption>,
+    lease_duration: Option<u32>,
+    boot_file: Option<String>,
+}
+
+impl Reservation {
+    pub fn new(client: ClientIdentifier, ip: Ipv4Addr) -> Reservation {
+        Reservation {
+            client,
+            ip,
+            hostname: None,
+            options: vec![],
+            lease_duration: None,
+            boot_file: None,
+        }
+    }
+    pub fn set_hostname(&mut self, hostname: &str) {
+        self.hostname = Some(hostname.to_string());
+    }
+    /// Set the options sent to the host, they replace the subnet and server
+    /// options with the same code.
+    pub fn set_options(&mut self, options: Vec<DhcpOption>) {
+        self.options = options;
+    }
+    /// Set the lease time in seconds.
+    pub fn set_lease_duration(&mut self, secs: u32) {
+        self.lease_duration = Some(secs);
+    }
+    pub fn set_boot_file(&mut self, boot_file: &str) {
+        self.boot_file = Some(boot_file.to_string());
+    }
+    pub fn get_client(&self) -> &ClientIdentifier {
+        &self.client
+    }
+    pub fn get_ip(&self) -> Ipv4Addr {
+        self.ip
+    }
+    pub fn get_hostname(&self) -> &Option<String> {
+        &self.hostname
+    }
+    pub fn get_options(&self) -> &[DhcpOption] {
+        &self.options
+    }
+    pub fn get_lease_duration(&self) -> Option<u32> {
+        self.lease_duration
+    }
+    pub fn get_boot_file(&self) -> &Option<String> {
+        &self.boot_file
+    }
+    /// Whether the reservation is for the host that sent `in_packet`.
+    pub fn matches(&self, in_packet: &Packet) -> bool {
+        self.client.matches_packet(in_packet)
+    }
+}
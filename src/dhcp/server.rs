@@ -1,168 +1,981 @@
-use crate::dhcp::{packet::*, FLAG_ZERO};
-use crate::dhcp::{DhcpLease, LeaseError,DhcpMessageTypeCode, DhcpOption, BOOTREPLY, BOOTREQUEST};
+use crate::dhcp::{packet::*, FLAG_BROADCAST, BOOTFILE_NAME, CLASSLESS_ROUTE_FORMAT, MS_CLASSLESS_ROUTE_FORMAT};
+use crate::dhcp::{BindingState, ClientIdentifier, DeclinedAddress, Reservation, DhcpLease, DistributeDhcpLeaseError, DhcpMessageTypeCode, DhcpOption, BOOTREPLY};
+use crate::dhcp::{find_vendor_space, format_client_id, link_address, ClientClass, BootPolicy, HostnamePolicy, Subnet, VendorOptionSpace};
+use crate::dhcp::{JournalLeaseStore, LeaseStore, MemoryLeaseStore, StorageResult};
+use crate::ipnet::Ipv4Net;
+use crate::macaddress::MacAddress;
 use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
-use std::{cell::Cell, fmt::Result, io::Error};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{cell::Cell, io::Error, io::ErrorKind};
 
-const LEASE_NUM: u32 = 252;
+const DEFAULT_LEASE_DURATION: u32 = 86400;
+const DEFAULT_DECLINE_PROBATION: u32 = 86400;
+// How long an offered address is kept for the client
+const OFFER_HOLD_SECS: u64 = 60;
 
-pub struct DhcpServer {
-    leases: HashMap<IpAddr, DhcpLease>,
-    last_leases: u32,
+pub const DHCP_SERVER_PORT: u16 = 67;
+pub const DHCP_CLIENT_PORT: u16 = 68;
+
+/// A DHCP server keeping its leases in `S`, in memory unless another
+/// [`LeaseStore`] is given to [`DhcpServer::with_store`].
+pub struct DhcpServer<S: LeaseStore = MemoryLeaseStore> {
+    leases: S,
     default_lease_duration: u32,
-    lease_num: u32,
-    lease_start: IpAddr,
+    // Subnets served, directly or through relay agents
+    subnets: Vec<Subnet>,
+    // Addresses offered and not yet requested
+    offers: HashMap<IpAddr, Offer>,
+    // Addresses only ever given to one host
+    reservations: HashMap<IpAddr, Reservation>,
+    // Client classes in order of precedence
+    classes: Vec<ClientClass>,
+    // Addresses found in use by another host, out of the pool for a while
+    declined: HashMap<IpAddr, DeclinedAddress>,
+    decline_probation: u32,
+    // Options handed out to clients, e.g. subnet mask, routers and DNS servers
+    options: Vec<DhcpOption>,
+    hostname_policy: HostnamePolicy,
+    // Option 43 contents, chosen by the client's vendor class (option 60)
+    vendor_spaces: Vec<VendorOptionSpace>,
+    boot_policy: BootPolicy,
 
     server: Server,
-    server_id: u32,
+}
+
+struct Offer {
+    client: ClientIdentifier,
+    until: Duration,
 }
 
 impl DhcpServer {
-    fn get_lease_from_ip(&self, ip: &IpAddr) -> Option<&DhcpLease> {
-        self.leases.get(ip)
-    }
-    fn get_lease_from_chi(&self, chi: &[u8]) -> Option<&DhcpLease> {
-        for (_, lease) in self.leases.iter() {
-            match lease.get_chi() {
-                Some(c) => {
-                    if c.as_bytes() == chi {
-                        // match client identifier
-                        return Some(lease);
-                    }
-                }
-                _ => {
-                    if lease.get_mac().get_octets() == chi {
-                        // match mac address
-                        return Some(lease);
-                    }
-                }
+    /// A server handing out `lease_num` addresses from `lease_start` on
+    /// `subnet`, answering through `server`. More subnets are added with
+    /// [`DhcpServer::add_subnet`]. Leases are kept in memory only.
+    pub fn new(server: Server, subnet: Ipv4Net, lease_start: Ipv4Addr, lease_num: u32) -> DhcpServer {
+        DhcpServer::with_store(server, subnet, lease_start, lease_num, MemoryLeaseStore::new())
+    }
+}
+
+impl DhcpServer<JournalLeaseStore> {
+    /// Like [`DhcpServer::new`], keeping the leases in a snapshot at `path`
+    /// and its journal, see [`JournalLeaseStore`]. The journal is replayed
+    /// before the server answers any client.
+    pub fn with_journal<P: AsRef<Path>>(
+        server: Server,
+        subnet: Ipv4Net,
+        lease_start: Ipv4Addr,
+        lease_num: u32,
+        path: P,
+    ) -> StorageResult<DhcpServer<JournalLeaseStore>> {
+        let store = JournalLeaseStore::open(path)?;
+        Ok(DhcpServer::with_store(server, subnet, lease_start, lease_num, store))
+    }
+}
+
+impl<S: LeaseStore> DhcpServer<S> {
+    /// Like [`DhcpServer::new`], keeping the leases in `store`. Leases
+    /// already in the store are served, those without a subnet are tagged
+    /// with the subnet of their address.
+    pub fn with_store(server: Server, subnet: Ipv4Net, lease_start: Ipv4Addr, lease_num: u32, store: S) -> DhcpServer<S> {
+        let last = u32::from(lease_start).saturating_add(lease_num.max(1) - 1);
+        let mut local = Subnet::new(subnet);
+        local.add_pool(lease_start, Ipv4Addr::from(last));
+        let mut dhcp_server = DhcpServer {
+            leases: store,
+            default_lease_duration: DEFAULT_LEASE_DURATION,
+            subnets: vec![local],
+            offers: HashMap::new(),
+            reservations: HashMap::new(),
+            classes: vec![],
+            declined: HashMap::new(),
+            decline_probation: DEFAULT_DECLINE_PROBATION,
+            options: vec![],
+            hostname_policy: HostnamePolicy::default(),
+            vendor_spaces: vec![],
+            boot_policy: BootPolicy::new(),
+            server,
+        };
+        dhcp_server.tag_subnets();
+        dhcp_server
+    }
+    /// Set the lease time in seconds.
+    pub fn set_default_lease_duration(&mut self, secs: u32) {
+        self.default_lease_duration = secs;
+    }
+    /// Never hand out the addresses from `first` to `last`, e.g. those of
+    /// statically configured hosts.
+    pub fn exclude(&mut self, first: Ipv4Addr, last: Ipv4Addr) {
+        for subnet in self.subnets.iter_mut() {
+            for pool in subnet.get_pools_mut() {
+                pool.exclude(first, last);
             }
         }
-        None
     }
-    fn get_pair_from_chi(&self, chi: &[u8]) -> Option<(&IpAddr, &DhcpLease)> {
-        for (ipaddr, lease) in self.leases.iter() {
-            match lease.get_chi() {
-                Some(c) => {
-                    if c.as_bytes() == chi {
-                        // match client identifier
-                        return Some((ipaddr,lease));
-                    }
-                }
-                _ => {
-                    if lease.get_mac().get_octets() == chi {
-                        // match mac address
-                        return Some((ipaddr,lease));
-                    }
+    /// Serve `subnet` too, e.g. a VLAN behind a relay agent. A subnet with
+    /// the same network replaces the existing one.
+    pub fn add_subnet(&mut self, subnet: Subnet) {
+        self.subnets.retain(|s| s.get_net() != subnet.get_net());
+        self.subnets.push(subnet);
+        self.tag_subnets();
+    }
+    pub fn get_subnets(&self) -> &[Subnet] {
+        &self.subnets
+    }
+    /// Subnet of the link the client of `in_packet` is on, see
+    /// [`link_address`]. Clients on the link the server is attached to
+    /// belong to the subnet of the server address.
+    pub fn select_subnet(&self, in_packet: &Packet) -> Option<&Subnet> {
+        let link = match link_address(in_packet) {
+            Some(ip) => ip,
+            None => match self.server.server_ip {
+                IpAddr::V4(ip) => ip,
+                IpAddr::V6(_) => return None,
+            },
+        };
+        self.subnet_of(&link)
+    }
+    fn subnet_of(&self, ip: &Ipv4Addr) -> Option<&Subnet> {
+        self.subnets.iter().find(|subnet| subnet.contains(ip))
+    }
+    // Set the subnet of a lease without one to that of its address
+    fn tag_subnet(&self, lease: &mut DhcpLease) -> bool {
+        match (lease.get_subnet(), lease.get_ip()) {
+            (None, IpAddr::V4(ip)) => match self.subnet_of(ip) {
+                Some(subnet) => {
+                    lease.set_subnet(*subnet.get_net());
+                    true
                 }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+    // Tag the stored leases without a subnet, e.g. those of a dnsmasq lease
+    // file, which has no place for it
+    fn tag_subnets(&mut self) {
+        let untagged: Vec<DhcpLease> = self
+            .leases
+            .iter()
+            .filter_map(|lease| {
+                let mut lease = lease.clone();
+                self.tag_subnet(&mut lease).then_some(lease)
+            })
+            .collect();
+        if untagged.is_empty() {
+            return;
+        }
+        let tagged = untagged
+            .into_iter()
+            .try_for_each(|lease| self.leases.update(lease))
+            .and_then(|_| self.leases.commit());
+        if let Err(e) = tagged {
+            eprintln!("[ERROR] Failed to store the subnets of the leases: {}", e);
+            self.leases.rollback();
+        }
+    }
+    // The selected subnet and those sharing its link
+    fn link_subnets(&self, in_packet: &Packet) -> Vec<&Subnet> {
+        match self.select_subnet(in_packet) {
+            Some(selected) => self
+                .subnets
+                .iter()
+                .filter(|subnet| subnet.shares_link_with(selected))
+                .collect(),
+            None => vec![],
+        }
+    }
+    /// Pin an address to a host, also when it is outside the pools. The
+    /// address is never given to another client. A reservation for the same
+    /// address replaces the existing one.
+    pub fn reserve(&mut self, reservation: Reservation) {
+        self.reservations
+            .insert(IpAddr::V4(reservation.get_ip()), reservation);
+    }
+    pub fn get_reservations(&self) -> &HashMap<IpAddr, Reservation> {
+        &self.reservations
+    }
+    // Reservation of the client of `in_packet` on its link
+    fn reservation_for(&self, in_packet: &Packet) -> Option<&Reservation> {
+        let subnets = self.link_subnets(in_packet);
+        self.reservations.values().find(|reservation| {
+            reservation.matches(in_packet)
+                && subnets.iter().any(|subnet| subnet.contains(&reservation.get_ip()))
+        })
+    }
+    /// Add a client class, classes added first take precedence when several
+    /// set the lease time or the same option.
+    pub fn add_class(&mut self, class: ClientClass) {
+        self.classes.push(class);
+    }
+    pub fn get_classes(&self) -> &[ClientClass] {
+        &self.classes
+    }
+    /// Names of the classes the client of `in_packet` is a member of.
+    pub fn classify(&self, in_packet: &Packet) -> Vec<String> {
+        self.classes
+            .iter()
+            .filter(|class| class.matches(in_packet))
+            .map(|class| class.get_name().to_string())
+            .collect()
+    }
+    fn member_classes<'a>(&'a self, classes: &'a [String]) -> impl Iterator<Item = &'a ClientClass> {
+        self.classes
+            .iter()
+            .filter(|class| classes.iter().any(|name| name == class.get_name()))
+    }
+    /// Set how long in seconds a declined address stays out of the pool.
+    pub fn set_decline_probation(&mut self, secs: u32) {
+        self.decline_probation = secs;
+    }
+    pub fn get_declined(&self) -> &HashMap<IpAddr, DeclinedAddress> {
+        &self.declined
+    }
+    /// Add existing leases, e.g. read from a lease file, and commit them to
+    /// the lease store.
+    pub fn load_leases(&mut self, leases: Vec<DhcpLease>) -> StorageResult<()> {
+        for mut lease in leases {
+            self.tag_subnet(&mut lease);
+            if let Err(e) = self.leases.insert(lease) {
+                self.leases.rollback();
+                return Err(e);
+            }
+        }
+        let committed = self.leases.commit();
+        if committed.is_err() {
+            self.leases.rollback();
+        }
+        committed
+    }
+    pub fn get_leases(&self) -> &S {
+        &self.leases
+    }
+    /// Set the options handed out to clients. Only options a client lists in
+    /// its Parameter Request List are sent, see [`select_reply_options`].
+    pub fn set_options(&mut self, options: Vec<DhcpOption>) {
+        self.options = options;
+    }
+    pub fn set_hostname_policy(&mut self, hostname_policy: HostnamePolicy) {
+        self.hostname_policy = hostname_policy;
+    }
+    /// Add a vendor option space, its sub-options are sent in option 43 to
+    /// clients of that vendor instead of a configured option 43.
+    pub fn add_vendor_space(&mut self, space: VendorOptionSpace) {
+        self.vendor_spaces.push(space);
+    }
+    pub fn set_boot_policy(&mut self, boot_policy: BootPolicy) {
+        self.boot_policy = boot_policy;
+    }
+    // Options of the reply giving or confirming `ip`. Those of the host's
+    // reservation replace those of its classes, then of the subnet, then the
+    // server-wide ones.
+    fn reply_options(&self, in_packet: &Packet, classes: &[String], ip: Ipv4Addr) -> Vec<DhcpOption> {
+        let reservation = self.reservation_for(in_packet);
+        let mut configured: Vec<DhcpOption> = reservation.map_or(vec![], |r| r.get_options().to_vec());
+        if let Some(hostname) = reservation.and_then(|r| r.get_hostname().clone()) {
+            configured.push(DhcpOption::HostName(hostname));
+        }
+        let subnet_options = self.subnet_of(&ip).map_or(&[][..], |subnet| subnet.get_options());
+        let class_options = self.member_classes(classes).flat_map(|class| class.get_options());
+        for opt in class_options.chain(subnet_options).chain(self.options.iter()) {
+            if configured.iter().all(|o| o.code() != opt.code()) {
+                configured.push(opt.clone());
+            }
+        }
+        let vendor_option = in_packet
+            .get_class_identifier()
+            .and_then(|class| find_vendor_space(&self.vendor_spaces, class))
+            .filter(|space| !space.get_sub_options().is_empty())
+            .and_then(|space| space.encode().ok())
+            .map(DhcpOption::VendorSpecificInformation);
+        let mut options = match vendor_option {
+            Some(vendor_option) => {
+                let mut configured: Vec<DhcpOption> = configured
+                    .into_iter()
+                    .filter(|opt| !matches!(opt, DhcpOption::VendorSpecificInformation(_)))
+                    .collect();
+                configured.push(vendor_option);
+                select_reply_options(&configured, in_packet.get_parameter_request_list())
+            }
+            None => select_reply_options(&configured, in_packet.get_parameter_request_list()),
+        };
+        // Boot options replace the configured ones and are sent whether the
+        // firmware asked for them or not
+        if let Some(rule) = self.boot_policy.select(in_packet) {
+            if let Ok(boot_options) = rule.options(in_packet) {
+                options.retain(|opt| boot_options.iter().all(|boot| boot.code() != opt.code()));
+                options.extend(boot_options);
             }
         }
-        None
+        if let Some(boot_file) = reservation.and_then(|r| r.get_boot_file().as_ref()) {
+            options.retain(|opt| opt.code() != BOOTFILE_NAME);
+            options.push(DhcpOption::BootfileName(boot_file.clone()));
+        }
+        // Client FQDN is only sent to clients that sent it (RFC 4702 section 4)
+        if let Some(fqdn) = self.hostname_policy.decide(in_packet).get_reply() {
+            options.push(DhcpOption::ClientFqdn(fqdn.clone()));
+        }
+        // RFC 3011 section 3: the subnet selection is echoed back unchanged
+        if let Some(ip) = in_packet.get_subnet_selection() {
+            options.push(DhcpOption::SubnetSelection(IpAddr::V4(ip)));
+        }
+        // RFC 3046 section 2.2: the server copies the relay agent information
+        // back into its reply, unchanged and as the last option
+        if let Some(sub_options) = in_packet.get_relay_agent_information() {
+            options.push(DhcpOption::RelayAgentInformation(sub_options.clone()));
+        }
+        options
+    }
+    /// Answer the clients until the socket fails.
+    pub fn serve(&mut self) -> Error {
+        loop {
+            match self.server.recv() {
+                Ok(p) => self.handle_request(&p),
+                Err(err) => return err,
+            }
+        }
+    }
+
+    /// Address for the client of `in_packet`, in order of preference: its
+    /// reserved address, the address it has or had, its pending offer, the
+    /// address it asks for in option 50, a never leased address of the pools
+    /// of its link and last an address whose lease expired.
+    pub fn get_available_ip(&self, in_packet: &Packet) -> Result<Ipv4Addr, DistributeDhcpLeaseError> {
+        self.allocate_ip(in_packet, &self.classify(in_packet))
+    }
+
+    // See get_available_ip, only pools allowing `classes` are used
+    fn allocate_ip(&self, in_packet: &Packet, classes: &[String]) -> Result<Ipv4Addr, DistributeDhcpLeaseError> {
+        let now = now();
+        let subnets = self.link_subnets(in_packet);
+        if subnets.is_empty() {
+            return Err(DistributeDhcpLeaseError::UnknownSubnet);
+        }
+        let (client_id, mac) = client_key(in_packet).ok_or(DistributeDhcpLeaseError::UnknownClient)?;
+        let client_id = client_id.as_deref();
+        let reserved = self
+            .reservation_for(in_packet)
+            .map(|reservation| IpAddr::V4(reservation.get_ip()));
+        // The client may have a lease on another link too
+        let leased: Vec<IpAddr> = self
+            .leases
+            .find_client(client_id, &mac)
+            .iter()
+            .map(|lease| *lease.get_ip())
+            .collect();
+        let offered = self
+            .offers
+            .iter()
+            .find(|(_, offer)| offer.client.matches(client_id, &mac))
+            .map(|(ip, _)| *ip);
+        let requested = in_packet.get_requested_ip().map(IpAddr::V4);
+        let candidates = reserved.into_iter().chain(leased).chain(offered).chain(requested);
+        for ip in candidates {
+            match ip {
+                IpAddr::V4(ip) if self.is_available_for(in_packet, classes, ip, now) => return Ok(ip),
+                _ => {}
+            }
+        }
+        let pools = || subnets.iter().flat_map(|subnet| subnet.pools_for(classes));
+        pools()
+            .find_map(|pool| {
+                pool.allocate(|ip| self.is_free(&IpAddr::V4(ip), now) && self.leases.get_by_ip(&IpAddr::V4(ip)).is_none())
+            })
+            .or_else(|| pools().find_map(|pool| pool.allocate(|ip| self.is_free(&IpAddr::V4(ip), now))))
+            .ok_or(DistributeDhcpLeaseError::LeaseNoAvailable)
+    }
+
+    // Whether nobody holds `ip`: no valid lease, offer, reservation or decline
+    fn is_free(&self, ip: &IpAddr, now: Duration) -> bool {
+        !self.is_declined(ip, now)
+            && !self.reservations.contains_key(ip)
+            && self.offers.get(ip).is_none_or(|offer| offer.until <= now)
+            && self.leases.get_by_ip(ip).is_none_or(|lease| lease.is_expired(now))
+    }
+
+    fn handle_dhcp_discover(&mut self, in_packet: &Packet, classes: &[String]) {
+        if let Some(reply) = self.offer(in_packet, classes) {
+            if let Err(e) = self.server.send_reply(in_packet, &reply) {
+                eprintln!("[ERROR] Failed to send reply: {}", e);
+            }
+        }
+    }
+
+    /// Answer a DHCPDISCOVER with a DHCPOFFER, the offered address is held
+    /// for the client for a short while. `None` when no address is left.
+    pub fn process_discover(&mut self, in_packet: &Packet) -> Option<Packet> {
+        let classes = self.classify(in_packet);
+        self.offer(in_packet, &classes)
     }
-    fn get_available_ip(&self) -> &IpAddr {
-        let start = match self.lease_start {
-            IpAddr::V4(ipv4) => u32::from_be_bytes(ipv4.octets()),
-            IpAddr::V6(ipv6) => todo!(),
+
+    fn offer(&mut self, in_packet: &Packet, classes: &[String]) -> Option<Packet> {
+        let now = now();
+        let ip = match self.allocate_ip(in_packet, classes) {
+            Ok(ip) => ip,
+            Err(e) => {
+                eprintln!("[WARN] No address for {:?}: {}", in_packet.get_client_mac(), e);
+                return None;
+            }
         };
-        for _ in 0..self.lease_num {
-            
+        let (client_id, mac) = client_key(in_packet)?;
+        let client_id = client_id.as_deref();
+        self.offers.retain(|_, offer| offer.until > now && !offer.client.matches(client_id, &mac));
+        self.offers.insert(
+            IpAddr::V4(ip),
+            Offer {
+                client: ClientIdentifier::new(client_id, &mac),
+                until: now + Duration::from_secs(OFFER_HOLD_SECS),
+            },
+        );
+        let options = [
+            vec![DhcpOption::DhcpMessageType(DhcpMessageTypeCode::Offer)],
+            self.lease_options(in_packet, classes),
+            self.reply_options(in_packet, classes, ip),
+        ]
+        .concat();
+        let mut reply = reply_packet(in_packet, in_packet.get_flags(), 0, u32::from(ip), options);
+        self.apply_boot(in_packet, &mut reply);
+        Some(reply)
+    }
+
+    // Lease time in seconds for the client of `in_packet`: that of its
+    // reservation, else of its first class setting one
+    fn lease_duration(&self, in_packet: &Packet, classes: &[String]) -> u32 {
+        self.reservation_for(in_packet)
+            .and_then(|reservation| reservation.get_lease_duration())
+            .or_else(|| self.member_classes(classes).find_map(|class| class.get_lease_duration()))
+            .unwrap_or(self.default_lease_duration)
+    }
+
+    // Server identifier, lease time and the T1 and T2 defaults of RFC 2131
+    // section 4.4.5
+    fn lease_options(&self, in_packet: &Packet, classes: &[String]) -> Vec<DhcpOption> {
+        let duration = self.lease_duration(in_packet, classes);
+        vec![
+            DhcpOption::ServerIdentifier(self.server.server_ip),
+            DhcpOption::IpAddressLeaseTime(duration),
+            DhcpOption::RenewalTimeValue(duration / 2),
+            DhcpOption::RebindingTimeValue((duration as u64 * 7 / 8) as u32),
+        ]
+    }
+
+    fn handle_dhcp_request(&mut self, in_packet: &Packet, classes: &[String]) {
+        if let Some(reply) = self.answer_request(in_packet, classes) {
+            if let Err(e) = self.server.send_reply(in_packet, &reply) {
+                eprintln!("[ERROR] Failed to send reply: {}", e);
+            }
         }
     }
 
-    fn handle_dhcp_discover(&self, in_packet: &Packet) {
-        // Check if leases for chaddr exist
-        // according to chaddr (or client-identifier in options)
-        // Get slice
-        let chi: &[u8] = match in_packet.get_client_identifier() {
-            Some(chi) => &chi[..], // TODO may need to parse &[u8]
-            None => {
-                // No client identifier in options
-                in_packet.get_chaddr()
+    /// Answer a DHCPREQUEST as in RFC 2131 section 4.3.2: a DHCPACK once the
+    /// lease is committed, a DHCPNAK, or `None` when the server stays silent.
+    pub fn process_request(&mut self, in_packet: &Packet) -> Option<Packet> {
+        let classes = self.classify(in_packet);
+        self.answer_request(in_packet, &classes)
+    }
+
+    fn answer_request(&mut self, in_packet: &Packet, classes: &[String]) -> Option<Packet> {
+        let now = now();
+        let (client_id, mac) = client_key(in_packet)?;
+        let client_id = client_id.as_deref();
+        let own_ips: Vec<IpAddr> = self
+            .leases
+            .find_client(client_id, &mac)
+            .iter()
+            .map(|lease| *lease.get_ip())
+            .collect();
+        let state = request_state(in_packet)?;
+        let ip = match state {
+            // The client chose the offer of another server
+            RequestState::Selecting(server_id, _) if IpAddr::V4(server_id) != self.server.server_ip => {
+                self.offers.retain(|_, offer| !offer.client.matches(client_id, &mac));
+                return None;
             }
+            RequestState::Selecting(_, ip) | RequestState::InitReboot(ip) | RequestState::Renewing(ip) => ip,
         };
-        #[cfg(debug_print)]
-        println!("[DEBUG] In handle_dhcp_discover");
-        // assume the length of client identifier described in option
-        // is the same to mac address (= 6)
-        let ip: &IpAddr = if let Some(lease) = self.get_lease_from_chi(chi) {
-            // lease match
-            #[cfg(debug_print)]
-            println!("[DEBUG] Lease found: {:?} for chi:{:?}", lease, chi);
-            lease.get_ip()
-
-        } else {
-            #[cfg(debug_print)]
-            println!("[DEBUG] No Lease found for chi:{:?}", chi);
-            self.get_available_ip()
+        let subnets = self.link_subnets(in_packet);
+        // A link the server knows nothing about is left to other servers
+        if subnets.is_empty() {
+            return None;
+        }
+        if !subnets.iter().any(|subnet| subnet.contains(&ip)) {
+            return Some(self.nak(in_packet, "wrong network"));
+        }
+        let available = match state {
+            // An INIT-REBOOT client must get the address it had
+            RequestState::InitReboot(_) if !own_ips.is_empty() => own_ips.contains(&IpAddr::V4(ip)),
+            // Without a record of the client the server stays silent, unless
+            // the address is someone else's
+            RequestState::InitReboot(_) => match self.leases.get_by_ip(&IpAddr::V4(ip)) {
+                Some(lease) if !lease.is_expired(now) => false,
+                _ => return None,
+            },
+            // Its own address or a free one of the pool, e.g. when the client
+            // renews a lease the server has forgotten
+            _ => self.is_available_for(in_packet, classes, ip, now),
         };
-        let ip: u32 = match ip {
-            IpAddr::V4(ipv4) => Some(u32::from_be_bytes(ipv4.octets())),
-            IpAddr::V6(ipv6) => None,
-        }.unwrap();
-        #[cfg(debug_print)]
-        println!("[DEBUG] Send reply");
-        let pre_packet: Packet = Packet::new(
-            BOOTREPLY,
-            1,
-            6,
-            0,
-            in_packet.get_xid().try_into().expect("Failed to convert xid"),
-            0,
-            FLAG_ZERO,
-            0, // 0 (DHCPDISCOVER), client's network address (DHCPINFORM)
-            ip,
-            0,
-            0,
-            in_packet.get_chaddr().try_into().expect("Failed to convert chaddr"),
-            in_packet.get_sname().try_into().expect("Failed to convert sname"),
-            in_packet.get_file().try_into().expect("Failed to convert file"),
-            vec![
-                DhcpOption::ServerIdentifier(self.server.server_ip),
-                DhcpOption::DhcpMessageType(DhcpMessageTypeCode::Offer),
-            ]
+        match available {
+            true => self.ack(in_packet, classes, ip, now),
+            false => Some(self.nak(in_packet, "requested address not available")),
+        }
+    }
+
+    // Whether `ip` can be leased to the client of `in_packet`: it is on the
+    // client's link and is its own address or a free address of a pool
+    fn is_available_for(&self, in_packet: &Packet, classes: &[String], ip: Ipv4Addr, now: Duration) -> bool {
+        let (client_id, mac) = match client_key(in_packet) {
+            Some(key) => key,
+            None => return false,
+        };
+        let client_id = client_id.as_deref();
+        let subnets = self.link_subnets(in_packet);
+        if !subnets.iter().any(|subnet| subnet.contains(&ip)) {
+            return false;
+        }
+        let in_pool = subnets
+            .iter()
+            .flat_map(|subnet| subnet.pools_for(classes))
+            .any(|pool| pool.contains(&ip));
+        let ip = IpAddr::V4(ip);
+        if self.is_declined(&ip, now) {
+            return false;
+        }
+        // A reserved address waits for a client still holding it to go
+        if let Some(reservation) = self.reservations.get(&ip) {
+            return reservation.matches(in_packet)
+                && self.leases.get_by_ip(&ip).is_none_or(|lease| {
+                    lease.is_for_client(client_id, &mac) || lease.is_expired(now)
+                });
+        }
+        if let Some(offer) = self.offers.get(&ip) {
+            if offer.until > now && !offer.client.matches(client_id, &mac) {
+                return false;
+            }
+        }
+        match self.leases.get_by_ip(&ip) {
+            Some(lease) if lease.is_for_client(client_id, &mac) => true,
+            Some(lease) if !lease.is_expired(now) => false,
+            _ => in_pool,
+        }
+    }
+
+    // Commit the lease of `ip` to the client and build the DHCPACK. A lease
+    // the store fails to commit is not acknowledged.
+    fn ack(&mut self, in_packet: &Packet, classes: &[String], ip: Ipv4Addr, now: Duration) -> Option<Packet> {
+        let duration = self.lease_duration(in_packet, classes);
+        let hostname = self
+            .reservation_for(in_packet)
+            .and_then(|reservation| reservation.get_hostname().clone())
+            .or_else(|| self.hostname_policy.decide(in_packet).get_hostname().clone());
+        let (client_id, mac) = client_key(in_packet)?;
+        let client_id = client_id.as_deref();
+        self.offers.retain(|_, offer| !offer.client.matches(client_id, &mac));
+        let expiry = now + Duration::from_secs(duration as u64);
+        let mut lease = DhcpLease::new(expiry, mac, IpAddr::V4(ip), hostname, client_id.map(format_client_id));
+        lease.set_start(now);
+        self.tag_subnet(&mut lease);
+        // A client holds one lease per subnet, an older one for another
+        // address on the same subnet ends
+        let stale: Vec<IpAddr> = self
+            .leases
+            .find_client(client_id, &mac)
+            .iter()
+            .filter(|old| old.get_subnet() == lease.get_subnet())
+            .map(|old| *old.get_ip())
+            .filter(|addr| *addr != IpAddr::V4(ip))
+            .collect();
+        let stored = stale
+            .iter()
+            .try_for_each(|addr| self.leases.remove(addr).map(|_| ()))
+            .and_then(|_| self.leases.insert(lease))
+            .and_then(|_| self.leases.commit());
+        if let Err(e) = stored {
+            eprintln!("[ERROR] Failed to store the lease of {}: {}", ip, e);
+            self.leases.rollback();
+            return None;
+        }
+
+        let options = [
+            vec![DhcpOption::DhcpMessageType(DhcpMessageTypeCode::Ack)],
+            self.lease_options(in_packet, classes),
+            self.reply_options(in_packet, classes, ip),
+        ]
+        .concat();
+        let mut reply = reply_packet(
+            in_packet,
+            in_packet.get_flags(),
+            in_packet.get_ciaddr(),
+            u32::from(ip),
+            options,
+        );
+        self.apply_boot(in_packet, &mut reply);
+        Some(reply)
+    }
+
+    // Boot file and next server of the boot rule, the host's reservation may
+    // override the boot file
+    fn apply_boot(&self, in_packet: &Packet, reply: &mut Packet) {
+        if let Some(rule) = self.boot_policy.select(in_packet) {
+            rule.apply(in_packet, reply);
+        }
+        if let Some(boot_file) = self.reservation_for(in_packet).and_then(|r| r.get_boot_file().as_ref()) {
+            // On error set_file leaves the field empty
+            let _ = reply.set_file(boot_file);
+        }
+    }
+
+    // A DHCPNAK carries only the options allowed by RFC 2131 table 3
+    fn nak(&self, in_packet: &Packet, message: &str) -> Packet {
+        let mut options = vec![
+            DhcpOption::DhcpMessageType(DhcpMessageTypeCode::Nak),
+            DhcpOption::ServerIdentifier(self.server.server_ip),
+            DhcpOption::Message(message.to_string()),
+        ];
+        if let Some(id) = in_packet.get_client_identifier() {
+            options.push(DhcpOption::ClientIdentifier(id.clone()));
+        }
+        if let Some(ip) = in_packet.get_subnet_selection() {
+            options.push(DhcpOption::SubnetSelection(IpAddr::V4(ip)));
+        }
+        if let Some(sub_options) = in_packet.get_relay_agent_information() {
+            options.push(DhcpOption::RelayAgentInformation(sub_options.clone()));
+        }
+        // A relay agent broadcasts the NAK to the client (RFC 2131 section 4.3.2)
+        let flags = match in_packet.get_giaddr() {
+            0 => in_packet.get_flags(),
+            _ => in_packet.get_flags() | FLAG_BROADCAST,
+        };
+        reply_packet(in_packet, flags, 0, 0, options)
+    }
+    fn handle_dhcp_decline(&mut self, in_packet: &Packet) {
+        // A DHCPDECLINE is not answered
+        self.process_decline(in_packet);
+    }
+
+    /// Quarantine the address a client declined (RFC 2131 section 4.3.3): the
+    /// client's lease ends and the address stays out of the pool for the
+    /// decline probation time.
+    pub fn process_decline(&mut self, in_packet: &Packet) {
+        let now = now();
+        self.expire_declined(now);
+        if in_packet.get_server_identifier() != Some(self.server.server_ip) {
+            return;
+        }
+        let ip = match in_packet.get_requested_ip() {
+            Some(ip) if self.subnet_of(&ip).is_some() => IpAddr::V4(ip),
+            _ => return,
+        };
+        let (client_id, mac) = match client_key(in_packet) {
+            Some(key) => key,
+            None => return,
+        };
+        let client_id = client_id.as_deref();
+        if self
+            .leases
+            .get_by_ip(&ip)
+            .is_some_and(|lease| lease.is_for_client(client_id, &mac))
+        {
+            if let Err(e) = self.leases.remove(&ip).and_then(|_| self.leases.commit()) {
+                eprintln!("[ERROR] Failed to remove the lease of {}: {}", ip, e);
+                self.leases.rollback();
+            }
+        }
+        eprintln!(
+            "[WARN] {} declined by {}, another host uses it. Out of the pool for {}s",
+            ip, mac, self.decline_probation
         );
+        let until = now + Duration::from_secs(self.decline_probation as u64);
+        self.declined.insert(ip, DeclinedAddress::new(ip, mac, until));
+    }
+
+    // Return addresses whose probation time ended to the pool
+    fn expire_declined(&mut self, now: Duration) {
+        self.declined.retain(|ip, declined| {
+            let keep = !declined.is_expired(now);
+            if !keep {
+                eprintln!("[INFO] {} is back in the pool after its decline probation", ip);
+            }
+            keep
+        });
     }
-    fn handle_dhcp_request(&self, in_packet: &Packet) {
-        todo!()
+    fn is_declined(&self, ip: &IpAddr, now: Duration) -> bool {
+        self.declined
+            .get(ip)
+            .is_some_and(|declined| !declined.is_expired(now))
     }
-    fn handle_dhcp_decline(&self, in_packet: &Packet) {
-        todo!()
+    fn handle_dhcp_release(&mut self, in_packet: &Packet) {
+        // A DHCPRELEASE is not answered
+        self.process_release(in_packet);
     }
-    fn handle_dhcp_release(&self, in_packet: &Packet) {
-        todo!()
+
+    /// End the lease of a client that gives up its address (RFC 2131 section
+    /// 4.3.4). The lease is kept as expired so the client is remembered and
+    /// gets the same address next time, unless someone else took it.
+    pub fn process_release(&mut self, in_packet: &Packet) {
+        if in_packet.get_server_identifier() != Some(self.server.server_ip) {
+            return;
+        }
+        let ip = IpAddr::V4(Ipv4Addr::from(in_packet.get_ciaddr()));
+        let (client_id, mac) = match client_key(in_packet) {
+            Some(key) => key,
+            None => return,
+        };
+        let client_id = client_id.as_deref();
+        let mut lease = match self.leases.get_by_ip(&ip) {
+            Some(lease) if lease.is_for_client(client_id, &mac) => lease.clone(),
+            _ => return,
+        };
+        lease.set_expiry(now());
+        lease.set_state(BindingState::Released);
+        if let Err(e) = self.leases.update(lease).and_then(|_| self.leases.commit()) {
+            eprintln!("[ERROR] Failed to release the lease of {}: {}", ip, e);
+            self.leases.rollback();
+        }
+    }
+
+    fn handle_dhcp_inform(&self, in_packet: &Packet, classes: &[String]) {
+        if let Some(reply) = self.inform(in_packet, classes) {
+            if let Err(e) = self.server.send_reply(in_packet, &reply) {
+                eprintln!("[ERROR] Failed to send reply: {}", e);
+            }
+        }
+    }
+
+    /// Answer a DHCPINFORM from a host configured with the address ciaddr
+    /// (RFC 2131 section 4.3.5): a DHCPACK with the configuration options but
+    /// no yiaddr and no lease time.
+    pub fn process_inform(&self, in_packet: &Packet) -> Option<Packet> {
+        self.inform(in_packet, &self.classify(in_packet))
+    }
+
+    fn inform(&self, in_packet: &Packet, classes: &[String]) -> Option<Packet> {
+        if in_packet.get_ciaddr() == 0 {
+            return None;
+        }
+        let options = [
+            vec![
+                DhcpOption::DhcpMessageType(DhcpMessageTypeCode::Ack),
+                DhcpOption::ServerIdentifier(self.server.server_ip),
+            ],
+            self.reply_options(in_packet, classes, Ipv4Addr::from(in_packet.get_ciaddr())),
+        ]
+        .concat();
+        Some(reply_packet(
+            in_packet,
+            in_packet.get_flags(),
+            in_packet.get_ciaddr(),
+            0,
+            options,
+        ))
     }
     
 
 }
-impl Handler for DhcpServer {
+/// State of the client sending a DHCPREQUEST (RFC 2131 section 4.3.2).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RequestState {
+    /// Accepting the offer of the server with this identifier, for the address
+    Selecting(Ipv4Addr, Ipv4Addr),
+    /// Rebooted and verifying the address it had
+    InitReboot(Ipv4Addr),
+    /// Extending the lease of ciaddr. RENEWING and REBINDING only differ in
+    /// the destination of the message, unicast or broadcast.
+    Renewing(Ipv4Addr),
+}
+
+/// Tell the state of the client from the server identifier, the requested
+/// address and ciaddr, `None` when the combination is not valid.
+pub fn request_state(in_packet: &Packet) -> Option<RequestState> {
+    let ciaddr = Ipv4Addr::from(in_packet.get_ciaddr());
+    match (
+        in_packet.get_server_identifier(),
+        in_packet.get_requested_ip(),
+        ciaddr.is_unspecified(),
+    ) {
+        (Some(IpAddr::V4(server_id)), Some(ip), _) => Some(RequestState::Selecting(server_id, ip)),
+        (None, Some(ip), true) => Some(RequestState::InitReboot(ip)),
+        // Some clients send option 50 when renewing although they must not
+        (None, _, false) => Some(RequestState::Renewing(ciaddr)),
+        _ => None,
+    }
+}
+
+/// Where a reply to `in_packet` is sent (RFC 2131 section 4.1): to the relay
+/// agent, to ciaddr, or broadcast. Unicast to yiaddr needs an ARP entry for a
+/// client without an address, so those replies are broadcast as well.
+///
+/// The reply to a DHCPINFORM goes straight to ciaddr (section 4.3.5).
+pub fn reply_destination(in_packet: &Packet, reply: &Packet, broadcast_ip: IpAddr) -> SocketAddr {
+    let inform = in_packet.get_dhcp_message_type() == Some(&DhcpMessageTypeCode::Inform);
+    if inform && in_packet.get_ciaddr() != 0 {
+        let ciaddr = Ipv4Addr::from(in_packet.get_ciaddr());
+        return SocketAddr::new(IpAddr::V4(ciaddr), DHCP_CLIENT_PORT);
+    }
+    let giaddr = in_packet.get_giaddr();
+    if giaddr != 0 {
+        return SocketAddr::new(IpAddr::V4(Ipv4Addr::from(giaddr)), DHCP_SERVER_PORT);
+    }
+    let nak = reply.get_dhcp_message_type() == Some(&DhcpMessageTypeCode::Nak);
+    match in_packet.get_ciaddr() {
+        ciaddr if ciaddr != 0 && !nak => {
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::from(ciaddr)), DHCP_CLIENT_PORT)
+        }
+        _ => SocketAddr::new(broadcast_ip, DHCP_CLIENT_PORT),
+    }
+}
+
+// Client identifier (option 61) and hardware address the client is known by.
+// A client with neither an identifier nor an Ethernet address is known by
+// its hardware type and chaddr, as an identifier of that type (RFC 2132
+// section 9.14). None when chaddr is empty too and the client cannot be told
+// from others.
+fn client_key(in_packet: &Packet) -> Option<(Option<Vec<u8>>, MacAddress)> {
+    let mac = in_packet.get_client_mac().filter(|mac| !mac.is_nil());
+    if let Some(id) = in_packet.get_client_identifier() {
+        return Some((Some(id.clone()), mac.unwrap_or(MacAddress::nil())));
+    }
+    if let Some(mac) = mac {
+        return Some((None, mac));
+    }
+    let chaddr = in_packet.get_chaddr();
+    let chaddr = &chaddr[..(in_packet.get_hlen() as usize).min(chaddr.len())];
+    if chaddr.iter().all(|b| *b == 0) {
+        return None;
+    }
+    Some((Some([&[in_packet.get_htype()], chaddr].concat()), MacAddress::nil()))
+}
+
+// A BOOTREPLY to `in_packet`, sname and file are left empty
+fn reply_packet(in_packet: &Packet, flags: u16, ciaddr: u32, yiaddr: u32, options: Vec<DhcpOption>) -> Packet {
+    let mut chaddr = [0u8; 16];
+    chaddr.copy_from_slice(in_packet.get_chaddr());
+    Packet::new(
+        BOOTREPLY,
+        in_packet.get_htype(),
+        in_packet.get_hlen(),
+        0,
+        in_packet.get_xid(),
+        0,
+        flags,
+        ciaddr,
+        yiaddr,
+        0,
+        in_packet.get_giaddr(),
+        chaddr,
+        [0u8; 64],
+        [0u8; 128],
+        options,
+    )
+}
+
+// Time since the Unix epoch, as lease expiry times are stored
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Pick the configured options for a reply to a client with the Parameter
+/// Request List `requested` (all options when the client sent none).
+///
+/// Classless Static Routes (121, and the Microsoft copy 249) are only sent when
+/// asked for. A client that gets option 121 ignores the Routers option (RFC
+/// 3442), so Routers is left out and the first router is added to option 121
+/// as the default route unless one is configured already.
+pub fn select_reply_options(configured: &[DhcpOption], requested: Option<&Vec<u8>>) -> Vec<DhcpOption> {
+    let asked = |code: u8| requested.is_some_and(|prl| prl.contains(&code));
+    let mut options: Vec<DhcpOption> = configured
+        .iter()
+        .filter(|opt| requested.is_none_or(|prl| prl.contains(&opt.code())))
+        .filter(|opt| {
+            !matches!(
+                opt,
+                DhcpOption::ClasslessStaticRoutes(_) | DhcpOption::MsClasslessStaticRoutes(_)
+            )
+        })
+        .cloned()
+        .collect();
+
+    let routes = configured.iter().find_map(|opt| match opt {
+        DhcpOption::ClasslessStaticRoutes(routes) => Some(routes),
+        _ => None,
+    });
+    if let Some(routes) = routes {
+        let mut routes = routes.clone();
+        let default_router = configured.iter().find_map(|opt| match opt {
+            DhcpOption::Routers(routers) => routers.iter().find_map(|router| match router {
+                IpAddr::V4(router) => Some(*router),
+                IpAddr::V6(_) => None,
+            }),
+            _ => None,
+        });
+        let has_default = routes.iter().any(|(dst, _)| dst.get_prefix_len() == 0);
+        if let (false, Some(router), Ok(default)) =
+            (has_default, default_router, Ipv4Net::new(Ipv4Addr::UNSPECIFIED, 0))
+        {
+            routes.push((default, router));
+        }
+        if asked(CLASSLESS_ROUTE_FORMAT) {
+            options.retain(|opt| !matches!(opt, DhcpOption::Routers(_)));
+            options.push(DhcpOption::ClasslessStaticRoutes(routes.clone()));
+        }
+        if asked(MS_CLASSLESS_ROUTE_FORMAT) {
+            options.push(DhcpOption::MsClasslessStaticRoutes(routes));
+        }
+    }
+    options
+}
+
+impl<S: LeaseStore> Handler for DhcpServer<S> {
     fn handle_request(&mut self, in_packet: &Packet) {
         if !self.server.is_for_this_server(in_packet) {
             // Check for this server
             return;
         }
+        // Classes are matched once, every step of the answer uses them
+        let classes = self.classify(in_packet);
         match in_packet.get_dhcp_message_type() {
-            Some(DhcpMessageTypeCode::Discover) => self.handle_dhcp_discover(in_packet),
-            Some(DhcpMessageTypeCode::Request) => self.handle_dhcp_request(in_packet),
+            Some(DhcpMessageTypeCode::Discover) => self.handle_dhcp_discover(in_packet, &classes),
+            Some(DhcpMessageTypeCode::Request) => self.handle_dhcp_request(in_packet, &classes),
             Some(DhcpMessageTypeCode::Decline) => self.handle_dhcp_decline(in_packet),
             Some(DhcpMessageTypeCode::Release) => self.handle_dhcp_release(in_packet),
-            // Ack = 5,
-            // Nak = 6,
-            // Release = 7,
-            Some(_) => todo!(),
-            None => todo!(),
+            Some(DhcpMessageTypeCode::Inform) => self.handle_dhcp_inform(in_packet, &classes),
+            // Offer, Ack and Nak are sent by servers, BOOTP requests have no
+            // message type. Neither is answered.
+            Some(_) | None => {}
         }
     }
 }
 
+/// Logs the DHCP messages seen by `server` without answering them.
 pub struct DhcpMonitor {
     server: Server,
 }
 
+impl DhcpMonitor {
+    pub fn new(server: Server) -> DhcpMonitor {
+        DhcpMonitor { server }
+    }
+    /// Log the messages received until the socket fails.
+    pub fn serve(&mut self) -> Error {
+        loop {
+            match self.server.recv() {
+                Ok(p) => self.handle_request(&p),
+                Err(err) => return err,
+            }
+        }
+    }
+}
+
 impl Handler for DhcpMonitor {
     fn handle_request(&mut self, in_packet: &Packet) {
-        // TODO
-        // Just add log?
-        todo!()
+        println!(
+            "[INFO] {:?} from {:?} xid {:#010x}",
+            in_packet.get_dhcp_message_type(),
+            in_packet.get_client_mac(),
+            in_packet.get_xid()
+        );
     }
 }
 
@@ -171,6 +984,7 @@ pub struct Server {
     out_buf: Cell<[u8; 2048]>,
     socket: UdpSocket,
     socket_src: Cell<SocketAddr>,
+    max_msg_size: Cell<u16>,
     server_ip: IpAddr,
     broadcast_ip: IpAddr,
     loopback_ip: IpAddr,
@@ -181,43 +995,46 @@ pub trait Handler {
 }
 
 impl Server {
+    /// A server answering from `server_ip` on `socket`, which must be bound to
+    /// port 67 and allowed to broadcast to `broadcast_ip`.
+    pub fn new(socket: UdpSocket, server_ip: IpAddr, broadcast_ip: IpAddr) -> Server {
+        Server {
+            in_buf: Cell::new([0; 2048]),
+            out_buf: Cell::new([0; 2048]),
+            socket,
+            socket_src: Cell::new(SocketAddr::new(
+                IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                DHCP_CLIENT_PORT,
+            )),
+            max_msg_size: Cell::new(MIN_MAX_MESSAGE_SIZE),
+            server_ip,
+            broadcast_ip,
+            loopback_ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        }
+    }
+    pub fn get_server_ip(&self) -> IpAddr {
+        self.server_ip
+    }
     pub fn serve<H: Handler>(&mut self, handler: &mut H) -> Error {
         loop {
-            let (len, recv_src) = match self.socket.recv_from(self.in_buf.get_mut()) {
-                Ok(it) => it,
+            match self.recv() {
+                Ok(p) => handler.handle_request(&p),
                 Err(err) => return err,
-            };
-            if let Ok(p) = Packet::decode_from_unchecked(&self.in_buf.get()[..len]) {
-                // if let Ok(p) = Packet::from_unchecked(&self.in_buf.get()[..len]) {
-                self.socket_src.set(recv_src);
-                // write the out_buf
-                handler.handle_request(&p);
             }
-            // let in_packet = Packet::from_bytes(&self.in_buf[..len]);
-            // let out_packet = handler.handle_request(self, &in_packet);
-            // if let Some(out_packet) = out_packet {
-            //     self.socket.send_to(&out_packet.to_bytes(), self.socket_src).unwrap();
-            //     handler.handle_reply(self, &out_packet);
         }
     }
 
-    pub fn reply(
-        &self,
-        offer_ip: IpAddr,
-        optins_vec: Vec<DhcpOption>,
-        msg_type: DhcpMessageTypeCode,
-        prepare_packet: Packet,
-    ) -> std::io::Result<usize> {
-        let mut send_packet = &prepare_packet;
-        // send_packet.set_op(BOOTREPLY);
-        // // Ethernet (100Mb/1Gb/2.5G etc)
-        // send_packet.set_htype(1);
-        // send_packet.set_hlen(6);
-        // send_packet.set_hops(0);
-        // send_packet.set_xid(prepare_packet.get_xid());
-        // send_packet.set_secs(0);
-
-        self.send_packet(send_packet)
+    /// Wait for the next DHCP packet, datagrams that do not decode are dropped.
+    pub fn recv(&self) -> std::io::Result<Packet> {
+        loop {
+            let mut in_buf = self.in_buf.get();
+            let (len, recv_src) = self.socket.recv_from(&mut in_buf)?;
+            if let Ok(p) = Packet::decode_from_unchecked(&in_buf[..len]) {
+                self.socket_src.set(recv_src);
+                self.max_msg_size.set(p.get_max_message_size());
+                return Ok(p);
+            }
+        }
     }
 
     pub fn is_for_this_server(&self, p: &Packet) -> bool {
@@ -240,9 +1057,19 @@ impl Server {
         if addr.ip().is_loopback() {
             addr.set_ip(self.loopback_ip);
         }
-        self.socket
-            .send_to(send_p.encode(&mut self.out_buf.get()), addr)
+        self.send_to(send_p, addr)
     }
 
-    // pub fn reply
+    /// Send `reply` to the client of `in_packet`, see [`reply_destination`].
+    pub fn send_reply(&self, in_packet: &Packet, reply: &Packet) -> std::io::Result<usize> {
+        self.send_to(reply, reply_destination(in_packet, reply, self.broadcast_ip))
+    }
+
+    fn send_to(&self, send_p: &Packet, addr: SocketAddr) -> std::io::Result<usize> {
+        let mut out_buf = self.out_buf.get();
+        let bytes = send_p
+            .encode_with_max_size(&mut out_buf, self.max_msg_size.get())
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        self.socket.send_to(bytes, addr)
+    }
 }
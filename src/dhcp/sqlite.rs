@@ -0,0 +1,365 @@
+use crate::dhcp::{
+    format_client_id, BindingState, DhcpLease, LeaseStore, MemoryLeaseStore, StorageError,
+    StorageResult, INFINITE_EXPIRY,
+};
+use crate::ipnet::Ipv4Net;
+use crate::macaddress::MacAddress;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Times are seconds since the Unix epoch, a NULL expiry is an infinite lease
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS leases (
+    ip TEXT PRIMARY KEY,
+    mac TEXT NOT NULL,
+    client_id TEXT,
+    hostname TEXT,
+    start INTEGER,
+    expiry INTEGER,
+    state TEXT NOT NULL,
+    subnet TEXT
+);
+CREATE INDEX IF NOT EXISTS leases_mac ON leases (mac);
+CREATE INDEX IF NOT EXISTS leases_client_id ON leases (client_id);
+CREATE INDEX IF NOT EXISTS leases_expiry ON leases (expiry);
+CREATE TABLE IF NOT EXISTS lease_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time INTEGER NOT NULL,
+    ip TEXT NOT NULL,
+    mac TEXT,
+    client_id TEXT,
+    hostname TEXT,
+    start INTEGER,
+    expiry INTEGER,
+    state TEXT,
+    subnet TEXT
+);
+CREATE INDEX IF NOT EXISTS lease_history_ip ON lease_history (ip, time);
+CREATE INDEX IF NOT EXISTS lease_history_mac ON lease_history (mac, time);
+CREATE INDEX IF NOT EXISTS lease_history_time ON lease_history (time);
+";
+
+const HISTORY_COLUMNS: &str = "time, ip, mac, client_id, hostname, start, expiry, state, subnet";
+
+/// A change of the lease of an address, as kept in the lease history.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LeaseHistoryEntry {
+    time: Duration,
+    ip: IpAddr,
+    lease: Option<DhcpLease>,
+}
+
+impl LeaseHistoryEntry {
+    /// When the change took effect: the start of a granted lease, the end of
+    /// a released or expired one, else the commit time.
+    pub fn get_time(&self) -> &Duration {
+        &self.time
+    }
+    pub fn get_ip(&self) -> &IpAddr {
+        &self.ip
+    }
+    /// The lease after the change, None when it was removed.
+    pub fn get_lease(&self) -> &Option<DhcpLease> {
+        &self.lease
+    }
+}
+
+enum Change {
+    Put(DhcpLease),
+    Del(IpAddr),
+}
+
+/// Leases kept in a SQLite database, with the history of their changes for
+/// auditing, e.g. which client had an address at a given time, see
+/// [`SqliteLeaseStore::who_had`].
+///
+/// The current leases are cached in memory. A commit writes the changes
+/// since the last commit in one transaction, each with a history entry.
+/// Available with the `sqlite` feature.
+pub struct SqliteLeaseStore {
+    connection: Connection,
+    leases: MemoryLeaseStore,
+    pending: Vec<Change>,
+}
+
+impl SqliteLeaseStore {
+    /// Open the database at `path`, creating it and its tables if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> StorageResult<SqliteLeaseStore> {
+        SqliteLeaseStore::with_connection(Connection::open(path)?)
+    }
+    /// Use an open database, e.g. `Connection::open_in_memory()`.
+    pub fn with_connection(connection: Connection) -> StorageResult<SqliteLeaseStore> {
+        connection.execute_batch(SCHEMA)?;
+        let mut leases = MemoryLeaseStore::new();
+        {
+            let mut statement = connection
+                .prepare("SELECT ip, mac, client_id, hostname, start, expiry, state, subnet FROM leases")?;
+            let rows = statement.query_map([], |row| lease_from_row(row, 0))?;
+            for lease in rows {
+                leases.insert(lease??.ok_or_else(invalid_row)?)?;
+            }
+        }
+        leases.commit()?;
+        Ok(SqliteLeaseStore {
+            connection,
+            leases,
+            pending: vec![],
+        })
+    }
+    pub fn get_connection(&self) -> &Connection {
+        &self.connection
+    }
+    /// The lease of the client that held `ip` at `at`, if any.
+    pub fn who_had(&self, ip: &IpAddr, at: Duration) -> StorageResult<Option<DhcpLease>> {
+        let entry = self
+            .connection
+            .query_row(
+                &format!(
+                    "SELECT {} FROM lease_history WHERE ip = ?1 AND time <= ?2 \
+                     ORDER BY time DESC, id DESC LIMIT 1",
+                    HISTORY_COLUMNS
+                ),
+                params![ip.to_string(), to_secs(&at)],
+                entry_from_row,
+            )
+            .optional()?;
+        match entry {
+            Some(entry) => Ok(entry?.lease.filter(|lease| lease.is_active(at))),
+            None => Ok(None),
+        }
+    }
+    /// History of `ip`, oldest first.
+    pub fn history_by_ip(&self, ip: &IpAddr) -> StorageResult<Vec<LeaseHistoryEntry>> {
+        self.history("ip = ?1", params![ip.to_string()])
+    }
+    /// History of the leases of a hardware address, oldest first.
+    pub fn history_by_mac(&self, mac: &MacAddress) -> StorageResult<Vec<LeaseHistoryEntry>> {
+        self.history("mac = ?1", params![format_client_id(mac.get_octets())])
+    }
+    /// Changes from `from` until before `to`, oldest first.
+    pub fn history_between(
+        &self,
+        from: Duration,
+        to: Duration,
+    ) -> StorageResult<Vec<LeaseHistoryEntry>> {
+        self.history(
+            "time >= ?1 AND time < ?2",
+            params![to_secs(&from), to_secs(&to)],
+        )
+    }
+
+    fn history(
+        &self,
+        condition: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> StorageResult<Vec<LeaseHistoryEntry>> {
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT {} FROM lease_history WHERE {} ORDER BY time, id",
+            HISTORY_COLUMNS, condition
+        ))?;
+        let rows = statement.query_map(params, entry_from_row)?;
+        let mut entries = vec![];
+        for entry in rows {
+            entries.push(entry??);
+        }
+        Ok(entries)
+    }
+}
+
+impl LeaseStore for SqliteLeaseStore {
+    fn insert(&mut self, lease: DhcpLease) -> StorageResult<()> {
+        self.pending.push(Change::Put(lease.clone()));
+        self.leases.insert(lease)
+    }
+    fn update(&mut self, lease: DhcpLease) -> StorageResult<()> {
+        self.leases.update(lease.clone())?;
+        self.pending.push(Change::Put(lease));
+        Ok(())
+    }
+    fn remove(&mut self, ip: &IpAddr) -> StorageResult<Option<DhcpLease>> {
+        let lease = self.leases.remove(ip)?;
+        if lease.is_some() {
+            self.pending.push(Change::Del(*ip));
+        }
+        Ok(lease)
+    }
+    fn get_by_ip(&self, ip: &IpAddr) -> Option<&DhcpLease> {
+        self.leases.get_by_ip(ip)
+    }
+    fn get_by_mac(&self, mac: &MacAddress) -> Vec<&DhcpLease> {
+        self.leases.get_by_mac(mac)
+    }
+    fn get_by_client_id(&self, client_id: &str) -> Vec<&DhcpLease> {
+        self.leases.get_by_client_id(client_id)
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = &DhcpLease> + '_> {
+        self.leases.iter()
+    }
+    fn iter_by_expiry(&self) -> Box<dyn Iterator<Item = &DhcpLease> + '_> {
+        self.leases.iter_by_expiry()
+    }
+    fn commit(&mut self) -> StorageResult<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let transaction = self.connection.transaction()?;
+        for change in &self.pending {
+            match change {
+                Change::Put(lease) => {
+                    let row = LeaseRow::new(lease);
+                    transaction.execute(
+                        "INSERT OR REPLACE INTO leases (ip, mac, client_id, hostname, start, expiry, state, subnet) \
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                        params![row.ip, row.mac, row.client_id, row.hostname, row.start, row.expiry, row.state, row.subnet],
+                    )?;
+                    transaction.execute(
+                        &format!("INSERT INTO lease_history ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)", HISTORY_COLUMNS),
+                        params![
+                            to_secs(&change_time(lease, now)),
+                            row.ip,
+                            row.mac,
+                            row.client_id,
+                            row.hostname,
+                            row.start,
+                            row.expiry,
+                            row.state,
+                            row.subnet
+                        ],
+                    )?;
+                }
+                Change::Del(ip) => {
+                    transaction
+                        .execute("DELETE FROM leases WHERE ip = ?1", params![ip.to_string()])?;
+                    transaction.execute(
+                        "INSERT INTO lease_history (time, ip) VALUES (?1, ?2)",
+                        params![to_secs(&now), ip.to_string()],
+                    )?;
+                }
+            }
+        }
+        transaction.commit()?;
+        self.pending.clear();
+        self.leases.commit()
+    }
+    fn rollback(&mut self) {
+        self.pending.clear();
+        self.leases.rollback();
+    }
+    fn len(&self) -> usize {
+        self.leases.len()
+    }
+}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(e: rusqlite::Error) -> Self {
+        StorageError::Database(e.to_string())
+    }
+}
+
+// Columns of a lease in the leases and lease_history tables
+struct LeaseRow {
+    ip: String,
+    mac: String,
+    client_id: Option<String>,
+    hostname: Option<String>,
+    start: Option<i64>,
+    expiry: Option<i64>,
+    state: String,
+    subnet: Option<String>,
+}
+
+impl LeaseRow {
+    fn new(lease: &DhcpLease) -> LeaseRow {
+        LeaseRow {
+            ip: lease.get_ip().to_string(),
+            mac: format_client_id(lease.get_mac().get_octets()),
+            client_id: lease.get_chi().clone(),
+            hostname: lease.get_hostname().clone(),
+            start: lease.get_start().as_ref().map(to_secs),
+            expiry: match *lease.get_expiry() {
+                INFINITE_EXPIRY => None,
+                expiry => Some(to_secs(&expiry)),
+            },
+            state: lease.get_state().to_string(),
+            subnet: lease.get_subnet().as_ref().map(|subnet| subnet.to_string()),
+        }
+    }
+}
+
+fn to_secs(time: &Duration) -> i64 {
+    i64::try_from(time.as_secs()).unwrap_or(i64::MAX)
+}
+
+fn from_secs(secs: i64) -> Duration {
+    Duration::from_secs(secs.max(0) as u64)
+}
+
+// When a change took effect, see LeaseHistoryEntry::get_time
+fn change_time(lease: &DhcpLease, now: Duration) -> Duration {
+    match (lease.get_state().is_bound(), lease.get_start()) {
+        (true, Some(start)) => *start,
+        (true, None) => now,
+        (false, _) => (*lease.get_expiry()).min(now),
+    }
+}
+
+fn invalid_row() -> StorageError {
+    StorageError::Database("invalid lease row".to_string())
+}
+
+// The lease in the columns from `first` on, None for a history entry of a
+// removed lease
+fn lease_from_row(row: &Row, first: usize) -> rusqlite::Result<StorageResult<Option<DhcpLease>>> {
+    let ip: String = row.get(first)?;
+    let mac: Option<String> = row.get(first + 1)?;
+    let client_id: Option<String> = row.get(first + 2)?;
+    let hostname: Option<String> = row.get(first + 3)?;
+    let start: Option<i64> = row.get(first + 4)?;
+    let expiry: Option<i64> = row.get(first + 5)?;
+    let state: Option<String> = row.get(first + 6)?;
+    let subnet: Option<String> = row.get(first + 7)?;
+    let (mac, state) = match (mac, state) {
+        (Some(mac), Some(state)) => (mac, state),
+        _ => return Ok(Ok(None)),
+    };
+    let parsed = (
+        MacAddress::from_str(&mac),
+        ip.parse(),
+        BindingState::from_str(&state),
+        subnet.map(|subnet| Ipv4Net::from_str(&subnet)).transpose(),
+    );
+    let (mac, ip, state, subnet) = match parsed {
+        (Ok(mac), Ok(ip), Ok(state), Ok(subnet)) => (mac, ip, state, subnet),
+        _ => return Ok(Err(invalid_row())),
+    };
+    let expiry = expiry.map_or(INFINITE_EXPIRY, from_secs);
+    let mut lease = DhcpLease::new(expiry, mac, ip, hostname, client_id);
+    if let Some(start) = start {
+        lease.set_start(from_secs(start));
+    }
+    lease.set_state(state);
+    if let Some(subnet) = subnet {
+        lease.set_subnet(subnet);
+    }
+    Ok(Ok(Some(lease)))
+}
+
+fn entry_from_row(row: &Row) -> rusqlite::Result<StorageResult<LeaseHistoryEntry>> {
+    let time: i64 = row.get(0)?;
+    let ip: String = row.get(1)?;
+    let (ip, lease) = match (ip.parse(), lease_from_row(row, 1)?) {
+        (Ok(ip), Ok(lease)) => (ip, lease),
+        _ => return Ok(Err(invalid_row())),
+    };
+    Ok(Ok(LeaseHistoryEntry {
+        time: from_secs(time),
+        ip,
+        lease,
+    }))
+}
@@ -1,3 +1,326 @@
-pub fn test_storage() {
-  println!("[TEST] test_options");
-}
\ No newline at end of file
+use crate::dhcp::{format_client_id, DhcpLease, DnsmasqLeases, LeaseError};
+use crate::macaddress::MacAddress;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageError {
+    LeaseNotFound(IpAddr),
+    // Line number, starting at 1, of a lease file entry that does not parse
+    InvalidLease(usize, LeaseError),
+    // Line number of a journal record whose checksum does not match
+    ChecksumMismatch(usize),
+    // Error of the database of a database backed store
+    Database(String),
+    Io(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::LeaseNotFound(ip) => write!(fmt, "No lease for {}", ip),
+            StorageError::InvalidLease(line, e) => {
+                write!(fmt, "Invalid lease on line {}: {}", line, e)
+            }
+            StorageError::ChecksumMismatch(line) => {
+                write!(fmt, "Checksum mismatch on line {}", line)
+            }
+            StorageError::Database(e) => write!(fmt, "Lease database error: {}", e),
+            StorageError::Io(e) => write!(fmt, "Lease storage I/O error: {}", e),
+        }
+    }
+}
+
+impl Error for StorageError {}
+
+impl From<std::io::Error> for StorageError {
+    fn from(e: std::io::Error) -> Self {
+        StorageError::Io(e.to_string())
+    }
+}
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+/// Where the server keeps its leases.
+///
+/// Changes are visible to lookups at once and made durable together by
+/// [`LeaseStore::commit`]: after a crash the store holds the leases of the
+/// last commit, never part of the changes made since. The server commits
+/// before it answers a client and rolls back the changes of a failed commit.
+pub trait LeaseStore {
+    /// Add a lease, replacing the lease of the same address.
+    fn insert(&mut self, lease: DhcpLease) -> StorageResult<()>;
+    /// Replace the lease of the same address, which must exist.
+    fn update(&mut self, lease: DhcpLease) -> StorageResult<()>;
+    fn remove(&mut self, ip: &IpAddr) -> StorageResult<Option<DhcpLease>>;
+    fn get_by_ip(&self, ip: &IpAddr) -> Option<&DhcpLease>;
+    /// Leases of a hardware address, a client may have one per subnet.
+    fn get_by_mac(&self, mac: &MacAddress) -> Vec<&DhcpLease>;
+    /// Leases of a client identifier formatted with [`format_client_id`].
+    fn get_by_client_id(&self, client_id: &str) -> Vec<&DhcpLease>;
+    fn iter(&self) -> Box<dyn Iterator<Item = &DhcpLease> + '_>;
+    /// Leases in order of expiry, the first to expire first.
+    fn iter_by_expiry(&self) -> Box<dyn Iterator<Item = &DhcpLease> + '_>;
+    /// Make the changes since the last commit durable, all or none of them.
+    fn commit(&mut self) -> StorageResult<()>;
+    /// Drop the changes since the last commit, e.g. after it failed.
+    fn rollback(&mut self);
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Leases that expired at `now`, see [`LeaseStore::iter_by_expiry`].
+    fn expired(&self, now: Duration) -> Vec<&DhcpLease> {
+        self.iter_by_expiry()
+            .take_while(|lease| lease.is_expired(now))
+            .collect()
+    }
+    /// Leases of the client with `client_id` and `mac`, see
+    /// [`DhcpLease::is_for_client`].
+    fn find_client(&self, client_id: Option<&[u8]>, mac: &MacAddress) -> Vec<&DhcpLease> {
+        let mut leases = match client_id {
+            Some(id) => self.get_by_client_id(&format_client_id(id)),
+            None => vec![],
+        };
+        for lease in self.get_by_mac(mac) {
+            if !leases.iter().any(|l| l.get_ip() == lease.get_ip()) {
+                leases.push(lease);
+            }
+        }
+        leases.retain(|lease| lease.is_for_client(client_id, mac));
+        leases
+    }
+}
+
+/// Leases kept in memory only, indexed by address, hardware address, client
+/// identifier and expiry. The leases replaced since the last commit are kept
+/// for [`LeaseStore::rollback`].
+#[derive(Clone, Debug, Default)]
+pub struct MemoryLeaseStore {
+    leases: HashMap<IpAddr, DhcpLease>,
+    by_mac: HashMap<MacAddress, Vec<IpAddr>>,
+    by_client_id: HashMap<String, Vec<IpAddr>>,
+    by_expiry: BTreeSet<(Duration, IpAddr)>,
+    // The lease each change since the last commit replaced, oldest first
+    undo: Vec<(IpAddr, Option<DhcpLease>)>,
+}
+
+impl MemoryLeaseStore {
+    pub fn new() -> MemoryLeaseStore {
+        MemoryLeaseStore::default()
+    }
+
+    fn index(&mut self, lease: &DhcpLease) {
+        let ip = *lease.get_ip();
+        self.by_mac.entry(*lease.get_mac()).or_default().push(ip);
+        if let Some(chi) = lease.get_chi() {
+            self.by_client_id.entry(chi.clone()).or_default().push(ip);
+        }
+        self.by_expiry.insert((*lease.get_expiry(), ip));
+    }
+
+    fn unindex(&mut self, lease: &DhcpLease) {
+        let ip = lease.get_ip();
+        if let Some(ips) = self.by_mac.get_mut(lease.get_mac()) {
+            ips.retain(|i| i != ip);
+            if ips.is_empty() {
+                self.by_mac.remove(lease.get_mac());
+            }
+        }
+        if let Some(chi) = lease.get_chi() {
+            if let Some(ips) = self.by_client_id.get_mut(chi) {
+                ips.retain(|i| i != ip);
+                if ips.is_empty() {
+                    self.by_client_id.remove(chi);
+                }
+            }
+        }
+        self.by_expiry.remove(&(*lease.get_expiry(), *ip));
+    }
+
+    // Remove the lease of `ip` without recording the change
+    fn take(&mut self, ip: &IpAddr) -> Option<DhcpLease> {
+        let lease = self.leases.remove(ip);
+        if let Some(lease) = &lease {
+            self.unindex(lease);
+        }
+        lease
+    }
+
+    // Add a lease without recording the change
+    fn put(&mut self, lease: DhcpLease) {
+        self.take(lease.get_ip());
+        self.index(&lease);
+        self.leases.insert(*lease.get_ip(), lease);
+    }
+
+    fn lookup(&self, ips: Option<&Vec<IpAddr>>) -> Vec<&DhcpLease> {
+        ips.map_or(vec![], |ips| {
+            ips.iter().filter_map(|ip| self.leases.get(ip)).collect()
+        })
+    }
+}
+
+impl LeaseStore for MemoryLeaseStore {
+    fn insert(&mut self, lease: DhcpLease) -> StorageResult<()> {
+        let ip = *lease.get_ip();
+        let old = self.leases.get(&ip).cloned();
+        self.undo.push((ip, old));
+        self.put(lease);
+        Ok(())
+    }
+    fn update(&mut self, lease: DhcpLease) -> StorageResult<()> {
+        if !self.leases.contains_key(lease.get_ip()) {
+            return Err(StorageError::LeaseNotFound(*lease.get_ip()));
+        }
+        self.insert(lease)
+    }
+    fn remove(&mut self, ip: &IpAddr) -> StorageResult<Option<DhcpLease>> {
+        let lease = self.take(ip);
+        if lease.is_some() {
+            self.undo.push((*ip, lease.clone()));
+        }
+        Ok(lease)
+    }
+    fn get_by_ip(&self, ip: &IpAddr) -> Option<&DhcpLease> {
+        self.leases.get(ip)
+    }
+    fn get_by_mac(&self, mac: &MacAddress) -> Vec<&DhcpLease> {
+        self.lookup(self.by_mac.get(mac))
+    }
+    fn get_by_client_id(&self, client_id: &str) -> Vec<&DhcpLease> {
+        self.lookup(self.by_client_id.get(client_id))
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = &DhcpLease> + '_> {
+        Box::new(self.leases.values())
+    }
+    fn iter_by_expiry(&self) -> Box<dyn Iterator<Item = &DhcpLease> + '_> {
+        Box::new(
+            self.by_expiry
+                .iter()
+                .filter_map(|(_, ip)| self.leases.get(ip)),
+        )
+    }
+    fn commit(&mut self) -> StorageResult<()> {
+        self.undo.clear();
+        Ok(())
+    }
+    fn rollback(&mut self) {
+        while let Some((ip, old)) = self.undo.pop() {
+            self.take(&ip);
+            if let Some(old) = old {
+                self.put(old);
+            }
+        }
+    }
+    fn len(&self) -> usize {
+        self.leases.len()
+    }
+}
+
+/// Leases kept in memory and written to a dnsmasq.leases file on commit, see
+/// [`DnsmasqLeases`].
+///
+/// A commit writes a new file next to it and renames it over the old one, so
+/// the file is always complete. The DUID line and DHCPv6 leases of a file
+/// taken over from dnsmasq are written back as they were.
+#[derive(Clone, Debug)]
+pub struct FileLeaseStore {
+    path: PathBuf,
+    leases: MemoryLeaseStore,
+    // The file without its DHCPv4 leases
+    file: DnsmasqLeases,
+}
+
+impl FileLeaseStore {
+    /// Open the lease file at `path`, a missing file is an empty store.
+    pub fn open<P: AsRef<Path>>(path: P) -> StorageResult<FileLeaseStore> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = match fs::read_to_string(&path) {
+            Ok(content) => DnsmasqLeases::parse(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => DnsmasqLeases::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut leases = MemoryLeaseStore::new();
+        for lease in file.get_leases() {
+            leases.insert(lease.clone())?;
+        }
+        leases.commit()?;
+        file.set_leases(vec![]);
+        Ok(FileLeaseStore { path, leases, file })
+    }
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl LeaseStore for FileLeaseStore {
+    fn insert(&mut self, lease: DhcpLease) -> StorageResult<()> {
+        self.leases.insert(lease)
+    }
+    fn update(&mut self, lease: DhcpLease) -> StorageResult<()> {
+        self.leases.update(lease)
+    }
+    fn remove(&mut self, ip: &IpAddr) -> StorageResult<Option<DhcpLease>> {
+        self.leases.remove(ip)
+    }
+    fn get_by_ip(&self, ip: &IpAddr) -> Option<&DhcpLease> {
+        self.leases.get_by_ip(ip)
+    }
+    fn get_by_mac(&self, mac: &MacAddress) -> Vec<&DhcpLease> {
+        self.leases.get_by_mac(mac)
+    }
+    fn get_by_client_id(&self, client_id: &str) -> Vec<&DhcpLease> {
+        self.leases.get_by_client_id(client_id)
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = &DhcpLease> + '_> {
+        self.leases.iter()
+    }
+    fn iter_by_expiry(&self) -> Box<dyn Iterator<Item = &DhcpLease> + '_> {
+        self.leases.iter_by_expiry()
+    }
+    fn commit(&mut self) -> StorageResult<()> {
+        let mut file = self.file.clone();
+        file.set_leases(self.leases.iter_by_expiry().cloned().collect());
+        write_atomically(&self.path, file.to_string().as_bytes())?;
+        self.leases.commit()
+    }
+    fn rollback(&mut self) {
+        self.leases.rollback()
+    }
+    fn len(&self) -> usize {
+        self.leases.len()
+    }
+}
+
+/// Replace the file at `path` with `content`: the content goes to a temporary
+/// file in the same directory, synced to disk, then renamed over `path`. The
+/// directory is synced last so the rename survives a crash.
+pub fn write_atomically(path: &Path, content: &[u8]) -> StorageResult<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    {
+        let mut file = fs::File::create(&tmp)?;
+        std::io::Write::write_all(&mut file, content)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, path)?;
+    sync_dir_of(path)
+}
+
+// Sync the directory of `path`, making a rename or new file in it durable
+pub(crate) fn sync_dir_of(path: &Path) -> StorageResult<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    fs::File::open(dir)?.sync_all()?;
+    Ok(())
+}
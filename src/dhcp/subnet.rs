@@ -0,0 +1,97 @@
+use crate::dhcp::packet::Packet;
+use crate::dhcp::{AddressPool, DhcpOption};
+use crate::ipnet::Ipv4Net;
+use std::net::Ipv4Addr;
+
+/// A subnet served by the server and the pools its addresses are allocated
+/// from.
+///
+/// Subnets with the same shared network name are on the same link, e.g. a
+/// VLAN with a secondary subnet. A client on that link may get an address of
+/// any of them.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Subnet {
+    net: Ipv4Net,
+    pools: Vec<AddressPool>,
+    shared_network: Option<String>,
+    options: Vec<DhcpOption>,
+}
+
+impl Subnet {
+    /// A subnet without pools, only reserved addresses are handed out on it.
+    pub fn new(net: Ipv4Net) -> Subnet {
+        Subnet {
+            net,
+            pools: vec![],
+            shared_network: None,
+            options: vec![],
+        }
+    }
+    /// Hand out the addresses from `first` to `last`, both included.
+    pub fn add_pool(&mut self, first: Ipv4Addr, last: Ipv4Addr) {
+        self.pools.push(AddressPool::new(first, last));
+    }
+    /// Add a pool configured beforehand, e.g. one restricted to some classes.
+    pub fn add_address_pool(&mut self, pool: AddressPool) {
+        self.pools.push(pool);
+    }
+    /// Put the subnet on the link named `name`, see [`Subnet`].
+    pub fn set_shared_network(&mut self, name: &str) {
+        self.shared_network = Some(name.to_string());
+    }
+    /// Set the options sent to clients of this subnet. They replace the
+    /// server-wide options with the same code.
+    pub fn set_options(&mut self, options: Vec<DhcpOption>) {
+        self.options = options;
+    }
+    pub fn get_net(&self) -> &Ipv4Net {
+        &self.net
+    }
+    pub fn get_pools(&self) -> &[AddressPool] {
+        &self.pools
+    }
+    pub fn get_pools_mut(&mut self) -> &mut [AddressPool] {
+        &mut self.pools
+    }
+    pub fn get_shared_network(&self) -> &Option<String> {
+        &self.shared_network
+    }
+    pub fn get_options(&self) -> &[DhcpOption] {
+        &self.options
+    }
+    pub fn contains(&self, ip: &Ipv4Addr) -> bool {
+        self.net.contains(ip)
+    }
+    /// Whether `ip` is in one of the pools of the subnet.
+    pub fn in_pool(&self, ip: &Ipv4Addr) -> bool {
+        self.pools.iter().any(|pool| pool.contains(ip))
+    }
+    /// Pools a member of `classes` may get an address from.
+    pub fn pools_for<'a>(&'a self, classes: &'a [String]) -> impl Iterator<Item = &'a AddressPool> {
+        self.pools.iter().filter(|pool| pool.allows(classes))
+    }
+    /// Whether `other` is on the same link, itself included.
+    pub fn shares_link_with(&self, other: &Subnet) -> bool {
+        match (&self.shared_network, &other.shared_network) {
+            (Some(name), Some(other_name)) => name == other_name,
+            _ => self.net == other.net,
+        }
+    }
+}
+
+/// Address on the link of the client of `in_packet`, taken in order from the
+/// Subnet Selection option (RFC 3011), the Link Selection sub-option of the
+/// relay agent (RFC 3527), the relay address and the client address. `None`
+/// for a client on the link the packet arrived on.
+pub fn link_address(in_packet: &Packet) -> Option<Ipv4Addr> {
+    if let Some(ip) = in_packet.get_subnet_selection() {
+        return Some(ip);
+    }
+    if let Some(ip) = in_packet.get_link_selection() {
+        return Some(ip);
+    }
+    [in_packet.get_giaddr(), in_packet.get_ciaddr()]
+        .into_iter()
+        .find(|ip| *ip != 0)
+        .map(Ipv4Addr::from)
+}
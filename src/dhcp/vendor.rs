@@ -0,0 +1,287 @@
+use crate::dhcp::packet::{ConvertPacketError, ConvertResult};
+use crate::dhcp::VENDOR_SPECIFIC_INFORMATION;
+use std::net::Ipv4Addr;
+
+// PXE sub-options (PXE specification 2.1 section 2.4)
+pub const PXE_DISCOVERY_CONTROL: u8 = 6;
+pub const PXE_BOOT_SERVERS: u8 = 8;
+pub const PXE_BOOT_MENU: u8 = 9;
+pub const PXE_MENU_PROMPT: u8 = 10;
+pub const PXE_BOOT_ITEM: u8 = 71;
+// Cisco lightweight access points, addresses of the wireless LAN controllers
+pub const CISCO_AP_CONTROLLERS: u8 = 241;
+// Ubiquiti UniFi devices, address of the controller
+pub const UBIQUITI_CONTROLLER: u8 = 1;
+
+// Encapsulated options use the option format of RFC 2132 section 8.4,
+// including Pad and End
+const SUB_OPTION_PAD: u8 = 0;
+const SUB_OPTION_END: u8 = 255;
+
+/// Type of the payload of a vendor sub-option.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum VendorOptionType {
+    Bytes,
+    String,
+    U8,
+    U16,
+    U32,
+    Bool,
+    Ip,
+    Ips,
+}
+
+/// A typed vendor sub-option payload.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum VendorValue {
+    Bytes(Vec<u8>),
+    String(String),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    Bool(bool),
+    Ip(Ipv4Addr),
+    Ips(Vec<Ipv4Addr>),
+}
+
+impl VendorValue {
+    pub fn kind(&self) -> VendorOptionType {
+        match self {
+            VendorValue::Bytes(_) => VendorOptionType::Bytes,
+            VendorValue::String(_) => VendorOptionType::String,
+            VendorValue::U8(_) => VendorOptionType::U8,
+            VendorValue::U16(_) => VendorOptionType::U16,
+            VendorValue::U32(_) => VendorOptionType::U32,
+            VendorValue::Bool(_) => VendorOptionType::Bool,
+            VendorValue::Ip(_) => VendorOptionType::Ip,
+            VendorValue::Ips(_) => VendorOptionType::Ips,
+        }
+    }
+
+    /// Parses `data` as `kind`, `None` when the payload does not fit.
+    pub fn decode(kind: VendorOptionType, data: &[u8]) -> Option<VendorValue> {
+        let value = match kind {
+            VendorOptionType::Bytes => VendorValue::Bytes(data.to_vec()),
+            VendorOptionType::String => VendorValue::String(String::from_utf8(data.to_vec()).ok()?),
+            VendorOptionType::U8 => VendorValue::U8(u8::from_be_bytes(data.try_into().ok()?)),
+            VendorOptionType::U16 => VendorValue::U16(u16::from_be_bytes(data.try_into().ok()?)),
+            VendorOptionType::U32 => VendorValue::U32(u32::from_be_bytes(data.try_into().ok()?)),
+            VendorOptionType::Bool => match data {
+                [0] => VendorValue::Bool(false),
+                [1] => VendorValue::Bool(true),
+                _ => return None,
+            },
+            VendorOptionType::Ip => {
+                VendorValue::Ip(Ipv4Addr::from(<[u8; 4]>::try_from(data).ok()?))
+            }
+            VendorOptionType::Ips if !data.is_empty() && data.len().is_multiple_of(4) => VendorValue::Ips(
+                data.chunks(4)
+                    .map(|ip| Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]))
+                    .collect(),
+            ),
+            VendorOptionType::Ips => return None,
+        };
+        Some(value)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            VendorValue::Bytes(data) => data.clone(),
+            VendorValue::String(s) => s.as_bytes().to_vec(),
+            VendorValue::U8(v) => vec![*v],
+            VendorValue::U16(v) => v.to_be_bytes().to_vec(),
+            VendorValue::U32(v) => v.to_be_bytes().to_vec(),
+            VendorValue::Bool(v) => vec![*v as u8],
+            VendorValue::Ip(ip) => ip.octets().to_vec(),
+            VendorValue::Ips(ips) => ips.iter().flat_map(|ip| ip.octets()).collect(),
+        }
+    }
+}
+
+/// A sub-option of the Vendor-Specific Information option (43).
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct VendorSubOption {
+    code: u8,
+    value: VendorValue,
+}
+
+impl VendorSubOption {
+    pub fn new(code: u8, value: VendorValue) -> VendorSubOption {
+        VendorSubOption { code, value }
+    }
+    pub fn get_code(&self) -> u8 {
+        self.code
+    }
+    pub fn get_value(&self) -> &VendorValue {
+        &self.value
+    }
+}
+
+/// The option space encapsulated in option 43 for one vendor (RFC 2132
+/// section 8.4).
+///
+/// A space applies to clients whose Vendor Class Identifier (option 60) starts
+/// with its vendor class, e.g. `PXEClient` for `PXEClient:Arch:00007:UNDI:003016`.
+/// Sub-option codes are given a type with [`VendorOptionSpace::define`],
+/// undefined codes or payloads that do not fit their type decode as bytes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct VendorOptionSpace {
+    name: String,
+    vendor_class: Vec<u8>,
+    types: Vec<(u8, VendorOptionType)>,
+    sub_options: Vec<VendorSubOption>,
+    end_option: bool,
+}
+
+impl VendorOptionSpace {
+    pub fn new(name: &str, vendor_class: &[u8]) -> VendorOptionSpace {
+        VendorOptionSpace {
+            name: name.to_string(),
+            vendor_class: vendor_class.to_vec(),
+            types: vec![],
+            sub_options: vec![],
+            end_option: false,
+        }
+    }
+
+    /// PXE clients, the payload is terminated by an End sub-option.
+    pub fn pxe() -> VendorOptionSpace {
+        let mut space = VendorOptionSpace::new("pxe", b"PXEClient");
+        space.define(PXE_DISCOVERY_CONTROL, VendorOptionType::U8);
+        space.define(PXE_BOOT_SERVERS, VendorOptionType::Bytes);
+        space.define(PXE_BOOT_MENU, VendorOptionType::Bytes);
+        space.define(PXE_MENU_PROMPT, VendorOptionType::Bytes);
+        space.define(PXE_BOOT_ITEM, VendorOptionType::Bytes);
+        space.set_end_option(true);
+        space
+    }
+    pub fn cisco_ap() -> VendorOptionSpace {
+        let mut space = VendorOptionSpace::new("cisco-ap", b"Cisco AP");
+        space.define(CISCO_AP_CONTROLLERS, VendorOptionType::Ips);
+        space
+    }
+    pub fn ubiquiti() -> VendorOptionSpace {
+        let mut space = VendorOptionSpace::new("ubiquiti", b"ubnt");
+        space.define(UBIQUITI_CONTROLLER, VendorOptionType::Ip);
+        space
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    pub fn get_vendor_class(&self) -> &[u8] {
+        &self.vendor_class
+    }
+    pub fn get_sub_options(&self) -> &[VendorSubOption] {
+        &self.sub_options
+    }
+    pub fn get_type(&self, code: u8) -> Option<VendorOptionType> {
+        self.types
+            .iter()
+            .find_map(|(c, kind)| (*c == code).then_some(*kind))
+    }
+    /// Give sub-option `code` the type `kind`, replacing an earlier definition.
+    pub fn define(&mut self, code: u8, kind: VendorOptionType) {
+        self.types.retain(|(c, _)| *c != code);
+        self.types.push((code, kind));
+    }
+    /// Terminate the encoded payload with an End sub-option.
+    pub fn set_end_option(&mut self, end_option: bool) {
+        self.end_option = end_option;
+    }
+    /// Add a sub-option sent to the clients of this vendor. The value must
+    /// match the type of the code when it is defined.
+    pub fn add_sub_option(&mut self, code: u8, value: VendorValue) -> ConvertResult<()> {
+        let fits = self
+            .get_type(code)
+            .is_none_or(|kind| kind == value.kind());
+        if !fits || code == SUB_OPTION_PAD || code == SUB_OPTION_END {
+            return Err(ConvertPacketError::InvalidOptionValue(
+                VENDOR_SPECIFIC_INFORMATION,
+            ));
+        }
+        self.sub_options.push(VendorSubOption::new(code, value));
+        Ok(())
+    }
+
+    pub fn matches(&self, vendor_class: &[u8]) -> bool {
+        vendor_class.starts_with(&self.vendor_class)
+    }
+
+    /// Parses an option 43 payload sent by a client of this vendor.
+    pub fn decode(&self, data: &[u8]) -> ConvertResult<Vec<VendorSubOption>> {
+        self.decode_option(VENDOR_SPECIFIC_INFORMATION, data)
+    }
+
+    /// Parses the payload of option `code` as sub-options of this space, for
+    /// spaces carried in another option than 43.
+    pub fn decode_option(&self, code: u8, data: &[u8]) -> ConvertResult<Vec<VendorSubOption>> {
+        let invalid_length = ConvertPacketError::InvalidOptionLength(code);
+        let mut sub_options = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let code = data[i];
+            match code {
+                SUB_OPTION_PAD => {
+                    i += 1;
+                    continue;
+                }
+                SUB_OPTION_END => break,
+                _ => {}
+            }
+            let len = *data.get(i + 1).ok_or(invalid_length.clone())? as usize;
+            let sub_data = data.get(i + 2..i + 2 + len).ok_or(invalid_length.clone())?;
+            let value = self
+                .get_type(code)
+                .and_then(|kind| VendorValue::decode(kind, sub_data))
+                .unwrap_or_else(|| VendorValue::Bytes(sub_data.to_vec()));
+            sub_options.push(VendorSubOption::new(code, value));
+            i += 2 + len;
+        }
+        Ok(sub_options)
+    }
+
+    /// Encodes the sub-options added to this space as an option 43 payload.
+    pub fn encode(&self) -> ConvertResult<Vec<u8>> {
+        encode_sub_options(
+            VENDOR_SPECIFIC_INFORMATION,
+            &self.sub_options,
+            self.end_option,
+        )
+    }
+}
+
+/// Encodes `sub_options` as the payload of option `code`, followed by an End
+/// sub-option when `end_option` is set.
+pub fn encode_sub_options(
+    code: u8,
+    sub_options: &[VendorSubOption],
+    end_option: bool,
+) -> ConvertResult<Vec<u8>> {
+    let mut data = Vec::new();
+    for sub_option in sub_options {
+        let sub_data = sub_option.value.encode();
+        if sub_data.len() > u8::MAX as usize {
+            return Err(ConvertPacketError::InvalidOptionLength(code));
+        }
+        data.push(sub_option.code);
+        data.push(sub_data.len() as u8);
+        data.extend_from_slice(&sub_data);
+    }
+    if end_option {
+        data.push(SUB_OPTION_END);
+    }
+    Ok(data)
+}
+
+/// The space of the client with Vendor Class Identifier `vendor_class`, the
+/// one with the longest matching vendor class when several match.
+pub fn find_vendor_space<'a>(
+    spaces: &'a [VendorOptionSpace],
+    vendor_class: &[u8],
+) -> Option<&'a VendorOptionSpace> {
+    spaces
+        .iter()
+        .filter(|space| space.matches(vendor_class))
+        .max_by_key(|space| space.vendor_class.len())
+}
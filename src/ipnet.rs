@@ -0,0 +1,92 @@
+use std::error::Error;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseIpv4NetError {
+    InvalidFormat,
+    InvalidAddress,
+    InvalidPrefixLength,
+}
+
+impl fmt::Display for ParseIpv4NetError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseIpv4NetError::InvalidFormat => write!(fmt, "Invalid format"),
+            ParseIpv4NetError::InvalidAddress => write!(fmt, "Invalid address"),
+            ParseIpv4NetError::InvalidPrefixLength => write!(fmt, "Invalid prefix length"),
+        }
+    }
+}
+
+impl Error for ParseIpv4NetError {}
+
+/// An IPv4 network in CIDR notation, e.g. `10.1.0.0/16`.
+///
+/// The address is kept as given, use [`Ipv4Net::network`] for the address
+/// with the host bits cleared.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Ipv4Net {
+    addr: Ipv4Addr,
+    prefix_len: u8,
+}
+
+impl Ipv4Net {
+    pub fn new(addr: Ipv4Addr, prefix_len: u8) -> Result<Ipv4Net, ParseIpv4NetError> {
+        if prefix_len > 32 {
+            return Err(ParseIpv4NetError::InvalidPrefixLength);
+        }
+        Ok(Ipv4Net { addr, prefix_len })
+    }
+    #[inline]
+    pub fn get_addr(&self) -> Ipv4Addr {
+        self.addr
+    }
+    #[inline]
+    pub fn get_prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+    #[inline]
+    pub fn netmask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(self.netmask_bits())
+    }
+    #[inline]
+    pub fn network(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.addr) & self.netmask_bits())
+    }
+    #[inline]
+    pub fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.addr) | !self.netmask_bits())
+    }
+    pub fn contains(&self, ip: &Ipv4Addr) -> bool {
+        u32::from(*ip) & self.netmask_bits() == u32::from(self.network())
+    }
+
+    fn netmask_bits(&self) -> u32 {
+        u32::MAX
+            .checked_shl(32 - self.prefix_len as u32)
+            .unwrap_or(0)
+    }
+}
+
+impl FromStr for Ipv4Net {
+    /// Parse a string of the form `10.1.0.0/16` as an IPv4 network.
+    type Err = ParseIpv4NetError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = s.split_once('/').ok_or(ParseIpv4NetError::InvalidFormat)?;
+        let addr = addr
+            .parse::<Ipv4Addr>()
+            .map_err(|_| ParseIpv4NetError::InvalidAddress)?;
+        let prefix_len = prefix_len
+            .parse::<u8>()
+            .map_err(|_| ParseIpv4NetError::InvalidPrefixLength)?;
+        Ipv4Net::new(addr, prefix_len)
+    }
+}
+
+impl fmt::Display for Ipv4Net {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}/{}", self.addr, self.prefix_len)
+    }
+}
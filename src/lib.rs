@@ -1,28 +1,1724 @@
 pub mod dhcp;
+pub mod ipnet;
 pub mod macaddress;
 
 // write a test function in lib.rs and start test
 #[cfg(test)]
 mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
     use std::str::FromStr;
 
+    use crate::dhcp::*;
+    use crate::ipnet::Ipv4Net;
     use crate::macaddress::MacAddress;
 
+    // A minimal DHCPDISCOVER as it appears on the wire
+    fn discover_bytes() -> Vec<u8> {
+        let mut bytes = vec![0u8; BOOTP_HEADER_LEN];
+        bytes[0] = BOOTREQUEST;
+        bytes[1] = 1;
+        bytes[2] = 6;
+        bytes[4..8].copy_from_slice(&0x3903f326u32.to_be_bytes());
+        bytes[28..34].copy_from_slice(&[0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        bytes.extend_from_slice(&MAGIC_COOKIE);
+        bytes.extend_from_slice(&[53, 1, 1]);
+        bytes.extend_from_slice(&[50, 4, 192, 168, 1, 10]);
+        bytes.extend_from_slice(&[55, 3, 1, 3, 6]);
+        bytes.extend_from_slice(&[PAD_OPTION, END_OPTION, 0, 0]);
+        bytes
+    }
+
+    #[test]
+    fn test_packet_decode() {
+        let p = Packet::decode_from_unchecked(&discover_bytes()).expect("Failed to decode packet");
+        assert_eq!(p.get_op(), BOOTREQUEST);
+        assert_eq!(p.get_xid(), 0x3903f326);
+        assert_eq!(&p.get_chaddr()[..6], &[0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        assert_eq!(p.get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Discover));
+        assert_eq!(
+            p.get_options(),
+            &[
+                DhcpOption::DhcpMessageType(DhcpMessageTypeCode::Discover),
+                DhcpOption::RequestedIpAddress(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))),
+                DhcpOption::ParameterRequestList(vec![1, 3, 6]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_packet_decode_malformed() {
+        let bytes = discover_bytes();
+        // Every truncation either decodes or fails, but never panics
+        for len in 0..bytes.len() {
+            let _ = Packet::decode_from_unchecked(&bytes[..len]);
+        }
+        assert_eq!(
+            Packet::decode_from_unchecked(&bytes[..OPTIONS_OFFSET - 1]),
+            Err(ConvertPacketError::InvalidLength)
+        );
+
+        let mut bad_cookie = bytes.clone();
+        bad_cookie[BOOTP_HEADER_LEN] = 0;
+        assert_eq!(
+            Packet::decode_from_unchecked(&bad_cookie),
+            Err(ConvertPacketError::InvalidMagicCookie)
+        );
+
+        let mut bad_hlen = bytes.clone();
+        bad_hlen[2] = 17;
+        assert_eq!(Packet::decode_from_unchecked(&bad_hlen), Err(ConvertPacketError::InvalidHlen));
+
+        // Requested IP Address option claims more octets than remain
+        let mut overrun = bytes[..OPTIONS_OFFSET + 3].to_vec();
+        overrun.extend_from_slice(&[50, 8, 192, 168]);
+        assert_eq!(
+            Packet::decode_from_unchecked(&overrun),
+            Err(ConvertPacketError::InvalidOptionLength(REQUESTED_IP_ADDRESS))
+        );
+
+        let mut bad_type = bytes.clone();
+        bad_type[OPTIONS_OFFSET + 2] = 42;
+        assert_eq!(
+            Packet::decode_from_unchecked(&bad_type),
+            Err(ConvertPacketError::UnrecognizedMessageType)
+        );
+
+        // Options with invalid values are kept raw, the packet still decodes:
+        // an Interface MTU below 68, a non-UTF-8 Host Name, an unknown NetBIOS
+        // node type and a Subnet Mask of three octets
+        let mut bad_values = bytes.clone();
+        let end = bad_values.iter().rposition(|&b| b == END_OPTION).unwrap() - 1;
+        bad_values.splice(end..end, [26, 2, 0, 10, 12, 2, 0xff, 0xfe, 46, 1, 3, 1, 3, 255, 255, 255]);
+        let p = Packet::decode_from_unchecked(&bad_values).expect("Failed to decode packet");
+        assert_eq!(p.get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Discover));
+        assert_eq!(p.get_options()[3], DhcpOption::Unrecognized(RawDhcpOption::new(INTERFACE_MTU, vec![0, 10])));
+        assert_eq!(p.get_options()[4], DhcpOption::Unrecognized(RawDhcpOption::new(HOST_NAME, vec![0xff, 0xfe])));
+        assert_eq!(p.get_options()[5], DhcpOption::Unrecognized(RawDhcpOption::new(NETBIOS_OVER_TCPIP_NODE_TYPE, vec![3])));
+        assert_eq!(p.get_options()[6], DhcpOption::Unrecognized(RawDhcpOption::new(SUBNET_MASK, vec![255, 255, 255])));
+        assert_eq!(p.get_host_name(), None);
+    }
+
+    fn offer_packet(options: Vec<DhcpOption>) -> Packet {
+        client_packet(BOOTREPLY, options)
+    }
+
+    fn request_packet(options: Vec<DhcpOption>) -> Packet {
+        client_packet(BOOTREQUEST, options)
+    }
+
+    fn client_packet(op: u8, options: Vec<DhcpOption>) -> Packet {
+        let mut chaddr = [0u8; 16];
+        chaddr[..6].copy_from_slice(&[0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        Packet::new(
+            op,
+            1,
+            6,
+            0,
+            0x3903f326,
+            0,
+            FLAG_ZERO,
+            0,
+            u32::from(Ipv4Addr::new(192, 168, 1, 10)),
+            0,
+            0,
+            chaddr,
+            [0u8; 64],
+            [0u8; 128],
+            options,
+        )
+    }
+
+    #[test]
+    fn test_packet_encode() {
+        let router = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        let p = offer_packet(vec![
+            DhcpOption::Routers(vec![router]),
+            DhcpOption::ServerIdentifier(router),
+            DhcpOption::SubnetMask(IpAddr::V4(Ipv4Addr::new(255, 255, 255, 0))),
+            DhcpOption::DhcpMessageType(DhcpMessageTypeCode::Offer),
+        ]);
+        let mut buf = [0xffu8; 2048];
+        let bytes = p.encode(&mut buf).expect("Failed to encode packet");
+        // Padded to the BOOTP minimum
+        assert_eq!(bytes.len(), BOOTP_MIN_LEN);
+        assert_eq!(&bytes[BOOTP_HEADER_LEN..OPTIONS_OFFSET], &MAGIC_COOKIE);
+        assert_eq!(&bytes[OPTIONS_OFFSET..OPTIONS_OFFSET + 3], &[DHCP_MESSAGE_TYPE, 1, 2]);
+        assert_eq!(bytes[OPTIONS_OFFSET + 3], SUBNET_MASK);
+        assert_eq!(bytes[OPTIONS_OFFSET + 9], ROUTERS);
+        assert_eq!(bytes[OPTIONS_OFFSET + 21], END_OPTION);
+        assert!(bytes[OPTIONS_OFFSET + 22..].iter().all(|&b| b == PAD_OPTION));
+
+        let decoded = Packet::decode_from_unchecked(bytes).expect("Failed to decode packet");
+        assert_eq!(decoded.get_yiaddr(), p.get_yiaddr());
+        assert_eq!(decoded.get_options()[0], DhcpOption::DhcpMessageType(DhcpMessageTypeCode::Offer));
+        assert_eq!(decoded.get_options().len(), 4);
+    }
+
+    #[test]
+    fn test_packet_encode_max_size() {
+        let servers = (0..70).map(|i| IpAddr::V4(Ipv4Addr::new(10, 0, 0, i))).collect::<Vec<_>>();
+        let p = offer_packet(vec![
+            DhcpOption::DhcpMessageType(DhcpMessageTypeCode::Offer),
+            DhcpOption::DomainNameServers(servers[..50].to_vec()),
+            DhcpOption::NameServers(servers[..50].to_vec()),
+        ]);
+        let mut buf = [0u8; 2048];
+        // 3 + 2 * 202 octets of options do not fit into 576 - 28 - 240
+        assert_eq!(p.encode(&mut buf), Err(ConvertPacketError::MessageTooLarge));
+        let bytes = p.encode_with_max_size(&mut buf, 1500).expect("Failed to encode packet");
+        assert_eq!(bytes.len(), OPTIONS_OFFSET + 3 + 2 * 202 + 1);
+
+        // Payloads longer than 255 octets are split into several instances
+        let p = offer_packet(vec![DhcpOption::DomainNameServers(servers.clone())]);
+        let bytes = p.encode_with_max_size(&mut buf, 1500).expect("Failed to encode packet");
+        assert_eq!(&bytes[OPTIONS_OFFSET..OPTIONS_OFFSET + 2], &[DOMAIN_NAME_SERVERS, 255]);
+        assert_eq!(&bytes[OPTIONS_OFFSET + 257..OPTIONS_OFFSET + 259], &[DOMAIN_NAME_SERVERS, 25]);
+        let decoded = Packet::decode_from_unchecked(bytes).expect("Failed to decode packet");
+        assert_eq!(decoded.get_options(), &[DhcpOption::DomainNameServers(servers)]);
+    }
+
+    #[test]
+    fn test_long_option_with_overload() {
+        // 80 routers do not fit into the options field of a 576 octet message,
+        // the second instance of the split option goes to 'file'
+        let routers = (0..80).map(|i| IpAddr::V4(Ipv4Addr::new(10, 0, 1, i))).collect::<Vec<_>>();
+        let p = offer_packet(vec![
+            DhcpOption::DhcpMessageType(DhcpMessageTypeCode::Ack),
+            DhcpOption::Routers(routers.clone()),
+        ]);
+        let mut buf = [0u8; 2048];
+        let bytes = p.encode(&mut buf).expect("Failed to encode packet");
+        assert_eq!(&bytes[108..110], &[ROUTERS, 65]);
+        let decoded = Packet::decode_from_unchecked(bytes).expect("Failed to decode packet");
+        assert_eq!(
+            decoded.get_options(),
+            &[
+                DhcpOption::DhcpMessageType(DhcpMessageTypeCode::Ack),
+                DhcpOption::Routers(routers),
+                DhcpOption::OptionOverload(OptionOverLoadCode::OverloadFile),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_packet_option_overload() {
+        let servers = (0..30).map(|i| IpAddr::V4(Ipv4Addr::new(10, 0, 0, i))).collect::<Vec<_>>();
+        let options = vec![
+            DhcpOption::DhcpMessageType(DhcpMessageTypeCode::Offer),
+            DhcpOption::DomainNameServers(servers.clone()),
+            DhcpOption::NameServers(servers.clone()),
+            DhcpOption::TimeServers(servers.clone()),
+        ];
+        let p = offer_packet(options.clone());
+        let mut buf = [0u8; 2048];
+        let bytes = p.encode(&mut buf).expect("Failed to encode packet");
+        assert_eq!(bytes.len(), OPTIONS_OFFSET + 3 + 2 * 122 + 3 + 1);
+        assert_eq!(&bytes[OPTIONS_OFFSET + 247..OPTIONS_OFFSET + 250], &[OPTION_OVERLOAD, 1, 1]);
+        // The third list is moved into the 'file' field
+        assert_eq!(&bytes[108..110], &[TIME_SERVERS, 120]);
+        assert_eq!(bytes[108 + 122], END_OPTION);
+
+        let decoded = Packet::decode_from_unchecked(bytes).expect("Failed to decode packet");
+        let mut expected = options.clone();
+        expected.push(DhcpOption::OptionOverload(OptionOverLoadCode::OverloadFile));
+        expected.swap(3, 4);
+        assert_eq!(decoded.get_options(), &expected[..]);
+        assert!(decoded.get_file().iter().all(|&b| b == 0));
+
+        // A boot file name in 'file' leaves only 'sname', which is too small
+        let mut file = [0u8; 128];
+        file[..9].copy_from_slice(b"pxelinux0");
+        let p = Packet::new(
+            BOOTREPLY, 1, 6, 0, 1, 0, FLAG_ZERO, 0, 0, 0, 0, [0u8; 16], [0u8; 64], file, options,
+        );
+        assert_eq!(p.encode(&mut buf), Err(ConvertPacketError::MessageTooLarge));
+    }
+
+    #[test]
+    fn test_unrecognized_option_round_trip() {
+        let mut bytes = discover_bytes();
+        // Insert in front of the Pad option which the encoder does not reproduce
+        let end = bytes.iter().rposition(|&b| b == END_OPTION).unwrap() - 1;
+        // A site-specific option (224) and an empty private option (250)
+        bytes.splice(end..end, [224, 3, 0xde, 0xad, 0x01, 250, 0]);
+        let p = Packet::decode_from_unchecked(&bytes).expect("Failed to decode packet");
+        let raw = RawDhcpOption::new(224, vec![0xde, 0xad, 0x01]);
+        assert_eq!(p.get_options()[3], DhcpOption::Unrecognized(raw));
+        assert_eq!(p.get_options()[3].code(), 224);
+        assert_eq!(p.get_options()[4], DhcpOption::Unrecognized(RawDhcpOption::new(250, vec![])));
+
+        let mut buf = [0u8; 2048];
+        let encoded = p.encode(&mut buf).expect("Failed to encode packet");
+        assert_eq!(&encoded[..end], &bytes[..end]);
+        assert_eq!(&encoded[end..end + 8], &[224, 3, 0xde, 0xad, 0x01, 250, 0, END_OPTION]);
+
+        // Raw Pad, Option Overload and End options are not written
+        for code in [PAD_OPTION, OPTION_OVERLOAD, END_OPTION] {
+            let raw = DhcpOption::Unrecognized(RawDhcpOption::new(code, vec![1]));
+            let message_type = DhcpOption::DhcpMessageType(DhcpMessageTypeCode::Discover);
+            assert_eq!(encode_options(&[raw, message_type]), Ok(vec![vec![DHCP_MESSAGE_TYPE, 1, 1]]));
+        }
+    }
+
+    #[test]
+    fn test_rfc2132_typed_options() {
+        let ntp = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 123));
+        let gw = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let cases = vec![
+            (DhcpOption::IpForwarding(true), vec![1]),
+            (DhcpOption::InterfaceMtu(1500), vec![0x05, 0xdc]),
+            (DhcpOption::NtpServers(vec![ntp]), vec![10, 0, 0, 123]),
+            (DhcpOption::StaticRoute(vec![(ntp, gw)]), vec![10, 0, 0, 123, 10, 0, 0, 1]),
+            (DhcpOption::PathMtuPlateauTable(vec![68, 1500]), vec![0, 68, 0x05, 0xdc]),
+            (DhcpOption::NetbiosNodeType(0x8), vec![0x8]),
+            (DhcpOption::BootfileName("pxelinux.0".to_string()), b"pxelinux.0".to_vec()),
+            (DhcpOption::MobileIpHomeAgents(vec![]), vec![]),
+        ];
+        for (opt, data) in cases {
+            assert_eq!(opt.encode().as_ref(), Ok(&data));
+            assert_eq!(DhcpOption::decode(opt.code(), &data), Ok(opt));
+        }
+
+        assert_eq!(
+            DhcpOption::decode(INTERFACE_MTU, &[0, 67]),
+            Err(ConvertPacketError::InvalidOptionValue(INTERFACE_MTU))
+        );
+        assert_eq!(
+            DhcpOption::decode(NETWORK_TIME_PROTOCOL_SERVERS, &[10, 0, 0, 123, 10, 0]),
+            Err(ConvertPacketError::InvalidOptionLength(NETWORK_TIME_PROTOCOL_SERVERS))
+        );
+        assert_eq!(
+            DhcpOption::decode(POLICY_FILTER, &[10, 0, 0, 0]),
+            Err(ConvertPacketError::InvalidOptionLength(POLICY_FILTER))
+        );
+        assert_eq!(
+            DhcpOption::decode(IP_FORWARDING, &[2]),
+            Err(ConvertPacketError::InvalidOptionValue(IP_FORWARDING))
+        );
+        assert_eq!(
+            DhcpOption::NtpServers(vec![]).encode(),
+            Err(ConvertPacketError::InvalidOptionLength(NETWORK_TIME_PROTOCOL_SERVERS))
+        );
+        assert_eq!(
+            DhcpOption::DefaultIpTtl(0).encode(),
+            Err(ConvertPacketError::InvalidOptionValue(DEFAULT_IP_TTL))
+        );
+    }
+
+    #[test]
+    fn test_classless_static_routes() {
+        let net = Ipv4Net::from_str("10.17.0.0/16").expect("Failed to parse network");
+        let default = Ipv4Net::from_str("0.0.0.0/0").expect("Failed to parse network");
+        let gw = Ipv4Addr::new(10, 0, 0, 1);
+        let opt = DhcpOption::ClasslessStaticRoutes(vec![(net, gw), (default, gw)]);
+        let data = vec![16, 10, 17, 10, 0, 0, 1, 0, 10, 0, 0, 1];
+        assert_eq!(opt.encode(), Ok(data.clone()));
+        assert_eq!(DhcpOption::decode(CLASSLESS_ROUTE_FORMAT, &data), Ok(opt));
+
+        // Host bits past the prefix are dropped, a short descriptor is an error
+        let decoded = DhcpOption::decode(CLASSLESS_ROUTE_FORMAT, &[12, 10, 0x1f, 10, 0, 0, 1]);
+        let net = Ipv4Net::from_str("10.16.0.0/12").unwrap();
+        assert_eq!(decoded, Ok(DhcpOption::ClasslessStaticRoutes(vec![(net, gw)])));
+        assert_eq!(
+            DhcpOption::decode(CLASSLESS_ROUTE_FORMAT, &[24, 192, 168, 10, 0, 0]),
+            Err(ConvertPacketError::InvalidOptionLength(CLASSLESS_ROUTE_FORMAT))
+        );
+        assert_eq!(
+            DhcpOption::decode(CLASSLESS_ROUTE_FORMAT, &[33, 0, 0, 0, 0, 10, 0, 0, 1]),
+            Err(ConvertPacketError::InvalidOptionValue(CLASSLESS_ROUTE_FORMAT))
+        );
+    }
+
+    #[test]
+    fn test_select_reply_options() {
+        let mask = DhcpOption::SubnetMask(IpAddr::V4(Ipv4Addr::new(255, 255, 255, 0)));
+        let routers = DhcpOption::Routers(vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))]);
+        let net = Ipv4Net::from_str("10.0.0.0/8").unwrap();
+        let routes = DhcpOption::ClasslessStaticRoutes(vec![(net, Ipv4Addr::new(192, 168, 1, 2))]);
+        let configured = vec![mask.clone(), routers.clone(), routes];
+
+        // Without option 121 in the request list the routes are not sent
+        let selected = select_reply_options(&configured, Some(&vec![1, 3, 6]));
+        assert_eq!(selected, vec![mask.clone(), routers.clone()]);
+        assert_eq!(select_reply_options(&configured, None), vec![mask.clone(), routers]);
+
+        // With option 121 the Routers option is replaced by a default route
+        let expected_routes = vec![
+            (net, Ipv4Addr::new(192, 168, 1, 2)),
+            (Ipv4Net::from_str("0.0.0.0/0").unwrap(), Ipv4Addr::new(192, 168, 1, 1)),
+        ];
+        let selected = select_reply_options(&configured, Some(&vec![1, 3, 121, 249]));
+        assert_eq!(
+            selected,
+            vec![
+                mask,
+                DhcpOption::ClasslessStaticRoutes(expected_routes.clone()),
+                DhcpOption::MsClasslessStaticRoutes(expected_routes),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ipv4net() {
+        let net = Ipv4Net::from_str("192.168.1.77/24").expect("Failed to parse network");
+        assert_eq!(net.network(), Ipv4Addr::new(192, 168, 1, 0));
+        assert_eq!(net.netmask(), Ipv4Addr::new(255, 255, 255, 0));
+        assert_eq!(net.broadcast(), Ipv4Addr::new(192, 168, 1, 255));
+        assert!(net.contains(&Ipv4Addr::new(192, 168, 1, 200)));
+        assert!(!net.contains(&Ipv4Addr::new(192, 168, 2, 1)));
+        assert_eq!(net.to_string(), "192.168.1.77/24");
+        assert!(Ipv4Net::from_str("0.0.0.0/0").unwrap().contains(&Ipv4Addr::new(8, 8, 8, 8)));
+        assert!(Ipv4Net::from_str("10.0.0.0/33").is_err());
+        assert!(Ipv4Net::from_str("10.0.0.0").is_err());
+    }
+
+    #[test]
+    fn test_relay_agent_information() {
+        let relay_data: Vec<u8> = vec![
+            1, 4, b'e', b't', b'h', b'7', // Circuit-ID
+            2, 6, 0x00, 0x1b, 0x21, 0xaa, 0xbb, 0xcc, // Remote-ID
+            5, 4, 10, 20, 30, 0, // Link Selection
+            6, 3, b'b', b'o', b'b', // Subscriber-ID
+            9, 2, 0xca, 0xfe, // Vendor-Specific, not modelled
+            11, 3, 10, 0, 0, // Server Identifier Override with a bad length
+            19, 0, // Relay Source Port
+        ];
+        let mut bytes = discover_bytes();
+        let end = bytes.iter().rposition(|&b| b == END_OPTION).unwrap();
+        let option = [&[RELAY_AGENT_INFORMATION, relay_data.len() as u8][..], &relay_data].concat();
+        bytes.splice(end..end, option);
+
+        let p = Packet::decode_from_unchecked(&bytes).expect("Failed to decode packet");
+        assert_eq!(p.get_circuit_id(), Some(&b"eth7"[..]));
+        assert_eq!(p.get_remote_id(), Some(&[0x00, 0x1b, 0x21, 0xaa, 0xbb, 0xcc][..]));
+        assert_eq!(p.get_link_selection(), Some(Ipv4Addr::new(10, 20, 30, 0)));
+        assert_eq!(p.get_subscriber_id(), Some("bob"));
+        let sub_options = p.get_relay_agent_information().expect("No relay agent information");
+        let vendor = RawDhcpOption::new(9, vec![0xca, 0xfe]);
+        assert_eq!(sub_options[4], RelayAgentSubOption::Unrecognized(vendor));
+        let bad_override = RawDhcpOption::new(11, vec![10, 0, 0]);
+        assert_eq!(sub_options[5], RelayAgentSubOption::Unrecognized(bad_override));
+        assert_eq!(sub_options[6], RelayAgentSubOption::RelaySourcePort(None));
+
+        // Echoed back byte for byte
+        let echoed = DhcpOption::RelayAgentInformation(sub_options.clone());
+        assert_eq!(echoed.encode(), Ok(relay_data));
+
+        assert_eq!(
+            DhcpOption::decode(RELAY_AGENT_INFORMATION, &[1, 5, b'e']),
+            Err(ConvertPacketError::InvalidOptionLength(RELAY_AGENT_INFORMATION))
+        );
+    }
+
+    #[test]
+    fn test_client_fqdn() {
+        assert_eq!(
+            encode_dns_name(CLIENT_FQDN, "laptop.example.com."),
+            Ok(b"\x06laptop\x07example\x03com\x00".to_vec())
+        );
+        assert_eq!(encode_dns_name(CLIENT_FQDN, "laptop"), Ok(b"\x06laptop".to_vec()));
+        assert!(encode_dns_name(CLIENT_FQDN, "bad..name").is_err());
+        assert_eq!(decode_dns_name(CLIENT_FQDN, b"\x06laptop\x00"), Ok("laptop.".to_string()));
+        assert!(decode_dns_name(CLIENT_FQDN, b"\x06lap").is_err());
+
+        let data = b"\x05\x00\x00\x06laptop".to_vec();
+        let fqdn = ClientFqdn::new(FQDN_FLAG_S | FQDN_FLAG_E, 0, 0, "laptop".to_string());
+        assert_eq!(DhcpOption::decode(CLIENT_FQDN, &data), Ok(DhcpOption::ClientFqdn(fqdn.clone())));
+        assert_eq!(DhcpOption::ClientFqdn(fqdn).encode(), Ok(data));
+        assert!(DhcpOption::decode(CLIENT_FQDN, &[0x05, 0]).is_err());
+    }
+
+    #[test]
+    fn test_domain_search() {
+        // Example from RFC 3397 section 2
+        let names = vec!["eng.apple.com".to_string(), "marketing.apple.com".to_string()];
+        let data = b"\x03eng\x05apple\x03com\x00\x09marketing\xc0\x04".to_vec();
+        assert_eq!(DhcpOption::DomainSearch(names.clone()).encode(), Ok(data.clone()));
+        assert_eq!(DhcpOption::decode(DOMAIN_SEARCH, &data), Ok(DhcpOption::DomainSearch(names)));
+
+        // Pointer to itself, forward pointer, loop back into the same name,
+        // reserved label type and missing root label
+        assert!(decode_domain_search(DOMAIN_SEARCH, b"\xc0\x00").is_err());
+        assert!(decode_domain_search(DOMAIN_SEARCH, b"\x01a\xc0\x05\x00\x01b\x00").is_err());
+        assert!(decode_domain_search(DOMAIN_SEARCH, b"\x01a\xc0\x00").is_err());
+        assert!(decode_domain_search(DOMAIN_SEARCH, b"\x40\x00").is_err());
+        assert!(decode_domain_search(DOMAIN_SEARCH, b"\x03com").is_err());
+
+        // A long list is split into several instances and joined again
+        let names: Vec<String> = (0..40).map(|i| format!("subdomain{}.example.com", i)).collect();
+        let option = DhcpOption::DomainSearch(names.clone());
+        assert!(option.encode().unwrap().len() > 255);
+        let packet = request_packet(vec![
+            DhcpOption::DhcpMessageType(DhcpMessageTypeCode::Request),
+            option,
+        ]);
+        let mut buf = [0u8; 2048];
+        let bytes = packet.encode_with_max_size(&mut buf, 1500).unwrap().to_vec();
+        let packet = Packet::try_from(bytes.as_slice()).unwrap();
+        assert!(packet
+            .get_options()
+            .contains(&DhcpOption::DomainSearch(names)));
+    }
+
+    #[test]
+    fn test_vendor_option_space() {
+        let mut cisco = VendorOptionSpace::cisco_ap();
+        let controllers = vec![Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 0, 6)];
+        cisco
+            .add_sub_option(CISCO_AP_CONTROLLERS, VendorValue::Ips(controllers.clone()))
+            .unwrap();
+        assert!(cisco
+            .add_sub_option(CISCO_AP_CONTROLLERS, VendorValue::String("wlc".to_string()))
+            .is_err());
+        let data = cisco.encode().unwrap();
+        assert_eq!(data, vec![241, 8, 10, 0, 0, 5, 10, 0, 0, 6]);
+        assert_eq!(
+            cisco.decode(&data),
+            Ok(vec![VendorSubOption::new(CISCO_AP_CONTROLLERS, VendorValue::Ips(controllers))])
+        );
+
+        let mut pxe = VendorOptionSpace::pxe();
+        pxe.add_sub_option(PXE_DISCOVERY_CONTROL, VendorValue::U8(8)).unwrap();
+        assert_eq!(pxe.encode(), Ok(vec![PXE_DISCOVERY_CONTROL, 1, 8, 255]));
+        // Undefined codes and payloads that do not fit their type stay bytes
+        assert_eq!(
+            pxe.decode(&[0, PXE_DISCOVERY_CONTROL, 2, 1, 2, 200, 1, 7, 255, 9]),
+            Ok(vec![
+                VendorSubOption::new(PXE_DISCOVERY_CONTROL, VendorValue::Bytes(vec![1, 2])),
+                VendorSubOption::new(200, VendorValue::Bytes(vec![7])),
+            ])
+        );
+        assert!(pxe.decode(&[PXE_BOOT_MENU, 4, 1]).is_err());
+
+        let mut arch = VendorOptionSpace::new("pxe-uefi", b"PXEClient:Arch:00007");
+        arch.define(PXE_DISCOVERY_CONTROL, VendorOptionType::U8);
+        let spaces = vec![pxe, arch, cisco];
+        let find = |class: &[u8]| find_vendor_space(&spaces, class).map(|s| s.get_name());
+        assert_eq!(find(b"PXEClient:Arch:00007:UNDI:003016"), Some("pxe-uefi"));
+        assert_eq!(find(b"PXEClient:Arch:00000:UNDI:002001"), Some("pxe"));
+        assert_eq!(find(b"Cisco AP c3700"), Some("cisco-ap"));
+        assert_eq!(find(b"MSFT 5.0"), None);
+    }
+
+    #[test]
+    fn test_boot_policy() {
+        assert_eq!(
+            DhcpOption::decode(CLIENT_ARCHITECTURE, &[0, 7]),
+            Ok(DhcpOption::ClientArchitecture(vec![ARCH_X64_UEFI]))
+        );
+        assert!(DhcpOption::decode(CLIENT_ARCHITECTURE, &[0, 7, 0]).is_err());
+
+        let mut bios = BootRule::new(&[ARCH_X86_BIOS], "pxelinux.0");
+        bios.set_next_server(Ipv4Addr::new(192, 168, 1, 2));
+        bios.set_tftp_server_name("tftp.lab");
+        bios.add_pxe_sub_option(PXE_DISCOVERY_CONTROL, VendorValue::U8(8)).unwrap();
+        let uefi = BootRule::new(&[ARCH_X64_UEFI, ARCH_X64_UEFI_ALT], "grubx64.efi");
+        let http = BootRule::new(&[ARCH_X64_UEFI_HTTP], "http://boot.lab/grubx64.efi");
+        let mut policy = BootPolicy::new();
+        policy.add_rule(bios);
+        policy.add_rule(uefi);
+        policy.add_rule(http);
+
+        let select = |options: Vec<DhcpOption>| {
+            let packet = offer_packet(options);
+            policy.select(&packet).map(|rule| rule.get_boot_file().to_string())
+        };
+        assert_eq!(select(vec![]), None);
+        assert_eq!(
+            select(vec![DhcpOption::ClassIdentifier(b"PXEClient".to_vec())]),
+            Some("pxelinux.0".to_string())
+        );
+        // x64 UEFI firmware sending 9
+        assert_eq!(
+            select(vec![DhcpOption::ClassIdentifier(b"PXEClient:Arch:00009:UNDI:003016".to_vec())]),
+            Some("grubx64.efi".to_string())
+        );
+        // Option 93 wins over the vendor class
+        assert_eq!(
+            select(vec![
+                DhcpOption::ClassIdentifier(b"HTTPClient:Arch:00016:UNDI:003001".to_vec()),
+                DhcpOption::ClientArchitecture(vec![ARCH_X64_UEFI]),
+            ]),
+            Some("grubx64.efi".to_string())
+        );
+        assert_eq!(select(vec![DhcpOption::ClientArchitecture(vec![ARCH_ARM64_UEFI])]), None);
+
+        let request = request_packet(vec![DhcpOption::ClassIdentifier(
+            b"PXEClient:Arch:00000:UNDI:002001".to_vec(),
+        )]);
+        let rule = policy.select(&request).unwrap();
+        assert_eq!(
+            rule.options(&request),
+            Ok(vec![
+                DhcpOption::ClassIdentifier(b"PXEClient".to_vec()),
+                DhcpOption::TftpServerName("tftp.lab".to_string()),
+                DhcpOption::BootfileName("pxelinux.0".to_string()),
+                DhcpOption::VendorSpecificInformation(vec![PXE_DISCOVERY_CONTROL, 1, 8, 255]),
+            ])
+        );
+        let mut reply = offer_packet(vec![]);
+        rule.apply(&request, &mut reply);
+        assert_eq!(reply.get_siaddr(), u32::from(Ipv4Addr::new(192, 168, 1, 2)));
+        assert_eq!(&reply.get_file()[..11], b"pxelinux.0\0");
+        assert!(reply.set_file(&"a".repeat(128)).is_err());
+    }
+
+    #[test]
+    fn test_user_class_and_ipxe() {
+        let classes = vec![b"lab".to_vec(), b"kiosk".to_vec()];
+        let data = b"\x03lab\x05kiosk".to_vec();
+        assert_eq!(DhcpOption::decode(USER_CLASS, &data), Ok(DhcpOption::UserClass(classes.clone())));
+        assert_eq!(DhcpOption::UserClass(classes).encode(), Ok(data));
+        assert!(DhcpOption::UserClass(vec![vec![]]).encode().is_err());
+        // iPXE sends its class without the RFC 3004 length octet
+        let ipxe = offer_packet(vec![DhcpOption::decode(USER_CLASS, b"iPXE").unwrap()]);
+        assert_eq!(ipxe.get_user_classes(), Some(vec![&b"iPXE"[..]]));
+        assert!(is_ipxe_client(&ipxe));
+
+        let data = [IPXE_FEATURE_HTTP, 1, 1, IPXE_VERSION, 3, 1, 21, 1];
+        let sub_options = vec![
+            VendorSubOption::new(IPXE_FEATURE_HTTP, VendorValue::U8(1)),
+            VendorSubOption::new(IPXE_VERSION, VendorValue::Bytes(vec![1, 21, 1])),
+        ];
+        assert_eq!(
+            DhcpOption::decode(IPXE_ENCAPSULATED, &data),
+            Ok(DhcpOption::IpxeEncapsulated(sub_options.clone()))
+        );
+        assert_eq!(DhcpOption::IpxeEncapsulated(sub_options.clone()).encode(), Ok(data.to_vec()));
+
+        let mut rule = BootRule::new(&[ARCH_X64_UEFI], "ipxe.efi");
+        rule.set_ipxe_script("http://boot.lab/boot.ipxe");
+        let class = DhcpOption::ClassIdentifier(b"PXEClient:Arch:00007:UNDI:003016".to_vec());
+        let firmware = offer_packet(vec![class.clone()]);
+        let chainloaded = offer_packet(vec![class, DhcpOption::IpxeEncapsulated(sub_options)]);
+        assert_eq!(rule.boot_file_for(&firmware), "ipxe.efi");
+        assert_eq!(rule.boot_file_for(&chainloaded), "http://boot.lab/boot.ipxe");
+        assert_eq!(rule.boot_file_for(&ipxe), "http://boot.lab/boot.ipxe");
+    }
+
+    fn test_server() -> DhcpServer {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = Server::new(
+            socket,
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)),
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 255)),
+        );
+        let subnet = Ipv4Net::from_str("192.168.1.0/24").unwrap();
+        DhcpServer::new(server, subnet, Ipv4Addr::new(192, 168, 1, 10), 100)
+    }
+
+    // A DHCPREQUEST from the client with MAC 00:11:22:33:44:`mac_last`
+    fn dhcp_request(mac_last: u8, ciaddr: Ipv4Addr, options: Vec<DhcpOption>) -> Packet {
+        client_message(DhcpMessageTypeCode::Request, mac_last, ciaddr, options)
+    }
+
+    fn client_message(
+        msg_type: DhcpMessageTypeCode,
+        mac_last: u8,
+        ciaddr: Ipv4Addr,
+        options: Vec<DhcpOption>,
+    ) -> Packet {
+        let mut chaddr = [0u8; 16];
+        chaddr[..6].copy_from_slice(&[0x00, 0x11, 0x22, 0x33, 0x44, mac_last]);
+        Packet::new(
+            BOOTREQUEST,
+            1,
+            6,
+            0,
+            0x3903f326,
+            0,
+            FLAG_ZERO,
+            u32::from(ciaddr),
+            0,
+            0,
+            0,
+            chaddr,
+            [0u8; 64],
+            [0u8; 128],
+            [vec![DhcpOption::DhcpMessageType(msg_type)], options].concat(),
+        )
+    }
+
+    #[test]
+    fn test_dhcp_request() {
+        let server_id = DhcpOption::ServerIdentifier(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)));
+        let requested = |ip: [u8; 4]| DhcpOption::RequestedIpAddress(IpAddr::V4(Ipv4Addr::from(ip)));
+        let unspecified = Ipv4Addr::UNSPECIFIED;
+        let mut server = test_server();
+
+        // SELECTING
+        let selecting = dhcp_request(0x55, unspecified, vec![
+            server_id.clone(),
+            requested([192, 168, 1, 20]),
+            DhcpOption::HostName("laptop".to_string()),
+        ]);
+        assert_eq!(
+            request_state(&selecting),
+            Some(RequestState::Selecting(Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 20)))
+        );
+        let ack = server.process_request(&selecting).unwrap();
+        assert_eq!(ack.get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Ack));
+        assert_eq!(ack.get_yiaddr(), u32::from(Ipv4Addr::new(192, 168, 1, 20)));
+        assert!(ack.get_options().contains(&DhcpOption::IpAddressLeaseTime(86400)));
+        let lease = server.get_leases().get_by_ip(&IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20))).unwrap();
+        assert_eq!(lease.get_hostname(), &Some("laptop".to_string()));
+        let other_server = DhcpOption::ServerIdentifier(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2)));
+        let elsewhere = dhcp_request(0x66, unspecified, vec![other_server, requested([192, 168, 1, 30])]);
+        assert_eq!(server.process_request(&elsewhere), None);
+        // The address is taken by the first client
+        let taken = dhcp_request(0x66, unspecified, vec![server_id, requested([192, 168, 1, 20])]);
+        let nak = server.process_request(&taken).unwrap();
+        assert_eq!(nak.get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Nak));
+        assert_eq!(nak.get_yiaddr(), 0);
+
+        // INIT-REBOOT
+        let reboot = |mac_last: u8, ip: [u8; 4]| dhcp_request(mac_last, unspecified, vec![requested(ip)]);
+        assert_eq!(request_state(&reboot(0x55, [192, 168, 1, 20])), Some(RequestState::InitReboot(Ipv4Addr::new(192, 168, 1, 20))));
+        let reply = |server: &mut DhcpServer, p: &Packet| {
+            server.process_request(p).map(|r| *r.get_dhcp_message_type().unwrap())
+        };
+        assert_eq!(reply(&mut server, &reboot(0x55, [192, 168, 1, 20])), Some(DhcpMessageTypeCode::Ack));
+        assert_eq!(reply(&mut server, &reboot(0x55, [192, 168, 1, 21])), Some(DhcpMessageTypeCode::Nak));
+        assert_eq!(reply(&mut server, &reboot(0x55, [10, 0, 0, 5])), Some(DhcpMessageTypeCode::Nak));
+        assert_eq!(reply(&mut server, &reboot(0x77, [192, 168, 1, 50])), None);
+        assert_eq!(reply(&mut server, &reboot(0x77, [192, 168, 1, 20])), Some(DhcpMessageTypeCode::Nak));
+
+        // RENEWING and REBINDING
+        let renew = |mac_last: u8, ip: [u8; 4]| dhcp_request(mac_last, Ipv4Addr::from(ip), vec![]);
+        assert_eq!(request_state(&renew(0x55, [192, 168, 1, 20])), Some(RequestState::Renewing(Ipv4Addr::new(192, 168, 1, 20))));
+        let ack = server.process_request(&renew(0x55, [192, 168, 1, 20])).unwrap();
+        assert_eq!(ack.get_ciaddr(), u32::from(Ipv4Addr::new(192, 168, 1, 20)));
+        assert_eq!(reply(&mut server, &renew(0x77, [192, 168, 1, 20])), Some(DhcpMessageTypeCode::Nak));
+        // A lease the server does not know about is granted when free
+        assert_eq!(reply(&mut server, &renew(0x77, [192, 168, 1, 40])), Some(DhcpMessageTypeCode::Ack));
+        assert_eq!(reply(&mut server, &renew(0x77, [192, 168, 1, 200])), Some(DhcpMessageTypeCode::Nak));
+        assert_eq!(server.get_leases().len(), 2);
+
+        // Replies to a renewing client are unicast, NAKs broadcast
+        let broadcast = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 255));
+        let renewing = renew(0x55, [192, 168, 1, 20]);
+        assert_eq!(
+            reply_destination(&renewing, &ack, broadcast),
+            "192.168.1.20:68".parse().unwrap()
+        );
+        assert_eq!(reply_destination(&renewing, &nak, broadcast), "192.168.1.255:68".parse().unwrap());
+        assert_eq!(request_state(&dhcp_request(0x55, unspecified, vec![])), None);
+    }
+
+    #[test]
+    fn test_non_ethernet_clients() {
+        let server_id = DhcpOption::ServerIdentifier(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)));
+        let requested = |ip: u8| DhcpOption::RequestedIpAddress(IpAddr::V4(Ipv4Addr::new(192, 168, 1, ip)));
+        // A message from an IEEE 802 (htype 6) client without option 61
+        let message = |msg_type: DhcpMessageTypeCode, hlen: u8, last: u8, ciaddr: Ipv4Addr, options: Vec<DhcpOption>| {
+            let mut chaddr = [0u8; 16];
+            chaddr[..6].copy_from_slice(&[0x00, 0x11, 0x22, 0x33, 0x44, last]);
+            Packet::new(
+                BOOTREQUEST, 6, hlen, 0, 0x3903f326, 0, FLAG_ZERO, u32::from(ciaddr), 0, 0, 0,
+                chaddr, [0u8; 64], [0u8; 128],
+                [vec![DhcpOption::DhcpMessageType(msg_type)], options].concat(),
+            )
+        };
+        let unspecified = Ipv4Addr::UNSPECIFIED;
+        let mut server = test_server();
+
+        // Such clients are told apart by chaddr, not taken for one client
+        let first = message(DhcpMessageTypeCode::Request, 6, 0x01, unspecified, vec![server_id.clone(), requested(20)]);
+        let second = message(DhcpMessageTypeCode::Request, 6, 0x02, unspecified, vec![server_id.clone(), requested(20)]);
+        assert_eq!(server.process_request(&first).unwrap().get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Ack));
+        assert_eq!(server.process_request(&second).unwrap().get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Nak));
+        let offer = server.process_discover(&message(DhcpMessageTypeCode::Discover, 6, 0x02, unspecified, vec![])).unwrap();
+        assert_ne!(offer.get_yiaddr(), u32::from(Ipv4Addr::new(192, 168, 1, 20)));
+        let lease = server.get_leases().get_by_ip(&IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20))).unwrap();
+        assert_eq!(lease.get_chi(), &Some("06:00:11:22:33:44:01".to_string()));
+        let release = message(DhcpMessageTypeCode::Release, 6, 0x02, Ipv4Addr::new(192, 168, 1, 20), vec![server_id.clone()]);
+        server.process_release(&release);
+        assert_eq!(server.get_leases().get_by_ip(&IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20))).unwrap().get_state(), BindingState::Active);
+
+        // A client with neither option 61 nor a hardware address gets nothing
+        let anonymous = message(DhcpMessageTypeCode::Discover, 0, 0x03, unspecified, vec![]);
+        assert_eq!(server.get_available_ip(&anonymous), Err(DistributeDhcpLeaseError::UnknownClient));
+        assert_eq!(server.process_discover(&anonymous), None);
+
+        // An imported lease without a hardware address is nobody's
+        let declined = DhcpLease::new(
+            std::time::Duration::MAX,
+            MacAddress::nil(),
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 30)),
+            None,
+            None,
+        );
+        assert!(!declined.is_for_client(None, &MacAddress::nil()));
+    }
+
     #[test]
-    fn test_dhcp() {
-        crate::dhcp::test_options();
+    fn test_dhcp_decline() {
+        let server_id = DhcpOption::ServerIdentifier(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)));
+        let ip = Ipv4Addr::new(192, 168, 1, 20);
+        let requested = DhcpOption::RequestedIpAddress(IpAddr::V4(ip));
+        let unspecified = Ipv4Addr::UNSPECIFIED;
+        let mut server = test_server();
+        let selecting = dhcp_request(0x55, unspecified, vec![server_id.clone(), requested.clone()]);
+        server.process_request(&selecting).unwrap();
+
+        let decline = client_message(
+            DhcpMessageTypeCode::Decline,
+            0x55,
+            unspecified,
+            vec![server_id.clone(), requested.clone()],
+        );
+        server.process_decline(&decline);
+        assert!(server.get_leases().is_empty());
+        let declined = &server.get_declined()[&IpAddr::V4(ip)];
+        assert_eq!(declined.get_reported_by(), &MacAddress::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x55));
+        // Nobody gets the address during the probation time
+        let other = dhcp_request(0x66, unspecified, vec![server_id.clone(), requested.clone()]);
+        let nak = server.process_request(&other).unwrap();
+        assert_eq!(nak.get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Nak));
+
+        // Back in the pool once the probation time is over
+        let mut server = test_server();
+        server.set_decline_probation(0);
+        server.process_decline(&decline);
+        let ack = server.process_request(&other).unwrap();
+        assert_eq!(ack.get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Ack));
+    }
+
+    #[test]
+    fn test_dhcp_release_and_inform() {
+        let server_id = DhcpOption::ServerIdentifier(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)));
+        let ip = Ipv4Addr::new(192, 168, 1, 20);
+        let requested = DhcpOption::RequestedIpAddress(IpAddr::V4(ip));
+        let mut server = test_server();
+        server.set_options(vec![DhcpOption::DomainName("lab".to_string())]);
+        let selecting = dhcp_request(0x55, Ipv4Addr::UNSPECIFIED, vec![server_id.clone(), requested.clone()]);
+        server.process_request(&selecting).unwrap();
+
+        let release = client_message(DhcpMessageTypeCode::Release, 0x55, ip, vec![server_id.clone()]);
+        assert_eq!(DhcpMessageTypeCode::try_from(8), Ok(DhcpMessageTypeCode::Inform));
+        server.process_release(&release);
+        // The client is remembered and gets its address back after a reboot
+        let lease = server.get_leases().get_by_ip(&IpAddr::V4(ip)).unwrap();
+        assert!(lease.get_expiry_secs() <= std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs());
+        let reboot = dhcp_request(0x55, Ipv4Addr::UNSPECIFIED, vec![requested]);
+        let ack = server.process_request(&reboot).unwrap();
+        assert_eq!(ack.get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Ack));
+
+        let static_ip = Ipv4Addr::new(192, 168, 1, 250);
+        let inform = client_message(DhcpMessageTypeCode::Inform, 0x66, static_ip, vec![]);
+        let ack = server.process_inform(&inform).unwrap();
+        assert_eq!(ack.get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Ack));
+        assert_eq!(ack.get_yiaddr(), 0);
+        assert_eq!(ack.get_ciaddr(), u32::from(static_ip));
+        assert!(ack.get_options().contains(&DhcpOption::DomainName("lab".to_string())));
+        assert!(!ack.get_options().iter().any(|opt| opt.code() == IP_ADDRESS_LEASE_TIME));
+        assert_eq!(
+            reply_destination(&inform, &ack, IpAddr::V4(Ipv4Addr::new(192, 168, 1, 255))),
+            "192.168.1.250:68".parse().unwrap()
+        );
+        let no_ciaddr = client_message(DhcpMessageTypeCode::Inform, 0x66, Ipv4Addr::UNSPECIFIED, vec![]);
+        assert_eq!(server.process_inform(&no_ciaddr), None);
+    }
+
+    #[test]
+    fn test_address_pool() {
+        let mut pool = AddressPool::new(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 10));
+        pool.exclude(Ipv4Addr::new(10, 0, 0, 3), Ipv4Addr::new(10, 0, 0, 4));
+        pool.exclude(Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 0, 8));
+        assert!(!pool.contains(&Ipv4Addr::new(10, 0, 0, 6)));
+        assert!(pool.contains(&Ipv4Addr::new(10, 0, 0, 9)));
+        assert_eq!(pool.size(), 10);
+        let all: Vec<_> = (0..5).map(|_| pool.allocate(|_| true).unwrap()).collect();
+        assert_eq!(
+            all,
+            [1, 2, 9, 10, 1].map(|last| Ipv4Addr::new(10, 0, 0, last))
+        );
+        assert_eq!(pool.allocate(|ip| ip.octets()[3] == 9), Some(Ipv4Addr::new(10, 0, 0, 9)));
+        assert_eq!(pool.allocate(|_| false), None);
+
+        // A full /16 with most of it excluded is scanned quickly
+        let mut pool = AddressPool::new(Ipv4Addr::new(172, 16, 0, 0), Ipv4Addr::new(172, 16, 255, 255));
+        pool.exclude(Ipv4Addr::new(172, 16, 0, 0), Ipv4Addr::new(172, 16, 254, 255));
+        assert_eq!(pool.allocate(|ip| ip.octets()[3] == 255), Some(Ipv4Addr::new(172, 16, 255, 255)));
+        assert_eq!(pool.allocate(|_| false), None);
+    }
+
+    #[test]
+    fn test_dhcp_discover() {
+        let unspecified = Ipv4Addr::UNSPECIFIED;
+        let mut server = test_server();
+        let discover = client_message(DhcpMessageTypeCode::Discover, 0x55, unspecified, vec![]);
+        let offer = server.process_discover(&discover).unwrap();
+        assert_eq!(offer.get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Offer));
+        assert_eq!(offer.get_yiaddr(), u32::from(Ipv4Addr::new(192, 168, 1, 10)));
+        assert!(offer.get_options().contains(&DhcpOption::IpAddressLeaseTime(86400)));
+        // The offer is held for the client, another one gets the next address
+        let again = server.process_discover(&discover).unwrap();
+        assert_eq!(again.get_yiaddr(), offer.get_yiaddr());
+        let other = client_message(DhcpMessageTypeCode::Discover, 0x66, unspecified, vec![]);
+        assert_eq!(server.process_discover(&other).unwrap().get_yiaddr(), u32::from(Ipv4Addr::new(192, 168, 1, 11)));
+
+        // Option 50 is honoured when free, not when offered to another client
+        let wants = |ip: Ipv4Addr| vec![DhcpOption::RequestedIpAddress(IpAddr::V4(ip))];
+        let requested = client_message(DhcpMessageTypeCode::Discover, 0x77, unspecified, wants(Ipv4Addr::new(192, 168, 1, 50)));
+        assert_eq!(server.get_available_ip(&requested), Ok(Ipv4Addr::new(192, 168, 1, 50)));
+        let taken = client_message(DhcpMessageTypeCode::Discover, 0x77, unspecified, wants(Ipv4Addr::new(192, 168, 1, 10)));
+        assert_eq!(server.get_available_ip(&taken), Ok(Ipv4Addr::new(192, 168, 1, 12)));
+
+        // A known client gets its previous address back
+        let mut server = test_server();
+        let lease = DhcpLease::new(
+            std::time::Duration::ZERO,
+            MacAddress::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x55),
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 42)),
+            None,
+            None,
+        );
+        server.load_leases(vec![lease]).unwrap();
+        assert_eq!(server.get_available_ip(&discover), Ok(Ipv4Addr::new(192, 168, 1, 42)));
+
+        // Reserved and excluded addresses are skipped
+        let mut server = test_server();
+        server.exclude(Ipv4Addr::new(192, 168, 1, 10), Ipv4Addr::new(192, 168, 1, 11));
+        let host = ClientIdentifier::Mac(MacAddress::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x99));
+        server.reserve(Reservation::new(host, Ipv4Addr::new(192, 168, 1, 12)));
+        assert_eq!(server.get_available_ip(&discover), Ok(Ipv4Addr::new(192, 168, 1, 13)));
+
+        // LeaseNoAvailable once the pool is full
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let tiny = Server::new(socket, IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)), IpAddr::V4(Ipv4Addr::new(192, 168, 1, 255)));
+        let mut server = DhcpServer::new(tiny, Ipv4Net::from_str("192.168.1.0/24").unwrap(), Ipv4Addr::new(192, 168, 1, 10), 1);
+        server.process_discover(&discover).unwrap();
+        assert_eq!(server.get_available_ip(&other), Err(DistributeDhcpLeaseError::LeaseNoAvailable));
+        assert_eq!(server.process_discover(&other), None);
+    }
+
+    #[test]
+    fn test_subnet_selection() {
+        let unspecified = Ipv4Addr::UNSPECIFIED;
+        let mut server = test_server();
+        let mut vlan10 = Subnet::new(Ipv4Net::from_str("10.1.0.0/24").unwrap());
+        vlan10.add_pool(Ipv4Addr::new(10, 1, 0, 100), Ipv4Addr::new(10, 1, 0, 110));
+        vlan10.set_options(vec![DhcpOption::Routers(vec![IpAddr::V4(Ipv4Addr::new(10, 1, 0, 1))])]);
+        server.add_subnet(vlan10);
+        server.set_options(vec![DhcpOption::Routers(vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))])]);
+        // A shared network whose first subnet has a single address
+        for (net, last) in [("10.2.0.0/24", 10), ("10.3.0.0/24", 20)] {
+            let mut subnet = Subnet::new(Ipv4Net::from_str(net).unwrap());
+            let first = subnet.get_net().network().octets();
+            subnet.add_pool(
+                Ipv4Addr::new(first[0], first[1], 0, 10),
+                Ipv4Addr::new(first[0], first[1], 0, last),
+            );
+            subnet.set_shared_network("vlan20");
+            server.add_subnet(subnet);
+        }
+        let relayed = |mac_last: u8, giaddr: Ipv4Addr, options: Vec<DhcpOption>| {
+            let mut p = client_message(DhcpMessageTypeCode::Discover, mac_last, unspecified, options);
+            p.set_giaddr(u32::from(giaddr));
+            p
+        };
+
+        // By relay address
+        let discover = relayed(0x55, Ipv4Addr::new(10, 1, 0, 1), vec![DhcpOption::ParameterRequestList(vec![3])]);
+        assert_eq!(server.select_subnet(&discover).unwrap().get_net().network(), Ipv4Addr::new(10, 1, 0, 0));
+        let offer = server.process_discover(&discover).unwrap();
+        assert_eq!(offer.get_yiaddr(), u32::from(Ipv4Addr::new(10, 1, 0, 100)));
+        assert!(offer
+            .get_options()
+            .contains(&DhcpOption::Routers(vec![IpAddr::V4(Ipv4Addr::new(10, 1, 0, 1))])));
+        // Link Selection and Subnet Selection win over the relay address
+        let link = DhcpOption::RelayAgentInformation(vec![RelayAgentSubOption::LinkSelection(Ipv4Addr::new(10, 2, 0, 0))]);
+        let linked = relayed(0x56, Ipv4Addr::new(10, 1, 0, 1), vec![link]);
+        assert_eq!(server.get_available_ip(&linked), Ok(Ipv4Addr::new(10, 2, 0, 10)));
+        let selection = DhcpOption::SubnetSelection(IpAddr::V4(Ipv4Addr::new(10, 1, 0, 0)));
+        let selected = client_message(DhcpMessageTypeCode::Discover, 0x57, unspecified, vec![selection.clone()]);
+        let offer = server.process_discover(&selected).unwrap();
+        assert_eq!(offer.get_yiaddr(), u32::from(Ipv4Addr::new(10, 1, 0, 101)));
+        assert!(offer.get_options().contains(&selection));
+        // Unknown links are not served
+        let unknown = relayed(0x58, Ipv4Addr::new(172, 16, 0, 1), vec![]);
+        assert_eq!(server.get_available_ip(&unknown), Err(DistributeDhcpLeaseError::UnknownSubnet));
+        assert_eq!(server.process_discover(&unknown), None);
+
+        // The shared network hands out the second subnet once the first is full
+        server.process_discover(&relayed(0x60, Ipv4Addr::new(10, 2, 0, 1), vec![])).unwrap();
+        let second = relayed(0x61, Ipv4Addr::new(10, 2, 0, 1), vec![]);
+        assert_eq!(server.get_available_ip(&second), Ok(Ipv4Addr::new(10, 3, 0, 10)));
+
+        // Leases know their subnet, and a relayed request for an address of
+        // another link is NAKed
+        let server_id = DhcpOption::ServerIdentifier(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)));
+        let mut request = dhcp_request(0x62, unspecified, vec![server_id.clone(), DhcpOption::RequestedIpAddress(IpAddr::V4(Ipv4Addr::new(10, 3, 0, 11)))]);
+        request.set_giaddr(u32::from(Ipv4Addr::new(10, 2, 0, 1)));
+        server.process_request(&request).unwrap();
+        let lease = server.get_leases().get_by_ip(&IpAddr::V4(Ipv4Addr::new(10, 3, 0, 11))).unwrap();
+        assert_eq!(lease.get_subnet(), &Some(Ipv4Net::from_str("10.3.0.0/24").unwrap()));
+        let mut wrong = dhcp_request(0x63, unspecified, vec![server_id.clone(), DhcpOption::RequestedIpAddress(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)))]);
+        wrong.set_giaddr(u32::from(Ipv4Addr::new(10, 1, 0, 1)));
+        let nak = server.process_request(&wrong).unwrap();
+        assert_eq!(nak.get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Nak));
+
+        // A client keeps one lease per subnet, a new address on the same
+        // subnet ends the older one
+        let request = |ip: Ipv4Addr, giaddr: Ipv4Addr| {
+            let mut p = dhcp_request(0x64, unspecified, vec![server_id.clone(), DhcpOption::RequestedIpAddress(IpAddr::V4(ip))]);
+            p.set_giaddr(u32::from(giaddr));
+            p
+        };
+        server.process_request(&request(Ipv4Addr::new(192, 168, 1, 30), unspecified)).unwrap();
+        server.process_request(&request(Ipv4Addr::new(10, 1, 0, 105), Ipv4Addr::new(10, 1, 0, 1))).unwrap();
+        let mac = MacAddress::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x64);
+        assert_eq!(server.get_leases().get_by_mac(&mac).len(), 2);
+        server.process_request(&request(Ipv4Addr::new(10, 1, 0, 106), Ipv4Addr::new(10, 1, 0, 1))).unwrap();
+        let mut held: Vec<IpAddr> = server.get_leases().get_by_mac(&mac).iter().map(|l| *l.get_ip()).collect();
+        held.sort();
+        assert_eq!(held, vec![IpAddr::V4(Ipv4Addr::new(10, 1, 0, 106)), IpAddr::V4(Ipv4Addr::new(192, 168, 1, 30))]);
+    }
+
+    #[test]
+    fn test_reservations() {
+        let unspecified = Ipv4Addr::UNSPECIFIED;
+        let printer_ip = Ipv4Addr::new(192, 168, 1, 200);
+        let mut server = test_server();
+        server.set_options(vec![DhcpOption::DomainName("lab".to_string())]);
+        // Outside the pool, with its own name, options, lease time and boot file
+        let mut printer = Reservation::new(ClientIdentifier::Mac(MacAddress::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x55)), printer_ip);
+        printer.set_hostname("printer");
+        printer.set_options(vec![DhcpOption::DomainName("printers.lab".to_string())]);
+        printer.set_lease_duration(3600);
+        printer.set_boot_file("printer.bin");
+        server.reserve(printer);
+        // Inside the pool, by client identifier
+        let laptop_id = vec![1, 0x00, 0x11, 0x22, 0x33, 0x44, 0x66];
+        server.reserve(Reservation::new(
+            ClientIdentifier::ClientId(format_client_id(&laptop_id)),
+            Ipv4Addr::new(192, 168, 1, 10),
+        ));
+        // By the Remote-ID of the relay agent
+        server.reserve(Reservation::new(ClientIdentifier::RemoteId(b"modem-1".to_vec()), Ipv4Addr::new(192, 168, 1, 201)));
+
+        let prl = DhcpOption::ParameterRequestList(vec![HOST_NAME, DOMAIN_NAME, BOOTFILE_NAME]);
+        let discover = client_message(DhcpMessageTypeCode::Discover, 0x55, unspecified, vec![prl.clone()]);
+        let offer = server.process_discover(&discover).unwrap();
+        assert_eq!(offer.get_yiaddr(), u32::from(printer_ip));
+        assert!(offer.get_options().contains(&DhcpOption::IpAddressLeaseTime(3600)));
+        assert!(offer.get_options().contains(&DhcpOption::HostName("printer".to_string())));
+        assert!(offer.get_options().contains(&DhcpOption::DomainName("printers.lab".to_string())));
+        assert!(offer.get_options().contains(&DhcpOption::BootfileName("printer.bin".to_string())));
+        assert_eq!(&offer.get_file()[..12], b"printer.bin\0");
+        let server_id = DhcpOption::ServerIdentifier(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)));
+        let requested = DhcpOption::RequestedIpAddress(IpAddr::V4(printer_ip));
+        server
+            .process_request(&dhcp_request(0x55, unspecified, vec![server_id.clone(), requested.clone()]))
+            .unwrap();
+        let lease = server.get_leases().get_by_ip(&IpAddr::V4(printer_ip)).unwrap();
+        assert_eq!(lease.get_hostname(), &Some("printer".to_string()));
+
+        // Nobody else gets a reserved address, in or out of the pool
+        let nak = server
+            .process_request(&dhcp_request(0x77, unspecified, vec![server_id, requested]))
+            .unwrap();
+        assert_eq!(nak.get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Nak));
+        let stranger = client_message(DhcpMessageTypeCode::Discover, 0x77, unspecified, vec![]);
+        assert_eq!(server.get_available_ip(&stranger), Ok(Ipv4Addr::new(192, 168, 1, 11)));
+        let laptop = client_message(
+            DhcpMessageTypeCode::Discover,
+            0x99,
+            unspecified,
+            vec![DhcpOption::ClientIdentifier(laptop_id)],
+        );
+        assert_eq!(server.get_available_ip(&laptop), Ok(Ipv4Addr::new(192, 168, 1, 10)));
+        let remote_id = DhcpOption::RelayAgentInformation(vec![RelayAgentSubOption::RemoteId(b"modem-1".to_vec())]);
+        let modem = client_message(DhcpMessageTypeCode::Discover, 0x88, unspecified, vec![remote_id]);
+        assert_eq!(server.get_available_ip(&modem), Ok(Ipv4Addr::new(192, 168, 1, 201)));
+    }
+
+    #[test]
+    fn test_client_classes() {
+        let unspecified = Ipv4Addr::UNSPECIFIED;
+        let mut server = test_server();
+        server.set_options(vec![DhcpOption::DomainName("lab".to_string())]);
+        let mut phones = ClientClass::new("phones", ClassMatch::VendorClass(b"Cisco Phone".to_vec()));
+        phones.set_lease_duration(600);
+        phones.set_options(vec![DhcpOption::DomainName("voice.lab".to_string())]);
+        server.add_class(phones);
+        let printers = ClassMatch::All(vec![
+            ClassMatch::MacPrefix(vec![0x00, 0x11, 0x22]),
+            ClassMatch::UserClass(b"printer".to_vec()),
+        ]);
+        server.add_class(ClientClass::new("printers", printers));
+        server.add_class(ClientClass::new("uefi", ClassMatch::Architecture(ARCH_X64_UEFI)));
+        server.add_class(ClientClass::new("lobby", ClassMatch::CircuitId(b"eth0/1".to_vec())));
+        server.add_class(ClientClass::new("not-lobby", ClassMatch::Not(Box::new(ClassMatch::CircuitId(b"eth0/1".to_vec())))));
+        // Phones only get addresses from their own pool
+        let mut subnet = Subnet::new(Ipv4Net::from_str("192.168.1.0/24").unwrap());
+        let mut data = AddressPool::new(Ipv4Addr::new(192, 168, 1, 10), Ipv4Addr::new(192, 168, 1, 19));
+        data.deny_class("phones");
+        subnet.add_address_pool(data);
+        let mut voice = AddressPool::new(Ipv4Addr::new(192, 168, 1, 150), Ipv4Addr::new(192, 168, 1, 159));
+        voice.allow_class("phones");
+        subnet.add_address_pool(voice);
+        server.add_subnet(subnet);
+
+        let printer = client_message(
+            DhcpMessageTypeCode::Discover,
+            0x55,
+            unspecified,
+            vec![
+                DhcpOption::UserClass(vec![b"printer".to_vec()]),
+                DhcpOption::ClientArchitecture(vec![ARCH_X64_UEFI]),
+                DhcpOption::RelayAgentInformation(vec![RelayAgentSubOption::CircuitId(b"eth0/1".to_vec())]),
+            ],
+        );
+        assert_eq!(server.classify(&printer), ["printers", "uefi", "lobby"]);
+        let other = client_message(DhcpMessageTypeCode::Discover, 0x56, unspecified, vec![]);
+        assert_eq!(server.classify(&other), ["not-lobby"]);
+
+        let prl = DhcpOption::ParameterRequestList(vec![DOMAIN_NAME]);
+        let phone = client_message(
+            DhcpMessageTypeCode::Discover,
+            0x57,
+            unspecified,
+            vec![DhcpOption::ClassIdentifier(b"Cisco Phone CP-7945G".to_vec()), prl.clone()],
+        );
+        let offer = server.process_discover(&phone).unwrap();
+        assert_eq!(offer.get_yiaddr(), u32::from(Ipv4Addr::new(192, 168, 1, 150)));
+        assert!(offer.get_options().contains(&DhcpOption::IpAddressLeaseTime(600)));
+        assert!(offer.get_options().contains(&DhcpOption::DomainName("voice.lab".to_string())));
+        let offer = server.process_discover(&other).unwrap();
+        assert_eq!(offer.get_yiaddr(), u32::from(Ipv4Addr::new(192, 168, 1, 10)));
+        assert!(offer.get_options().contains(&DhcpOption::IpAddressLeaseTime(86400)));
+
+        // Others are refused the pool of the phones
+        let server_id = DhcpOption::ServerIdentifier(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)));
+        let requested = DhcpOption::RequestedIpAddress(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 150)));
+        let nak = server
+            .process_request(&dhcp_request(0x56, unspecified, vec![server_id.clone(), requested.clone()]))
+            .unwrap();
+        assert_eq!(nak.get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Nak));
+        let phone_request = dhcp_request(
+            0x57,
+            unspecified,
+            vec![server_id.clone(), requested, DhcpOption::ClassIdentifier(b"Cisco Phone".to_vec())],
+        );
+        let ack = server.process_request(&phone_request).unwrap();
+        assert_eq!(ack.get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Ack));
+        // and phones the general pool
+        let data_ip = DhcpOption::RequestedIpAddress(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 12)));
+        let phone_request = dhcp_request(
+            0x58,
+            unspecified,
+            vec![server_id, data_ip, DhcpOption::ClassIdentifier(b"Cisco Phone".to_vec())],
+        );
+        let nak = server.process_request(&phone_request).unwrap();
+        assert_eq!(nak.get_dhcp_message_type(), Some(&DhcpMessageTypeCode::Nak));
+    }
+
+    #[test]
+    fn test_hostname_policy() {
+        assert_eq!(sanitize_hostname("Bob's iPhone"), Some("bob-s-iphone".to_string()));
+        assert_eq!(sanitize_hostname("--"), None);
+
+        // Client asks the server to update its A record
+        let fqdn = ClientFqdn::new(FQDN_FLAG_S | FQDN_FLAG_E, 0, 0, "Laptop.home.".to_string());
+        let p = request_packet(vec![
+            DhcpOption::HostName("ignored".to_string()),
+            DhcpOption::ClientFqdn(fqdn),
+        ]);
+        let policy = HostnamePolicy::new(DnsUpdateMode::Client, Some("example.com".to_string()));
+        let decision = policy.decide(&p);
+        assert_eq!(decision.get_hostname(), &Some("laptop".to_string()));
+        assert_eq!(decision.get_fqdn(), &Some("laptop.example.com.".to_string()));
+        assert!(decision.server_updates());
+        let reply = ClientFqdn::new(FQDN_FLAG_S | FQDN_FLAG_E, 255, 255, "laptop.example.com.".to_string());
+        assert_eq!(decision.get_reply(), &Some(reply));
+
+        // The server takes over the update and says so with the O flag
+        let fqdn = ClientFqdn::new(FQDN_FLAG_E, 0, 0, "laptop".to_string());
+        let p = request_packet(vec![DhcpOption::ClientFqdn(fqdn)]);
+        let policy = HostnamePolicy::new(DnsUpdateMode::Server, None);
+        let reply = policy.decide(&p).get_reply().clone().expect("No FQDN reply");
+        assert_eq!(reply.get_flags(), FQDN_FLAG_S | FQDN_FLAG_O | FQDN_FLAG_E);
+        assert_eq!(reply.get_name(), "laptop");
+
+        // Host Name only: rewritten, no FQDN in the reply
+        let p = request_packet(vec![DhcpOption::HostName("android-1234".to_string())]);
+        let mut policy = HostnamePolicy::new(DnsUpdateMode::NoUpdate, None);
+        policy.add_rewrite("Android-1234", "kitchen_tablet");
+        let decision = policy.decide(&p);
+        assert_eq!(decision.get_hostname(), &Some("kitchen-tablet".to_string()));
+        assert!(!decision.server_updates());
+        assert_eq!(decision.get_reply(), &None);
+    }
+
+    #[test]
+    fn test_options() {
+        // Encoded options decode to the same options, the message type first
+        // and a long one split and joined again (RFC 3396)
+        let options = vec![
+            DhcpOption::Routers(vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))]),
+            DhcpOption::DhcpMessageType(DhcpMessageTypeCode::Ack),
+            DhcpOption::IpAddressLeaseTime(86400),
+            DhcpOption::DomainName("lab".repeat(100)),
+            DhcpOption::Unrecognized(RawDhcpOption::new(224, vec![0xde, 0xad])),
+        ];
+        let area: Vec<u8> = encode_options(&options).unwrap().concat();
+        assert_eq!(area[..3], [DHCP_MESSAGE_TYPE, 1, 5]);
+        let decoded = decode_options(&area).unwrap();
+        assert_eq!(decoded.len(), options.len());
+        for option in &options {
+            assert!(decoded.contains(option), "{:?} lost", option);
+        }
     }
 
     #[test]
     fn test_storage() {
-        crate::dhcp::test_storage();
+        use std::time::Duration;
+
+        let lease = |secs: u64, mac: u8, ip: u8, chi: Option<&str>| {
+            DhcpLease::new(
+                Duration::from_secs(secs),
+                MacAddress::new(0x00, 0x11, 0x22, 0x33, 0x44, mac),
+                IpAddr::V4(Ipv4Addr::new(192, 168, 1, ip)),
+                Some(format!("host-{}", ip)),
+                chi.map(|chi| chi.to_string()),
+            )
+        };
+        let mac = MacAddress::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x01);
+        let ip = |ip: u8| IpAddr::V4(Ipv4Addr::new(192, 168, 1, ip));
+
+        // Lookups by address, hardware address and client identifier
+        let mut store = MemoryLeaseStore::new();
+        store.insert(lease(300, 0x01, 10, Some("01:aa"))).unwrap();
+        store.insert(lease(100, 0x02, 11, None)).unwrap();
+        store.insert(lease(200, 0x01, 12, None)).unwrap();
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.get_by_ip(&ip(11)).unwrap().get_mac(), &MacAddress::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x02));
+        assert_eq!(store.get_by_mac(&mac).len(), 2);
+        assert_eq!(store.get_by_client_id("01:aa").len(), 1);
+        // Another identifier is another client, a lease without one is found
+        // by hardware address
+        assert_eq!(store.find_client(Some(&[0x01, 0xaa]), &mac).len(), 2);
+        assert_eq!(store.find_client(Some(&[0x01, 0xbb]), &mac).len(), 1);
+
+        // Expiry order follows updates, update needs an existing lease
+        let expiry: Vec<IpAddr> = store.iter_by_expiry().map(|l| *l.get_ip()).collect();
+        assert_eq!(expiry, vec![ip(11), ip(12), ip(10)]);
+        store.update(lease(50, 0x01, 10, Some("01:aa"))).unwrap();
+        assert_eq!(store.iter_by_expiry().next().unwrap().get_ip(), &ip(10));
+        assert_eq!(store.expired(Duration::from_secs(150)).len(), 2);
+        assert_eq!(store.update(lease(50, 0x03, 13, None)), Err(StorageError::LeaseNotFound(ip(13))));
+
+        // Removing a lease drops it from every index
+        assert!(store.remove(&ip(10)).unwrap().is_some());
+        assert!(store.get_by_client_id("01:aa").is_empty());
+        assert_eq!(store.get_by_mac(&mac).len(), 1);
+
+        // A file store keeps the leases of the last commit only
+        let path = std::env::temp_dir().join(format!("rolldhcp-test-{}.leases", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut store = FileLeaseStore::open(&path).unwrap();
+        assert!(store.is_empty());
+        store.insert(lease(300, 0x01, 10, Some("01:aa"))).unwrap();
+        store.insert(lease(100, 0x02, 11, None)).unwrap();
+        store.commit().unwrap();
+        store.remove(&ip(11)).unwrap();
+        let reopened = FileLeaseStore::open(&path).unwrap();
+        assert_eq!(reopened.len(), 2);
+        assert_eq!(reopened.get_by_ip(&ip(10)), Some(&lease(300, 0x01, 10, Some("01:aa"))));
+        assert_eq!(reopened.get_by_ip(&ip(11)).unwrap().get_chi(), &None);
+
+        // A dnsmasq file has no subnets, the server tags the leases with theirs
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = Server::new(socket, ip(1), ip(255));
+        let subnet = Ipv4Net::from_str("192.168.1.0/24").unwrap();
+        let server = DhcpServer::with_store(server, subnet, Ipv4Addr::new(192, 168, 1, 10), 100, reopened);
+        assert!(server.get_leases().iter().all(|lease| lease.get_subnet() == &Some(subnet)));
+
+        // A line that does not parse is reported with its number
+        std::fs::write(&path, "300 00:11:22:33:44:01 192.168.1.10 * *\nnot a lease\n").unwrap();
+        let invalid = LeaseError::ParseError(ParseDhcpLeaseError::InvalidFieldsLength);
+        assert_eq!(FileLeaseStore::open(&path).unwrap_err(), StorageError::InvalidLease(2, invalid));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    // A store whose commits fail while `failing` is set
+    struct FailingLeaseStore {
+        leases: MemoryLeaseStore,
+        failing: std::rc::Rc<std::cell::Cell<bool>>,
+    }
+
+    impl LeaseStore for FailingLeaseStore {
+        fn insert(&mut self, lease: DhcpLease) -> StorageResult<()> {
+            self.leases.insert(lease)
+        }
+        fn update(&mut self, lease: DhcpLease) -> StorageResult<()> {
+            self.leases.update(lease)
+        }
+        fn remove(&mut self, ip: &IpAddr) -> StorageResult<Option<DhcpLease>> {
+            self.leases.remove(ip)
+        }
+        fn get_by_ip(&self, ip: &IpAddr) -> Option<&DhcpLease> {
+            self.leases.get_by_ip(ip)
+        }
+        fn get_by_mac(&self, mac: &MacAddress) -> Vec<&DhcpLease> {
+            self.leases.get_by_mac(mac)
+        }
+        fn get_by_client_id(&self, client_id: &str) -> Vec<&DhcpLease> {
+            self.leases.get_by_client_id(client_id)
+        }
+        fn iter(&self) -> Box<dyn Iterator<Item = &DhcpLease> + '_> {
+            self.leases.iter()
+        }
+        fn iter_by_expiry(&self) -> Box<dyn Iterator<Item = &DhcpLease> + '_> {
+            self.leases.iter_by_expiry()
+        }
+        fn commit(&mut self) -> StorageResult<()> {
+            match self.failing.get() {
+                true => Err(StorageError::Io("disk full".to_string())),
+                false => self.leases.commit(),
+            }
+        }
+        fn rollback(&mut self) {
+            self.leases.rollback()
+        }
+        fn len(&self) -> usize {
+            self.leases.len()
+        }
+    }
+
+    #[test]
+    fn test_failed_commit() {
+        let server_id = DhcpOption::ServerIdentifier(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)));
+        let requested = |ip: u8| DhcpOption::RequestedIpAddress(IpAddr::V4(Ipv4Addr::new(192, 168, 1, ip)));
+        let ip = |ip: u8| IpAddr::V4(Ipv4Addr::new(192, 168, 1, ip));
+        let failing = std::rc::Rc::new(std::cell::Cell::new(false));
+        let store = FailingLeaseStore {
+            leases: MemoryLeaseStore::new(),
+            failing: failing.clone(),
+        };
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = Server::new(socket, ip(1), ip(255));
+        let subnet = Ipv4Net::from_str("192.168.1.0/24").unwrap();
+        let mut server = DhcpServer::with_store(server, subnet, Ipv4Addr::new(192, 168, 1, 10), 100, store);
+        let selecting = |ip: u8| dhcp_request(0x55, Ipv4Addr::UNSPECIFIED, vec![server_id.clone(), requested(ip)]);
+        server.process_request(&selecting(20)).unwrap();
+
+        // A lease that is not committed is neither acknowledged nor kept, the
+        // lease it replaced is back
+        failing.set(true);
+        assert_eq!(server.process_request(&selecting(30)), None);
+        assert!(server.get_leases().get_by_ip(&ip(30)).is_none());
+        assert!(server.get_leases().get_by_ip(&ip(20)).is_some());
+        assert_eq!(server.get_leases().len(), 1);
+
+        // Nor is a release or a decline
+        let release = client_message(DhcpMessageTypeCode::Release, 0x55, Ipv4Addr::new(192, 168, 1, 20), vec![server_id.clone()]);
+        server.process_release(&release);
+        assert_eq!(server.get_leases().get_by_ip(&ip(20)).unwrap().get_state(), BindingState::Active);
+        let decline = client_message(DhcpMessageTypeCode::Decline, 0x55, Ipv4Addr::UNSPECIFIED, vec![server_id.clone(), requested(20)]);
+        server.process_decline(&decline);
+        assert!(server.get_leases().get_by_ip(&ip(20)).is_some());
+
+        // The next commit holds none of the dropped changes
+        failing.set(false);
+        server.process_request(&selecting(20)).unwrap();
+        assert_eq!(server.get_leases().len(), 1);
+        assert!(server.get_leases().get_by_ip(&ip(30)).is_none());
+    }
+
+    #[test]
+    fn test_lease_journal() {
+        use std::time::Duration;
+
+        let lease = |secs: u64, ip: u8| {
+            let mut lease = DhcpLease::new(
+                Duration::from_secs(secs),
+                MacAddress::new(0x00, 0x11, 0x22, 0x33, 0x44, ip),
+                IpAddr::V4(Ipv4Addr::new(192, 168, 1, ip)),
+                Some(format!("host-{}", ip)),
+                None,
+            );
+            lease.set_start(Duration::from_secs(secs - 3600));
+            lease
+        };
+        let ip = |ip: u8| IpAddr::V4(Ipv4Addr::new(192, 168, 1, ip));
+        let path = std::env::temp_dir().join(format!("rolldhcp-test-{}.snapshot", std::process::id()));
+        let journal = path.with_extension("snapshot.journal");
+        let clean = || {
+            for file in [&path, &journal, &path.with_extension("snapshot.journal.old")] {
+                let _ = std::fs::remove_file(file);
+            }
+        };
+        clean();
+
+        // Committed transitions are replayed, uncommitted ones are lost
+        let mut store = JournalLeaseStore::open(&path).unwrap();
+        store.insert(lease(1700003600, 10)).unwrap();
+        store.insert(lease(1700003600, 11)).unwrap();
+        store.commit().unwrap();
+        let mut released = lease(1700003600, 11);
+        released.set_state(BindingState::Released);
+        store.update(released.clone()).unwrap();
+        store.remove(&ip(10)).unwrap();
+        store.commit().unwrap();
+        store.insert(lease(1700003600, 12)).unwrap();
+        drop(store);
+        let store = JournalLeaseStore::open(&path).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.get_by_ip(&ip(11)), Some(&released));
+        drop(store);
+
+        // A torn final record drops its commit and is cut from the journal
+        let committed = std::fs::read(&journal).unwrap();
+        let mut torn = committed.clone();
+        torn.extend_from_slice(b"0badc0de put 1700000000 active 1700003600 00:11");
+        std::fs::write(&journal, &torn).unwrap();
+        let mut store = JournalLeaseStore::open(&path).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(std::fs::read(&journal).unwrap(), committed);
+        store.insert(lease(1700003600, 12)).unwrap();
+        store.commit().unwrap();
+        drop(store);
+        assert_eq!(JournalLeaseStore::open(&path).unwrap().len(), 2);
+
+        // A damaged record before the last one is an error
+        let content = std::fs::read_to_string(&journal).unwrap().replacen("host-11", "host-99", 1);
+        std::fs::write(&journal, content).unwrap();
+        assert!(matches!(JournalLeaseStore::open(&path), Err(StorageError::ChecksumMismatch(_))));
+        clean();
+
+        // Compaction moves the journal into the snapshot
+        let mut store = JournalLeaseStore::open(&path).unwrap();
+        store.set_compact_after(4);
+        store.insert(lease(1700003600, 10)).unwrap();
+        store.insert(lease(1700003600, 11)).unwrap();
+        store.commit().unwrap();
+        store.insert(lease(1700003600, 12)).unwrap();
+        store.commit().unwrap();
+        store.wait_for_compaction().unwrap();
+        assert_eq!(std::fs::read(&journal).unwrap().len(), 0);
+        assert!(!path.with_extension("snapshot.journal.old").exists());
+        store.remove(&ip(12)).unwrap();
+        store.commit().unwrap();
+        drop(store);
+
+        // A failed commit, e.g. on a full disk, leaves part of its records
+        // behind. Rolling back cuts them, the store opens again.
+        let mut store = JournalLeaseStore::open(&path).unwrap();
+        let committed = std::fs::read(&journal).unwrap();
+        store.insert(lease(1700003600, 13)).unwrap();
+        let mut torn = std::fs::OpenOptions::new().append(true).open(&journal).unwrap();
+        std::io::Write::write_all(&mut torn, b"0badc0de put 1700000000 active * 17000").unwrap();
+        store.rollback();
+        assert_eq!(std::fs::read(&journal).unwrap(), committed);
+        assert!(store.get_by_ip(&ip(13)).is_none());
+        store.insert(lease(1700003600, 14)).unwrap();
+        store.commit().unwrap();
+        drop(store);
+        let mut store = JournalLeaseStore::open(&path).unwrap();
+        assert!(store.get_by_ip(&ip(13)).is_none());
+        assert!(store.get_by_ip(&ip(14)).is_some());
+
+        // A failed compaction does not fail the commit that started it
+        let old = path.with_extension("snapshot.journal.old");
+        std::fs::create_dir(&old).unwrap();
+        store.set_compact_after(1);
+        store.remove(&ip(14)).unwrap();
+        assert_eq!(store.commit(), Ok(()));
+        std::fs::remove_dir(&old).unwrap();
+        drop(store);
+        assert!(JournalLeaseStore::open(&path).unwrap().get_by_ip(&ip(14)).is_none());
+
+        // The server replays the journal when it is built
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = Server::new(
+            socket,
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)),
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 255)),
+        );
+        let subnet = Ipv4Net::from_str("192.168.1.0/24").unwrap();
+        let server = DhcpServer::with_journal(server, subnet, Ipv4Addr::new(192, 168, 1, 10), 100, &path).unwrap();
+        assert_eq!(server.get_leases().len(), 2);
+        assert!(server.get_leases().get_by_ip(&ip(12)).is_none());
+        let mut tagged = lease(1700003600, 10);
+        tagged.set_subnet(subnet);
+        assert_eq!(server.get_leases().get_by_ip(&ip(10)), Some(&tagged));
+        drop(server);
+        // The subnet of a lease is in its record
+        let store = JournalLeaseStore::open(&path).unwrap();
+        assert_eq!(store.get_by_ip(&ip(10)), Some(&tagged));
+        clean();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_lease_store() {
+        use std::time::Duration;
+
+        let lease = |mac: u8, start: u64, expiry: u64| {
+            let mut lease = DhcpLease::new(
+                Duration::from_secs(expiry),
+                MacAddress::new(0x00, 0x11, 0x22, 0x33, 0x44, mac),
+                IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3)),
+                Some(format!("host-{}", mac)),
+                Some(format!("01:00:11:22:33:44:{:02x}", mac)),
+            );
+            lease.set_start(Duration::from_secs(start));
+            lease
+        };
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3));
+        let at = Duration::from_secs;
+        let path = std::env::temp_dir().join(format!("rolldhcp-test-{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        // One client holds the address, releases it, another one gets it
+        let mut store = SqliteLeaseStore::open(&path).unwrap();
+        store.insert(lease(0x01, 1000, 4600)).unwrap();
+        store.commit().unwrap();
+        let mut released = lease(0x01, 1000, 2000);
+        released.set_state(BindingState::Released);
+        store.update(released).unwrap();
+        store.commit().unwrap();
+        store.insert(lease(0x02, 3000, 6600)).unwrap();
+        store.commit().unwrap();
+
+        assert_eq!(store.who_had(&ip, at(500)).unwrap(), None);
+        assert_eq!(store.who_had(&ip, at(1500)).unwrap(), Some(lease(0x01, 1000, 4600)));
+        assert_eq!(store.who_had(&ip, at(2500)).unwrap(), None);
+        assert_eq!(store.who_had(&ip, at(3500)).unwrap(), Some(lease(0x02, 3000, 6600)));
+        assert_eq!(store.who_had(&ip, at(7000)).unwrap(), None);
+        assert_eq!(store.history_by_ip(&ip).unwrap().len(), 3);
+        let first = MacAddress::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x01);
+        let history = store.history_by_mac(&first).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].get_time(), &at(2000));
+        assert_eq!(history[1].get_lease().as_ref().unwrap().get_state(), BindingState::Released);
+        assert_eq!(store.history_between(at(0), at(2500)).unwrap().len(), 2);
+
+        // Removing a lease keeps its history, uncommitted changes are lost
+        store.remove(&ip).unwrap();
+        store.commit().unwrap();
+        assert_eq!(store.history_by_ip(&ip).unwrap().last().unwrap().get_lease(), &None);
+        assert_eq!(store.who_had(&ip, at(3500)).unwrap(), Some(lease(0x02, 3000, 6600)));
+        store.insert(lease(0x03, 8000, 11600)).unwrap();
+        drop(store);
+        let mut store = SqliteLeaseStore::open(&path).unwrap();
+        assert!(store.is_empty());
+        let mut tagged = lease(0x03, 8000, 11600);
+        tagged.set_subnet(Ipv4Net::from_str("10.1.2.0/24").unwrap());
+        store.insert(tagged.clone()).unwrap();
+        store.commit().unwrap();
+        drop(store);
+        let store = SqliteLeaseStore::open(&path).unwrap();
+        assert_eq!(store.get_by_ip(&ip), Some(&tagged));
+        assert_eq!(store.history_between(at(8000), at(8001)).unwrap()[0].get_lease(), &Some(tagged));
+        assert_eq!(store.get_by_client_id("01:00:11:22:33:44:03").len(), 1);
+        drop(store);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_isc_leases() {
+        use std::time::Duration;
+
+        let content = r#"# The format of this file is documented in the dhcpd.leases(5) manual page.
+authoring-byte-order little-endian;
+server-duid "\000\001\000\001";
+
+lease 192.168.1.20 {
+  starts 2 2023/11/14 22:13:20;
+  ends 3 2023/11/15 22:13:20;
+  binding state active;
+}
+lease 192.168.1.20 {
+  starts 2 2023/11/14 22:13:20;
+  ends 3 2023/11/15 22:13:20;
+  cltt 2 2023/11/14 22:13:20;
+  binding state active;
+  next binding state free;
+  hardware ethernet 00:11:22:33:44:55;
+  uid "\001\000\021\"3DU";
+  set vendor-class-identifier = "MSFT 5.0";
+  on expiry { set ddns-fwd-name = "laptop"; }
+  client-hostname "laptop";
+}
+lease 192.168.1.21 {
+  starts epoch 1700000000; # Tue Nov 14 22:13:20 2023
+  ends never;
+  binding state bootp;
+  hardware ethernet 00:11:22:33:44:56;
+  uid 01:00:11:22:33:44:56;
+}
+lease 192.168.1.22 {
+  ends 2 2023/11/14 22:13:20;
+  binding state free;
+}
+host printer { hardware ethernet 00:11:22:33:44:57; fixed-address 192.168.1.5; }
+"#;
+        let leases = parse_isc_leases(content).unwrap();
+        assert_eq!(leases.len(), 3);
+        // The last block of an address is its lease
+        let lease = &leases[0];
+        assert_eq!(lease.get_start(), &Some(Duration::from_secs(1700000000)));
+        assert_eq!(lease.get_expiry(), &Duration::from_secs(1700086400));
+        assert_eq!(lease.get_state(), BindingState::Active);
+        assert_eq!(lease.get_mac(), &MacAddress::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x55));
+        assert_eq!(lease.get_chi().as_deref(), Some("01:00:11:22:33:44:55"));
+        assert_eq!(lease.get_hostname().as_deref(), Some("laptop"));
+        assert_eq!(leases[1].get_expiry(), &INFINITE_EXPIRY);
+        assert_eq!(leases[1].get_chi().as_deref(), Some("01:00:11:22:33:44:56"));
+        assert_eq!(leases[2].get_mac(), &MacAddress::nil());
+
+        // Only active bindings are carried over
+        let now = Duration::from_secs(1700050000);
+        let active: Vec<IpAddr> = leases.iter().filter(|l| l.is_active(now)).map(|l| *l.get_ip()).collect();
+        assert_eq!(active, vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)), IpAddr::V4(Ipv4Addr::new(192, 168, 1, 21))]);
+
+        // Written back and read again unchanged
+        let written = format_isc_leases(&leases);
+        assert!(written.contains("  starts 2 2023/11/14 22:13:20;\n  ends 3 2023/11/15 22:13:20;\n"));
+        assert!(written.contains("  ends never;\n  binding state bootp;\n"));
+        assert_eq!(parse_isc_leases(&written).unwrap(), leases);
+
+        let unterminated = "lease 192.168.1.20 {\n  binding state active;\n";
+        let invalid = LeaseError::ParseError(ParseDhcpLeaseError::UnexpectedToken);
+        assert_eq!(parse_isc_leases(unterminated), Err(StorageError::InvalidLease(1, invalid)));
+        let state = "lease 192.168.1.20 {\n  binding state gone;\n}\n";
+        let invalid = LeaseError::ParseError(ParseDhcpLeaseError::InvalidBindingState);
+        assert_eq!(parse_isc_leases(state), Err(StorageError::InvalidLease(1, invalid)));
+
+        // A lease of another hardware type is skipped, the rest is read
+        let token_ring = "lease 192.168.1.23 {\n  hardware token-ring 00:11:22:33:44:58;\n  binding state active;\n}\n";
+        let mixed = format!("{}{}", token_ring, content);
+        assert_eq!(parse_isc_leases(&mixed).unwrap(), leases);
+        // and replaces the earlier lease of its address
+        let replaced = format!("{}{}", content, token_ring.replace(".23", ".20"));
+        assert_eq!(parse_isc_leases(&replaced).unwrap(), leases[1..].to_vec());
+    }
+
+    #[test]
+    fn test_kea_leases() {
+        use std::time::Duration;
+
+        let content = "\
+address,hwaddr,client_id,valid_lifetime,expire,subnet_id,fqdn_fwd,fqdn_rev,hostname,state,user_context,pool_id
+192.168.1.20,00:11:22:33:44:55,01:00:11:22:33:44:55,3600,1700003600,1,0,0,laptop,0,,0
+192.168.1.21,00:11:22:33:44:56,,3600,1700003600,1,0,0,a&#x2cb,0,{ \"a\": 1 },0
+192.168.1.22,,,3600,1700003600,1,0,0,,1,,0
+192.168.1.23,00:11:22:33:44:58,,3600,1700003600,1,0,0,,0,,0
+192.168.1.23,00:11:22:33:44:58,,0,1700003600,1,0,0,,0,,0
+192.168.1.24,80:00:00:48:fe:80:00:00:00:00:00:00:00:02:c9:03:00:0a:bc:de,,3600,1700003600,1,0,0,,0,,0
+";
+        let leases = parse_kea_leases4(content).unwrap();
+        // A valid lifetime of 0 deletes the lease, an InfiniBand lease is skipped
+        assert_eq!(leases.len(), 3);
+        let lease = &leases[0];
+        assert_eq!(lease.get_start(), &Some(Duration::from_secs(1700000000)));
+        assert_eq!(lease.get_expiry(), &Duration::from_secs(1700003600));
+        assert_eq!(lease.get_mac(), &MacAddress::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x55));
+        assert_eq!(lease.get_chi().as_deref(), Some("01:00:11:22:33:44:55"));
+        assert_eq!(lease.get_hostname().as_deref(), Some("laptop"));
+        assert_eq!(lease.get_state(), BindingState::Active);
+        assert_eq!(leases[1].get_hostname().as_deref(), Some("a,b"));
+        // A declined address
+        assert_eq!(leases[2].get_state(), BindingState::Abandoned);
+        assert_eq!(leases[2].get_mac(), &MacAddress::nil());
+
+        let written = format_kea_leases4(&leases, |_| 1);
+        assert_eq!(written.lines().nth(1), Some("192.168.1.20,00:11:22:33:44:55,01:00:11:22:33:44:55,3600,1700003600,1,0,0,laptop,0,,0"));
+        assert_eq!(parse_kea_leases4(&written).unwrap(), leases);
+
+        // Older files without the state column
+        let old = "address,hwaddr,client_id,valid_lifetime,expire,subnet_id,fqdn_fwd,fqdn_rev,hostname\n\
+                   192.168.1.20,00:11:22:33:44:55,,4294967295,1704967295,1,0,0,\n";
+        let leases = parse_kea_leases4(old).unwrap();
+        assert_eq!(leases[0].get_expiry(), &INFINITE_EXPIRY);
+        assert_eq!(leases[0].get_state(), BindingState::Active);
+
+        let missing = "address,hwaddr,valid_lifetime,expire\n";
+        let invalid = LeaseError::ParseError(ParseDhcpLeaseError::MissingField);
+        assert_eq!(parse_kea_leases4(missing), Err(StorageError::InvalidLease(1, invalid)));
+        let short = format!("{}\n192.168.1.20,00:11:22:33:44:55\n", content.lines().next().unwrap());
+        let invalid = LeaseError::ParseError(ParseDhcpLeaseError::InvalidFieldsLength);
+        assert_eq!(parse_kea_leases4(&short), Err(StorageError::InvalidLease(2, invalid)));
+        let garbled = format!("{}\n192.168.1.20,00:11:22:33:44:zz,,3600,1700003600,1,0,0,,0,,0\n", content.lines().next().unwrap());
+        let invalid = LeaseError::ParseError(ParseDhcpLeaseError::InvalidMacAddress);
+        assert_eq!(parse_kea_leases4(&garbled), Err(StorageError::InvalidLease(2, invalid)));
+    }
+
+    #[test]
+    fn test_dnsmasq_leases() {
+        use std::time::Duration;
+
+        let content = "\
+1700000000 00:11:22:33:44:55 192.168.1.20 laptop 01:00:11:22:33:44:55
+0 00:11:22:33:44:56 192.168.1.21 * *
+duid 00:01:00:01:2a:3b:4c:5d:00:11:22:33:44:55
+1700000300 12345 2001:db8::20 laptop 00:01:00:01:2a:3b:4c:5d:00:11:22:33:44:55
+1700000300 T54321 2001:db8::21 * *
+";
+        let file = DnsmasqLeases::parse(content).unwrap();
+        let leases = file.get_leases();
+        assert_eq!(leases.len(), 2);
+        assert_eq!(leases[0].get_expiry_secs(), 1700000000);
+        assert_eq!(leases[0].get_mac(), &MacAddress::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x55));
+        assert_eq!(leases[0].get_ip(), &IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)));
+        assert_eq!(leases[0].get_hostname().as_deref(), Some("laptop"));
+        assert_eq!(leases[0].get_chi().as_deref(), Some("01:00:11:22:33:44:55"));
+        // An expiry of 0 is a lease that never ends
+        assert_eq!(leases[1].get_expiry(), &INFINITE_EXPIRY);
+        assert!(!leases[1].is_expired(Duration::from_secs(u32::MAX as u64)));
+        assert_eq!((leases[1].get_hostname(), leases[1].get_chi()), (&None, &None));
+        assert_eq!(file.get_duid().as_deref(), Some("00:01:00:01:2a:3b:4c:5d:00:11:22:33:44:55"));
+        assert_eq!(file.get_leases6().len(), 2);
+        assert_eq!(file.get_leases6()[0].get_iaid(), 12345);
+        assert!(file.get_leases6()[1].is_temporary());
+
+        // Written back line for line
+        assert_eq!(file.to_string(), content);
+        // Client identifiers are stored lowercase, as the server formats them
+        let lease = DhcpLease::from_str("1700000000 00:11:22:33:44:55 192.168.1.20 * 01:AB").unwrap();
+        assert_eq!(lease.get_chi().as_deref(), Some("01:ab"));
+        // A hostname that would break the line is sanitized when it is set,
+        // and written and read back whole
+        let mut lease = lease;
+        lease.set_hostname(Some("John's Laptop".to_string()));
+        assert_eq!(lease.get_hostname().as_deref(), Some("john-s-laptop"));
+        assert_eq!(lease.to_string(), "1700000000 00:11:22:33:44:55 192.168.1.20 john-s-laptop 01:ab");
+        assert_eq!(DhcpLease::from_str(&lease.to_string()).unwrap(), lease);
+        lease.set_hostname(Some("*".to_string()));
+        assert_eq!(lease.get_hostname(), &None);
+        let imported = parse_isc_leases("lease 192.168.1.20 {\n  client-hostname \"my laptop\";\n}\n").unwrap();
+        assert_eq!(imported[0].get_hostname().as_deref(), Some("my-laptop"));
+
+        // Strict: every field is checked
+        let parse_error = |line: &str| match DhcpLease::from_str(line) {
+            Err(LeaseError::ParseError(e)) => Some(e),
+            _ => None,
+        };
+        assert_eq!(parse_error("1700000000 00:11:22:33:44:55 192.168.1.20"), Some(ParseDhcpLeaseError::InvalidFieldsLength));
+        assert_eq!(parse_error("1700000000 00:11:22:33:44:55 ipv4 192.168.1.20 *"), Some(ParseDhcpLeaseError::InvalidIpAddr));
+        assert_eq!(parse_error("soon 00:11:22:33:44:55 192.168.1.20 * *"), Some(ParseDhcpLeaseError::ParseExpiredTimeError));
+        assert_eq!(parse_error("1700000000 00:11:22:33:44 192.168.1.20 * *"), Some(ParseDhcpLeaseError::InvalidMacAddress));
+        assert_eq!(parse_error("1700000000 00:11:22:33:44:55 192.168.1.20 * 1:2"), Some(ParseDhcpLeaseError::InvalidClientId));
+        let twice = "duid 00:01\nduid 00:02\n";
+        let duplicate = LeaseError::ParseError(ParseDhcpLeaseError::DuplicateDuid);
+        assert_eq!(DnsmasqLeases::parse(twice), Err(StorageError::InvalidLease(2, duplicate)));
+        let iaid = "duid 00:01\n1700000300 X1 2001:db8::20 * *\n";
+        let invalid = LeaseError::ParseError(ParseDhcpLeaseError::InvalidIaid);
+        assert_eq!(DnsmasqLeases::parse(iaid), Err(StorageError::InvalidLease(2, invalid)));
     }
 
     #[test]
     fn test_macaddress() {
         // Parse a MAC address from String
         let test_mac_str = "00:11:22:33:44:55";
-        let test_mac = MacAddress::from_str(&test_mac_str).expect("Failed to parse MAC address");
+        let test_mac = MacAddress::from_str(test_mac_str).expect("Failed to parse MAC address");
         println!("Parsed MAC address: {}", test_mac);
 
 